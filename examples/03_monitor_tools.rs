@@ -78,14 +78,12 @@ async fn main() -> anyhow::Result<()> {
                 }
                 println!();
             }
-            Message::System(sys) => {
-                if sys.subtype == "init" {
-                    println!("System initialized");
-                    if let Some(ref session_id) = sys.session_id {
-                        println!("Session ID: {}", session_id);
-                    }
-                    println!();
+            Message::System(sys) if sys.subtype == "init" => {
+                println!("System initialized");
+                if let Some(ref session_id) = sys.session_id {
+                    println!("Session ID: {}", session_id);
                 }
+                println!();
             }
             Message::Result(result) => {
                 println!("\n========================================================");
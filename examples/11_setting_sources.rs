@@ -86,17 +86,15 @@ async fn example_default() -> anyhow::Result<()> {
     let mut stream = client.receive_response();
     while let Some(message) = stream.next().await {
         match message? {
-            Message::System(msg) => {
-                if msg.subtype == "init" {
-                    let commands = extract_slash_commands(&msg);
-                    println!("Available slash commands: {:?}", commands);
-                    if commands.contains(&"commit".to_string()) {
-                        println!("❌ /commit is available (unexpected)");
-                    } else {
-                        println!("✓ /commit is NOT available (expected - no settings loaded)");
-                    }
-                    break;
+            Message::System(msg) if msg.subtype == "init" => {
+                let commands = extract_slash_commands(&msg);
+                println!("Available slash commands: {:?}", commands);
+                if commands.contains(&"commit".to_string()) {
+                    println!("❌ /commit is available (unexpected)");
+                } else {
+                    println!("✓ /commit is NOT available (expected - no settings loaded)");
                 }
+                break;
             }
             Message::Result(_) => break,
             _ => {}
@@ -131,17 +129,15 @@ async fn example_user_only() -> anyhow::Result<()> {
     let mut stream = client.receive_response();
     while let Some(message) = stream.next().await {
         match message? {
-            Message::System(msg) => {
-                if msg.subtype == "init" {
-                    let commands = extract_slash_commands(&msg);
-                    println!("Available slash commands: {:?}", commands);
-                    if commands.contains(&"commit".to_string()) {
-                        println!("❌ /commit is available (unexpected)");
-                    } else {
-                        println!("✓ /commit is NOT available (expected)");
-                    }
-                    break;
+            Message::System(msg) if msg.subtype == "init" => {
+                let commands = extract_slash_commands(&msg);
+                println!("Available slash commands: {:?}", commands);
+                if commands.contains(&"commit".to_string()) {
+                    println!("❌ /commit is available (unexpected)");
+                } else {
+                    println!("✓ /commit is NOT available (expected)");
                 }
+                break;
             }
             Message::Result(_) => break,
             _ => {}
@@ -176,17 +172,15 @@ async fn example_project_and_user() -> anyhow::Result<()> {
     let mut stream = client.receive_response();
     while let Some(message) = stream.next().await {
         match message? {
-            Message::System(msg) => {
-                if msg.subtype == "init" {
-                    let commands = extract_slash_commands(&msg);
-                    println!("Available slash commands: {:?}", commands);
-                    if commands.contains(&"commit".to_string()) {
-                        println!("✓ /commit is available (expected)");
-                    } else {
-                        println!("❌ /commit is NOT available (unexpected)");
-                    }
-                    break;
+            Message::System(msg) if msg.subtype == "init" => {
+                let commands = extract_slash_commands(&msg);
+                println!("Available slash commands: {:?}", commands);
+                if commands.contains(&"commit".to_string()) {
+                    println!("✓ /commit is available (expected)");
+                } else {
+                    println!("❌ /commit is NOT available (unexpected)");
                 }
+                break;
             }
             Message::Result(_) => break,
             _ => {}
@@ -321,34 +321,24 @@ async fn example_control_protocol() -> Result<(), Box<dyn std::error::Error>> {
 
     if let Some(info) = server_info {
         println!("✓ Server info retrieved successfully!");
-        if let Some(commands) = info.get("commands")
-            && let Some(commands_array) = commands.as_array()
-        {
-            println!("  - Available commands: {}", commands_array.len());
-        }
-        if let Some(output_style) = info.get("output_style") {
+        println!("  - Available commands: {}", info.commands.len());
+        if let Some(output_style) = &info.output_style {
             println!("  - Output style: {}", output_style);
         }
 
         // Show available output styles if present
-        if let Some(styles) = info.get("available_output_styles")
-            && let Some(styles_array) = styles.as_array()
-        {
-            let style_names: Vec<_> = styles_array.iter().filter_map(|s| s.as_str()).collect();
-            println!("  - Available output styles: {}", style_names.join(", "));
+        if !info.available_output_styles.is_empty() {
+            println!(
+                "  - Available output styles: {}",
+                info.available_output_styles.join(", ")
+            );
         }
 
         // Show a few example commands
-        if let Some(commands) = info.get("commands")
-            && let Some(commands_array) = commands.as_array()
-        {
+        if !info.commands.is_empty() {
             println!("  - Example commands:");
-            for cmd in commands_array.iter().take(5) {
-                if let Some(name) = cmd.get("name")
-                    && let Some(name_str) = name.as_str()
-                {
-                    println!("    • {}", name_str);
-                }
+            for cmd in info.commands.iter().take(5) {
+                println!("    • {}", cmd.name);
             }
         }
     } else {
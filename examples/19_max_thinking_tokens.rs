@@ -82,7 +82,18 @@ async fn main() -> anyhow::Result<()> {
 
                 if let Some(ref usage) = result.usage {
                     println!("\nToken Usage:");
-                    println!("{}", serde_json::to_string_pretty(usage)?);
+                    println!("  Input: {}", usage.input_tokens);
+                    println!("  Output: {}", usage.output_tokens);
+                    println!("  Cache read: {}", usage.cache_read_input_tokens);
+                    println!("  Cache write: {}", usage.cache_creation_input_tokens);
+                }
+                for (model, usage) in &result.model_usage {
+                    println!(
+                        "  {}: {} tokens, ${:.4}",
+                        model,
+                        usage.total_tokens(),
+                        usage.cost_usd
+                    );
                 }
             }
             _ => {}
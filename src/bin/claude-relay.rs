@@ -0,0 +1,142 @@
+//! Relay a Claude Code CLI session over TCP or a Unix domain socket
+//!
+//! Run this next to the CLI and connect to it with `SocketTransport`:
+//!
+//! ```text
+//! claude-relay --listen tcp://0.0.0.0:7878 --token "$SECRET" --permission-prompt-tool stdio
+//! ```
+//!
+//! CLI flags that depend on the remote client's options must be given here,
+//! since the relay starts the CLI: `--permission-prompt-tool stdio` routes
+//! permission prompts to a remote `can_use_tool` callback, and `--mcp-config`
+//! should declare SDK MCP servers (`{"type": "sdk"}`) hosted by the client.
+
+use claude_agent_sdk_rs::relay::{RelayConfig, RelayServer};
+use claude_agent_sdk_rs::{ClaudeAgentOptions, McpServers, PermissionMode, RelayEndpoint};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: claude-relay --listen <tcp://HOST:PORT | unix:///PATH> [OPTIONS] [-- CLI_ARGS...]
+
+Options:
+  --listen <ENDPOINT>               Address to listen on
+  --token <TOKEN>                   Auth token clients must present (or CLAUDE_RELAY_TOKEN)
+  --cli-path <PATH>                 Path to the Claude Code CLI
+  --cwd <DIR>                       Working directory for the CLI
+  --model <MODEL>                   Model to use
+  --permission-mode <MODE>          default, acceptEdits, plan or bypassPermissions
+  --permission-prompt-tool <NAME>   Permission prompt tool (use `stdio` for remote can_use_tool)
+  --mcp-config <PATH>               MCP server configuration file
+  -h, --help                        Print this help
+
+Arguments after `--` are passed to the CLI as extra flags (`--flag value` or `--flag`).";
+
+struct Args {
+    endpoint: RelayEndpoint,
+    token: Option<String>,
+    options: ClaudeAgentOptions,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut endpoint = None;
+    let mut token = std::env::var("CLAUDE_RELAY_TOKEN").ok();
+    let mut options = ClaudeAgentOptions::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("missing value for {}", name))
+        };
+        match arg.as_str() {
+            "--listen" => {
+                endpoint = Some(
+                    value("--listen")?
+                        .parse::<RelayEndpoint>()
+                        .map_err(|e| e.to_string())?,
+                )
+            }
+            "--token" => token = Some(value("--token")?),
+            "--cli-path" => options.cli_path = Some(PathBuf::from(value("--cli-path")?)),
+            "--cwd" => options.cwd = Some(PathBuf::from(value("--cwd")?)),
+            "--model" => options.model = Some(value("--model")?),
+            "--permission-mode" => {
+                let mode = value("--permission-mode")?;
+                options.permission_mode = Some(
+                    serde_json::from_value::<PermissionMode>(serde_json::Value::String(
+                        mode.clone(),
+                    ))
+                    .map_err(|_| format!("invalid permission mode '{}'", mode))?,
+                );
+            }
+            "--permission-prompt-tool" => {
+                options.permission_prompt_tool_name = Some(value("--permission-prompt-tool")?)
+            }
+            "--mcp-config" => {
+                options.mcp_servers = McpServers::Path(PathBuf::from(value("--mcp-config")?))
+            }
+            "-h" | "--help" => return Err(String::new()),
+            "--" => {
+                options.extra_args = parse_extra_args(args.by_ref().collect())?;
+                break;
+            }
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
+    }
+
+    Ok(Args {
+        endpoint: endpoint.ok_or("--listen is required")?,
+        token,
+        options,
+    })
+}
+
+/// Turn `--flag value --switch` into extra CLI args
+fn parse_extra_args(raw: Vec<String>) -> Result<HashMap<String, Option<String>>, String> {
+    let mut extra = HashMap::new();
+    let mut iter = raw.into_iter().peekable();
+    while let Some(arg) = iter.next() {
+        let flag = arg
+            .strip_prefix("--")
+            .ok_or_else(|| format!("expected a --flag after `--`, got '{}'", arg))?;
+        let value = iter.next_if(|next| !next.starts_with("--"));
+        extra.insert(flag.to_string(), value);
+    }
+    Ok(extra)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) if message.is_empty() => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let config = match args.token {
+        Some(token) => RelayConfig::builder().auth_token(token).build(),
+        None => {
+            eprintln!("warning: no --token given, any client that can reach the relay may connect");
+            RelayConfig::default()
+        }
+    };
+
+    match RelayServer::new(args.options, config)
+        .serve(&args.endpoint)
+        .await
+    {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -4,17 +4,26 @@ use futures::stream::Stream;
 use std::collections::HashMap;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
 
-use crate::errors::{ClaudeError, Result};
+use crate::errors::{ClaudeError, ControlErrorKind, Result};
 use crate::internal::message_parser::MessageParser;
+use crate::internal::message_queue::QueueMetrics;
 use crate::internal::query_full::QueryFull;
+use crate::internal::runtime_hooks::RuntimeHooks;
+use crate::internal::supervisor;
 use crate::internal::transport::subprocess::QueryPrompt;
-use crate::internal::transport::{SubprocessTransport, Transport};
-use crate::types::config::{ClaudeAgentOptions, PermissionMode};
+use crate::internal::transport::{ShutdownStage, SubprocessTransport, Transport, tap_transport};
+use crate::types::capabilities::Capabilities;
+use crate::types::config::{ClaudeAgentOptions, PermissionMode, RestartPolicy};
 use crate::types::efficiency::{build_efficiency_hooks, merge_hooks};
-use crate::types::hooks::{HookEvent, HookMatcher};
-use crate::types::mcp::McpSdkServerConfig;
+use crate::types::hooks::{HookEvent, HookHandle, HookMatcher};
+use crate::types::mcp::{McpSdkServerConfig, McpServerConfig, McpServerStatus};
 use crate::types::messages::{Message, UserContentBlock};
+use crate::types::permissions::CanUseToolCallback;
+use crate::types::sandbox::SandboxStatus;
+use crate::types::server_info::ServerInfo;
 
 /// Client for bidirectional streaming interactions with Claude
 ///
@@ -58,9 +67,10 @@ pub struct ClaudeClient {
     /// Shutdown receiver - signals when background task completes
     shutdown_rx: Option<tokio::sync::oneshot::Receiver<()>>,
     connected: bool,
-    /// Custom transport for testing (feature-gated)
-    #[cfg(feature = "testing")]
-    _custom_transport: Option<Arc<dyn Transport>>,
+    /// Custom transport supplied via `with_transport`, consumed on connect
+    custom_transport: Option<Arc<dyn Transport>>,
+    /// Hooks added with `add_hook`; outlives the connection so restarts keep them
+    runtime_hooks: Arc<RuntimeHooks>,
 }
 
 impl ClaudeClient {
@@ -83,8 +93,8 @@ impl ClaudeClient {
             query: None,
             shutdown_rx: None,
             connected: false,
-            #[cfg(feature = "testing")]
-            _custom_transport: None,
+            custom_transport: None,
+            runtime_hooks: Arc::default(),
         }
     }
 
@@ -127,7 +137,12 @@ impl ClaudeClient {
             .unwrap_or_default();
 
         // Merge user hooks with efficiency hooks
-        let merged_hooks = merge_hooks(self.options.hooks.clone(), efficiency_hooks);
+        let mut merged_hooks = merge_hooks(self.options.hooks.clone(), efficiency_hooks);
+
+        // Gates that run hooks added after connect
+        if self.options.runtime_hooks {
+            merged_hooks = merge_hooks(merged_hooks, self.runtime_hooks.gates());
+        }
 
         // Convert hooks to internal format
         merged_hooks.as_ref().map(|hooks_map| {
@@ -158,27 +173,65 @@ impl ClaudeClient {
     /// Common setup for QueryFull after transport is connected
     ///
     /// This handles the common initialization logic shared between
-    /// `connect()` and `connect_with_transport()`.
-    async fn setup_query(&mut self, mut query: QueryFull, initialize: bool) -> Result<()> {
+    /// `connect()` and `connect_with_transport()`. With a restart policy, the
+    /// CLI is supervised and respawned if it exits unexpectedly.
+    async fn setup_query(
+        &mut self,
+        mut query: QueryFull,
+        initialize: bool,
+        restart_policy: Option<RestartPolicy>,
+    ) -> Result<()> {
         // Extract SDK MCP servers from options
         let sdk_mcp_servers = self.extract_sdk_mcp_servers();
         query.set_sdk_mcp_servers(sdk_mcp_servers);
 
         // Pass can_use_tool callback to query
         query.set_can_use_tool(self.options.can_use_tool.clone());
+        query.set_control_request_timeout(self.options.control_request_timeout);
+
+        query.set_message_queue(
+            self.options.message_queue_capacity,
+            self.options.overflow_policy,
+        );
 
         // Build hooks configuration
         let hooks = self.build_hooks_config();
 
-        // Start reading messages in background
-        let shutdown_rx = query.start().await?;
+        let started = async {
+            // Start reading messages in background
+            let shutdown_rx = query.start().await?;
 
-        // Initialize with hooks if requested
-        if initialize {
-            query.initialize(hooks).await?;
+            // Initialize with hooks if requested
+            if initialize {
+                query.initialize(hooks.clone()).await?;
+                // Custom transports are only checked once they report a version here
+                query.capabilities().check_options(&self.options)?;
+            }
+            Ok(shutdown_rx)
         }
+        .await;
+        // Don't leave the transport connected and the reader running on failure
+        let shutdown_rx = match started {
+            Ok(shutdown_rx) => shutdown_rx,
+            Err(e) => {
+                query.abandon().await;
+                return Err(e);
+            }
+        };
 
-        self.query = Some(Arc::new(query));
+        let query = Arc::new(query);
+        let shutdown_rx = match restart_policy {
+            Some(policy) => supervisor::spawn(
+                Arc::clone(&query),
+                shutdown_rx,
+                self.options.clone(),
+                hooks,
+                policy,
+            ),
+            None => shutdown_rx,
+        };
+
+        self.query = Some(query);
         self.shutdown_rx = Some(shutdown_rx);
         self.connected = true;
 
@@ -219,15 +272,21 @@ impl ClaudeClient {
             query: None,
             shutdown_rx: None,
             connected: false,
-            #[cfg(feature = "testing")]
-            _custom_transport: None,
+            custom_transport: None,
+            runtime_hooks: Arc::default(),
         })
     }
 
-    /// Create a client with a custom transport (for testing)
+    /// Create a client with a custom transport
+    ///
+    /// The transport replaces the default [`SubprocessTransport`], so the CLI can be
+    /// launched by other means (a job runner, a container, a remote relay). Calling
+    /// [`connect()`](Self::connect) connects the transport and runs the full
+    /// `initialize` handshake, registering hooks and SDK MCP servers from `options`.
     ///
-    /// This method allows injecting a mock transport for testing purposes,
-    /// enabling deterministic tests without requiring the Claude Code CLI.
+    /// The transport is responsible for starting the CLI with the arguments it needs.
+    /// CLI flags derived from `options` (model, permission mode, `--input-format stream-json`,
+    /// `--permission-prompt-tool stdio` for `can_use_tool`, ...) are not applied.
     ///
     /// # Arguments
     ///
@@ -237,45 +296,48 @@ impl ClaudeClient {
     /// # Example
     ///
     /// ```no_run
-    /// # #[cfg(feature = "testing")]
-    /// # {
-    /// use claude_agent_sdk_rs::{ClaudeClient, ClaudeAgentOptions};
-    /// use claude_agent_sdk_rs::testing::MockTransport;
+    /// use claude_agent_sdk_rs::{ClaudeAgentOptions, ClaudeClient, QueryPrompt, SubprocessTransport};
     /// use std::sync::Arc;
     ///
-    /// let transport = Arc::new(MockTransport::builder().build());
-    /// let client = ClaudeClient::with_transport(transport, ClaudeAgentOptions::default());
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let options = ClaudeAgentOptions::default();
+    /// let transport = SubprocessTransport::new_async(QueryPrompt::Streaming, options.clone()).await?;
+    /// let mut client = ClaudeClient::with_transport(Arc::new(transport), options);
+    /// client.connect().await?;
+    /// # Ok(())
     /// # }
     /// ```
-    #[cfg(feature = "testing")]
     pub fn with_transport(transport: Arc<dyn Transport>, options: ClaudeAgentOptions) -> Self {
         Self {
             options,
             query: None,
             shutdown_rx: None,
             connected: false,
-            // Store the transport for use in connect_with_transport
-            _custom_transport: Some(transport),
+            custom_transport: Some(transport),
+            runtime_hooks: Arc::default(),
         }
     }
 
-    /// Connect with a pre-configured transport (for testing)
+    /// Connect with a pre-configured mock transport (for testing)
     ///
     /// This method uses the mock transport for testing purposes, skipping
     /// the initialization step since there's no real CLI to communicate with.
+    /// Use [`connect()`](Self::connect) for transports backed by a real CLI.
     #[cfg(feature = "testing")]
     pub async fn connect_with_transport(&mut self) -> Result<()> {
         if self.connected {
             return Ok(());
         }
 
-        let transport = self._custom_transport.take().ok_or_else(|| {
+        let transport = self.custom_transport.take().ok_or_else(|| {
             ClaudeError::InvalidConfig(
                 "No custom transport configured. Use with_transport() first.".to_string(),
             )
         })?;
 
         // Connect the transport
+        let transport = tap_transport(transport, &self.options);
         transport.connect().await?;
 
         // Create Query with the custom transport
@@ -283,18 +345,24 @@ impl ClaudeClient {
 
         // Use common setup, but skip initialization for mock transport
         // (mock transport doesn't have a real CLI to initialize)
-        self.setup_query(query, false).await
+        self.setup_query(query, false, None).await
     }
 
     /// Connect to Claude (analogous to Python's __aenter__)
     ///
     /// This establishes the connection to the Claude Code CLI and initializes
-    /// the bidirectional communication channel.
+    /// the bidirectional communication channel. If the client was created with
+    /// [`with_transport()`](Self::with_transport), that transport is used instead
+    /// of spawning the CLI.
+    ///
+    /// A Landlock sandbox that is not fully enforced is logged as a warning; use
+    /// [`sandbox_status()`](Self::sandbox_status) to check the enforcement level.
     ///
     /// # Errors
     ///
     /// Returns an error if:
     /// - Claude CLI cannot be found or started
+    /// - A [`LandlockMode::Strict`](crate::LandlockMode::Strict) sandbox can't be fully enforced
     /// - The initialization handshake fails
     /// - Hook registration fails
     pub async fn connect(&mut self) -> Result<()> {
@@ -302,26 +370,37 @@ impl ClaudeClient {
             return Ok(());
         }
 
+        if let Some(transport) = self.custom_transport.take() {
+            if self.options.restart_policy.is_some() {
+                warn!("restart_policy is ignored for custom transports");
+            }
+            let transport = tap_transport(transport, &self.options);
+            transport.connect().await?;
+            let query = QueryFull::new_with_transport(transport);
+            return self.setup_query(query, true, None).await;
+        }
+
         // When can_use_tool is configured, route permission prompts through
         // the stdio control protocol so the CLI sends can_use_tool requests
-        if self.options.can_use_tool.is_some()
-            && self.options.permission_prompt_tool_name.is_none()
+        if self.options.can_use_tool.is_some() && self.options.permission_prompt_tool_name.is_none()
         {
             self.options.permission_prompt_tool_name = Some("stdio".to_string());
         }
 
         // Create transport in streaming mode (no initial prompt)
         let prompt = QueryPrompt::Streaming;
-        let transport = SubprocessTransport::new(prompt, self.options.clone())?;
+        let transport = SubprocessTransport::new_async(prompt, self.options.clone()).await?;
 
         // Don't send initial prompt - we'll use query() for that
         transport.connect().await?;
 
         // Create Query with hooks
-        let query = QueryFull::new(Box::new(transport));
+        let query =
+            QueryFull::new_with_transport(tap_transport(Arc::new(transport), &self.options));
 
         // Use common setup with initialization enabled
-        self.setup_query(query, true).await
+        let restart_policy = self.options.restart_policy.clone();
+        self.setup_query(query, true, restart_policy).await
     }
 
     /// Send a query to Claude
@@ -408,7 +487,7 @@ impl ClaudeClient {
         })?;
 
         // Write via transport - stdin/stdout have separate locks, no deadlock
-        query.transport.write(&message_str).await?;
+        query.transport().write(&message_str).await?;
 
         Ok(())
     }
@@ -518,7 +597,7 @@ impl ClaudeClient {
         })?;
 
         // Write via transport - stdin/stdout have separate locks, no deadlock
-        query.transport.write(&message_str).await?;
+        query.transport().write(&message_str).await?;
 
         Ok(())
     }
@@ -566,11 +645,22 @@ impl ClaudeClient {
 
         Box::pin(async_stream::stream! {
             // Clone the receiver - flume receivers are cloneable and lock-free
-            // No outer lock needed - message_rx is already accessible via Arc
-            let rx = query.message_rx.clone();
+            let rx = query.message_receiver();
 
             // No mutex needed - flume receiver is lock-free
-            while let Ok(json) = rx.recv_async().await {
+            while let Ok(item) = rx.recv_async().await {
+                // A queue overflow ends the stream; errors for single messages don't
+                let json = match item {
+                    Ok(json) => json,
+                    Err(e @ ClaudeError::QueueOverflow(_)) => {
+                        yield Err(e);
+                        break;
+                    }
+                    Err(e) => {
+                        yield Err(e);
+                        continue;
+                    }
+                };
                 match MessageParser::parse(json) {
                     Ok(msg) => yield Ok(msg),
                     Err(e) => yield Err(e),
@@ -629,11 +719,22 @@ impl ClaudeClient {
 
         Box::pin(async_stream::stream! {
             // Clone the receiver - flume receivers are cloneable and lock-free
-            // No outer lock needed - message_rx is already accessible via Arc
-            let rx = query.message_rx.clone();
+            let rx = query.message_receiver();
 
             // No mutex needed - flume receiver is lock-free
-            while let Ok(json) = rx.recv_async().await {
+            while let Ok(item) = rx.recv_async().await {
+                // A queue overflow ends the stream; errors for single messages don't
+                let json = match item {
+                    Ok(json) => json,
+                    Err(e @ ClaudeError::QueueOverflow(_)) => {
+                        yield Err(e);
+                        break;
+                    }
+                    Err(e) => {
+                        yield Err(e);
+                        continue;
+                    }
+                };
                 match MessageParser::parse(json) {
                     Ok(msg) => {
                         let is_result = matches!(msg, Message::Result(_));
@@ -654,13 +755,24 @@ impl ClaudeClient {
     ///
     /// # Errors
     ///
-    /// Returns an error if the client is not connected or if sending fails.
+    /// Returns an error if the client is not connected or if sending fails. Returns a
+    /// [`ControlProtocol`](ClaudeError::ControlProtocol) error if the CLI answers with an
+    /// error or not within [`ClaudeAgentOptions::control_request_timeout`].
     pub async fn interrupt(&self) -> Result<()> {
         let query = self.query.as_ref().ok_or_else(|| {
             ClaudeError::InvalidConfig("Client not connected. Call connect() first.".to_string())
         })?;
 
-        query.interrupt().await
+        query.interrupt(None).await
+    }
+
+    /// Like [`interrupt`](Self::interrupt), waiting at most `timeout` for the CLI to answer
+    pub async fn interrupt_with_timeout(&self, timeout: Duration) -> Result<()> {
+        let query = self.query.as_ref().ok_or_else(|| {
+            ClaudeError::InvalidConfig("Client not connected. Call connect() first.".to_string())
+        })?;
+
+        query.interrupt(Some(timeout)).await
     }
 
     /// Change the permission mode dynamically
@@ -673,13 +785,112 @@ impl ClaudeClient {
     ///
     /// # Errors
     ///
-    /// Returns an error if the client is not connected or if sending fails.
+    /// Returns an error if the client is not connected or if sending fails. Returns a
+    /// [`ControlProtocol`](ClaudeError::ControlProtocol) error if the CLI answers with an
+    /// error or not within [`ClaudeAgentOptions::control_request_timeout`].
     pub async fn set_permission_mode(&self, mode: PermissionMode) -> Result<()> {
         let query = self.query.as_ref().ok_or_else(|| {
             ClaudeError::InvalidConfig("Client not connected. Call connect() first.".to_string())
         })?;
 
-        query.set_permission_mode(mode).await
+        query.set_permission_mode(mode, None).await
+    }
+
+    /// Like [`set_permission_mode`](Self::set_permission_mode), waiting at most `timeout`
+    /// for the CLI to answer
+    pub async fn set_permission_mode_with_timeout(
+        &self,
+        mode: PermissionMode,
+        timeout: Duration,
+    ) -> Result<()> {
+        let query = self.query.as_ref().ok_or_else(|| {
+            ClaudeError::InvalidConfig("Client not connected. Call connect() first.".to_string())
+        })?;
+
+        query.set_permission_mode(mode, Some(timeout)).await
+    }
+
+    /// Replace the [`can_use_tool`](ClaudeAgentOptions::can_use_tool) callback
+    ///
+    /// Permission requests received afterwards go to `callback`; requests already
+    /// being handled finish with the old one. With `None`, tool use is allowed, as
+    /// when no callback is configured. Before connect, this sets the option.
+    ///
+    /// The CLI only asks the SDK for permission if it was started with
+    /// `--permission-prompt-tool stdio`, which `connect()` does when `can_use_tool` is
+    /// set. To start without a callback and add one later, set
+    /// [`permission_prompt_tool_name`](ClaudeAgentOptions::permission_prompt_tool_name)
+    /// to `"stdio"`.
+    pub fn set_can_use_tool(&mut self, callback: Option<CanUseToolCallback>) {
+        if let Some(query) = &self.query {
+            query.set_can_use_tool(callback.clone());
+        }
+        self.options.can_use_tool = callback;
+    }
+
+    /// Switch the permission mode and the `can_use_tool` callback together
+    ///
+    /// The callback is installed before the mode change is sent, so no permission
+    /// request in between is decided by the old callback. If the CLI rejects the mode,
+    /// the old callback is restored.
+    ///
+    /// If the request times out or the connection drops, the CLI may already have
+    /// switched modes, so the new callback is left installed. Call this again, or
+    /// [`set_can_use_tool`](Self::set_can_use_tool), to settle on a policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the client is not connected, or as
+    /// [`set_permission_mode`](Self::set_permission_mode).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use claude_agent_sdk_rs::{ClaudeClient, ClaudeAgentOptions, PermissionMode};
+    /// # use claude_agent_sdk_rs::{PermissionResult, PermissionResultDeny};
+    /// # use std::sync::Arc;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = ClaudeClient::new(ClaudeAgentOptions::default());
+    /// # client.connect().await?;
+    /// // The conversation touched production configs: ask a human from now on
+    /// client
+    ///     .set_permission_policy(
+    ///         PermissionMode::Default,
+    ///         Some(Arc::new(|tool_name, _input, _context| {
+    ///             Box::pin(async move {
+    ///                 println!("Approval needed for {}", tool_name);
+    ///                 PermissionResult::Deny(PermissionResultDeny {
+    ///                     message: "Waiting for human approval".to_string(),
+    ///                     interrupt: false,
+    ///                 })
+    ///             })
+    ///         })),
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_permission_policy(
+        &mut self,
+        mode: PermissionMode,
+        callback: Option<CanUseToolCallback>,
+    ) -> Result<()> {
+        let query = self.query.as_ref().ok_or_else(|| {
+            ClaudeError::InvalidConfig("Client not connected. Call connect() first.".to_string())
+        })?;
+
+        let previous = query.set_can_use_tool(callback.clone());
+        let result = query.set_permission_mode(mode, None).await;
+        // Only a rejection means the mode is unchanged
+        if let Err(ClaudeError::ControlProtocol(e)) = &result
+            && e.kind == ControlErrorKind::Cli
+        {
+            query.set_can_use_tool(previous);
+            return result;
+        }
+        self.options.can_use_tool = callback;
+        result
     }
 
     /// Change the AI model dynamically
@@ -692,13 +903,28 @@ impl ClaudeClient {
     ///
     /// # Errors
     ///
-    /// Returns an error if the client is not connected or if sending fails.
+    /// Returns an error if the client is not connected or if sending fails. Returns a
+    /// [`ControlProtocol`](ClaudeError::ControlProtocol) error if the CLI answers with an
+    /// error or not within [`ClaudeAgentOptions::control_request_timeout`].
     pub async fn set_model(&self, model: Option<&str>) -> Result<()> {
         let query = self.query.as_ref().ok_or_else(|| {
             ClaudeError::InvalidConfig("Client not connected. Call connect() first.".to_string())
         })?;
 
-        query.set_model(model).await
+        query.set_model(model, None).await
+    }
+
+    /// Like [`set_model`](Self::set_model), waiting at most `timeout` for the CLI to answer
+    pub async fn set_model_with_timeout(
+        &self,
+        model: Option<&str>,
+        timeout: Duration,
+    ) -> Result<()> {
+        let query = self.query.as_ref().ok_or_else(|| {
+            ClaudeError::InvalidConfig("Client not connected. Call connect() first.".to_string())
+        })?;
+
+        query.set_model(model, Some(timeout)).await
     }
 
     /// Rewind tracked files to their state at a specific user message.
@@ -718,7 +944,9 @@ impl ClaudeClient {
     ///
     /// # Errors
     ///
-    /// Returns an error if the client is not connected or if sending fails.
+    /// Returns an error if the client is not connected or if sending fails. Returns a
+    /// [`ControlProtocol`](ClaudeError::ControlProtocol) error if the CLI answers with an
+    /// error or not within [`ClaudeAgentOptions::control_request_timeout`].
     ///
     /// # Example
     ///
@@ -760,21 +988,175 @@ impl ClaudeClient {
             ClaudeError::InvalidConfig("Client not connected. Call connect() first.".to_string())
         })?;
 
-        query.rewind_files(user_message_id).await
+        query.rewind_files(user_message_id, None).await
+    }
+
+    /// Like [`rewind_files`](Self::rewind_files), waiting at most `timeout` for the CLI
+    /// to answer
+    pub async fn rewind_files_with_timeout(
+        &self,
+        user_message_id: &str,
+        timeout: Duration,
+    ) -> Result<()> {
+        let query = self.query.as_ref().ok_or_else(|| {
+            ClaudeError::InvalidConfig("Client not connected. Call connect() first.".to_string())
+        })?;
+
+        query.rewind_files(user_message_id, Some(timeout)).await
+    }
+
+    /// Add a hook without restarting the CLI
+    ///
+    /// Requires [`ClaudeAgentOptions::runtime_hooks`]. The hook takes effect on the next
+    /// `event`, can be added before or after connecting, and survives supervised
+    /// restarts. `matcher.matcher` is matched against the tool name (the trigger for
+    /// `PreCompact`) the way the CLI matches it: `*` or no pattern matches everything,
+    /// `Edit|Write` lists exact names, and anything else is a regular expression such
+    /// as `mcp__github__.*`.
+    ///
+    /// Runtime hooks for an event run in the order they were added; the first
+    /// non-empty output is returned to the CLI. They share the CLI's 60 second hook
+    /// timeout, and `matcher.timeout` bounds each matcher's hooks on the SDK side.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `runtime_hooks` is not enabled, or if `matcher.matcher` is
+    /// not a valid regular expression.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use claude_agent_sdk_rs::{ClaudeClient, ClaudeAgentOptions, HookEvent, HookMatcher};
+    /// # use claude_agent_sdk_rs::{HookJsonOutput, SyncHookJsonOutput};
+    /// # use std::sync::Arc;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let options = ClaudeAgentOptions::builder().runtime_hooks(true).build();
+    /// let mut client = ClaudeClient::new(options);
+    /// client.connect().await?;
+    ///
+    /// // Entering a sensitive phase: audit every shell command
+    /// let audit = client.add_hook(
+    ///     HookEvent::PreToolUse,
+    ///     HookMatcher::builder()
+    ///         .matcher("Bash")
+    ///         .hooks(vec![Arc::new(|input, _, _| {
+    ///             Box::pin(async move {
+    ///                 println!("audit: {:?}", input);
+    ///                 HookJsonOutput::Sync(SyncHookJsonOutput::default())
+    ///             })
+    ///         })])
+    ///         .build(),
+    /// )?;
+    /// client.query("Rotate the database credentials").await?;
+    ///
+    /// client.remove_hook(audit);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_hook(&self, event: HookEvent, matcher: HookMatcher) -> Result<HookHandle> {
+        if !self.options.runtime_hooks {
+            return Err(ClaudeError::InvalidConfig(
+                "Adding hooks after connect requires ClaudeAgentOptions::runtime_hooks".to_string(),
+            ));
+        }
+        self.runtime_hooks.add(event, matcher)
+    }
+
+    /// Remove a hook added with [`add_hook`](Self::add_hook)
+    ///
+    /// Returns `false` if the hook was already removed. A hook that is running
+    /// finishes; it is not called again.
+    pub fn remove_hook(&self, handle: HookHandle) -> bool {
+        self.runtime_hooks.remove(handle)
+    }
+
+    /// Add an MCP server to the running CLI
+    ///
+    /// Works for in-process SDK servers as well as stdio, SSE and HTTP ones. Adding
+    /// a name that was already added at runtime replaces that server. Servers added
+    /// this way are restored after supervised restarts.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the client is not connected, if `name` is an SDK server
+    /// from [`ClaudeAgentOptions::mcp_servers`], or if the CLI rejects the server.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use claude_agent_sdk_rs::{ClaudeClient, ClaudeAgentOptions, McpServerConfig};
+    /// # use claude_agent_sdk_rs::create_sdk_mcp_server;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = ClaudeClient::new(ClaudeAgentOptions::default());
+    /// # client.connect().await?;
+    /// let server = create_sdk_mcp_server("calculator", "1.0.0", vec![]);
+    /// client
+    ///     .add_mcp_server("calculator", McpServerConfig::Sdk(server))
+    ///     .await?;
+    ///
+    /// for server in client.mcp_status().await? {
+    ///     println!("{}: {:?}", server.name, server.status);
+    /// }
+    ///
+    /// client.remove_mcp_server("calculator").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn add_mcp_server(
+        &self,
+        name: impl Into<String>,
+        config: McpServerConfig,
+    ) -> Result<()> {
+        let query = self.query.as_ref().ok_or_else(|| {
+            ClaudeError::InvalidConfig("Client not connected. Call connect() first.".to_string())
+        })?;
+
+        query.add_mcp_server(name.into(), config).await
+    }
+
+    /// Remove an MCP server added with [`add_mcp_server`](Self::add_mcp_server)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the client is not connected, if `name` was not added at
+    /// runtime, or if the CLI rejects the change.
+    pub async fn remove_mcp_server(&self, name: &str) -> Result<()> {
+        let query = self.query.as_ref().ok_or_else(|| {
+            ClaudeError::InvalidConfig("Client not connected. Call connect() first.".to_string())
+        })?;
+
+        query.remove_mcp_server(name).await
+    }
+
+    /// Connection state of every MCP server the CLI knows about
+    ///
+    /// Covers servers from options and those added at runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the client is not connected or the request fails.
+    pub async fn mcp_status(&self) -> Result<Vec<McpServerStatus>> {
+        let query = self.query.as_ref().ok_or_else(|| {
+            ClaudeError::InvalidConfig("Client not connected. Call connect() first.".to_string())
+        })?;
+
+        query.mcp_status().await
     }
 
     /// Get server initialization info including available commands and output styles
     ///
     /// Returns initialization information from the Claude Code server including:
-    /// - Available commands (slash commands, system commands, etc.)
+    /// - Available slash commands
     /// - Current and available output styles
-    /// - Server capabilities
+    /// - Available models and account info
     ///
     /// This is analogous to Python's `client.get_server_info()`.
     ///
     /// # Returns
     ///
-    /// Dictionary with server info, or None if not connected
+    /// Server info, or None if not connected
     ///
     /// # Example
     ///
@@ -785,17 +1167,64 @@ impl ClaudeClient {
     /// # let mut client = ClaudeClient::new(ClaudeAgentOptions::default());
     /// # client.connect().await?;
     /// if let Some(info) = client.get_server_info() {
-    ///     println!("Commands available: {}", info.get("commands").map(|c| c.as_array().map(|a| a.len()).unwrap_or(0)).unwrap_or(0));
-    ///     println!("Output style: {:?}", info.get("output_style"));
+    ///     println!("Commands available: {}", info.commands.len());
+    ///     println!("Output style: {:?}", info.output_style);
+    ///     if info.supports_command("compact") {
+    ///         client.query("/compact").await?;
+    ///     }
     /// }
     /// # Ok(())
     /// # }
     /// ```
-    pub fn get_server_info(&self) -> Option<serde_json::Value> {
+    pub fn get_server_info(&self) -> Option<ServerInfo> {
         let query = self.query.as_ref()?;
         query.get_initialization_result()
     }
 
+    /// Get what the connected CLI supports, or None if not connected
+    ///
+    /// Worked out from the CLI version and the `initialize` response. `connect()`
+    /// already fails with [`ClaudeError::UnsupportedCapability`] if the options need
+    /// something the CLI lacks; this is for deciding which calls to make afterwards.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use claude_agent_sdk_rs::{Capability, ClaudeClient, ClaudeAgentOptions};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = ClaudeClient::new(ClaudeAgentOptions::default());
+    /// # client.connect().await?;
+    /// if client
+    ///     .capabilities()
+    ///     .is_some_and(|capabilities| capabilities.supports(Capability::FileCheckpointing))
+    /// {
+    ///     client.rewind_files("user-message-id").await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn capabilities(&self) -> Option<Capabilities> {
+        Some(self.query.as_ref()?.capabilities())
+    }
+
+    /// Get metrics for the queue feeding the message streams
+    ///
+    /// Use this to spot consumers that fall behind the CLI; see
+    /// [`ClaudeAgentOptions::message_queue_capacity`]. Returns `None` if not connected.
+    pub fn queue_metrics(&self) -> Option<QueueMetrics> {
+        Some(self.query.as_ref()?.queue_metrics())
+    }
+
+    /// OS-level sandboxing in effect for the connected CLI
+    ///
+    /// Reports how much of the [`landlock_sandbox`](ClaudeAgentOptions::landlock_sandbox)
+    /// policy the kernel enforces, so callers can refuse to proceed on hosts without
+    /// working Landlock. Returns `None` if not connected.
+    pub fn sandbox_status(&self) -> Option<SandboxStatus> {
+        Some(self.query.as_ref()?.transport().sandbox_status())
+    }
+
     /// Start a new session by switching to a different session ID
     ///
     /// This is a convenience method that creates a new conversation context.
@@ -839,23 +1268,29 @@ impl ClaudeClient {
 
     /// Disconnect from Claude (analogous to Python's __aexit__)
     ///
-    /// This cleanly shuts down the connection to Claude Code CLI.
+    /// This cleanly shuts down the connection to Claude Code CLI. Stdin is closed
+    /// first; if the CLI does not exit, its process group is terminated according to
+    /// [`ClaudeAgentOptions::shutdown_policy`]. The returned [`ShutdownStage`] tells
+    /// how far that had to escalate.
     ///
     /// # Errors
     ///
     /// Returns an error if disconnection fails.
-    pub async fn disconnect(&mut self) -> Result<()> {
+    pub async fn disconnect(&mut self) -> Result<ShutdownStage> {
         if !self.connected {
-            return Ok(());
+            return Ok(ShutdownStage::Graceful);
         }
 
         // Mark as disconnected early to prevent Drop warning if close() fails
         self.connected = false;
 
         if let Some(query) = self.query.take() {
+            // Tell the supervisor (if any) that the CLI exiting is expected
+            query.begin_shutdown();
+
             // Close stdin first to signal CLI to exit
             // This will cause the background task to finish
-            let _ = query.transport.end_input().await;
+            let _ = query.transport().end_input().await;
 
             // Wait for background task to complete with timeout instead of fixed sleep
             // This is much faster than the previous 100ms hardcoded sleep
@@ -864,11 +1299,11 @@ impl ClaudeClient {
                 let _ = tokio::time::timeout(std::time::Duration::from_millis(500), rx).await;
             }
 
-            // Close the transport (waits for process to exit)
-            query.transport.close().await?;
+            // Close the transport, escalating until the process has exited
+            return query.transport().shutdown().await;
         }
 
-        Ok(())
+        Ok(ShutdownStage::Graceful)
     }
 }
 
@@ -3,6 +3,10 @@
 use std::path::PathBuf;
 use thiserror::Error;
 
+use crate::locator::CliCandidate;
+use crate::types::capabilities::Capability;
+use crate::types::config::ResourceLimitKind;
+
 /// Main error type for the Claude Agent SDK
 #[derive(Debug, Error)]
 pub enum ClaudeError {
@@ -28,7 +32,7 @@ pub enum ClaudeError {
 
     /// Control protocol error
     #[error("Control protocol error: {0}")]
-    ControlProtocol(String),
+    ControlProtocol(#[from] ControlProtocolError),
 
     /// Invalid configuration
     #[error("Invalid configuration: {0}")]
@@ -42,6 +46,14 @@ pub enum ClaudeError {
     #[error("Image validation error: {0}")]
     ImageValidation(#[from] ImageValidationError),
 
+    /// Message queue overflow
+    #[error("Message queue overflow: {0}")]
+    QueueOverflow(#[from] QueueOverflowError),
+
+    /// The CLI is too old for a feature the options or a call need
+    #[error("Unsupported by the CLI: {0}")]
+    UnsupportedCapability(#[from] UnsupportedCapabilityError),
+
     /// IO error
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -59,6 +71,8 @@ pub struct CliNotFoundError {
     pub message: String,
     /// Path that was checked
     pub cli_path: Option<PathBuf>,
+    /// Every candidate inspected during discovery, in search order
+    pub candidates: Vec<CliCandidate>,
 }
 
 impl CliNotFoundError {
@@ -67,8 +81,15 @@ impl CliNotFoundError {
         Self {
             message: message.into(),
             cli_path,
+            candidates: Vec::new(),
         }
     }
+
+    /// Attach the candidates inspected during discovery
+    pub fn with_candidates(mut self, candidates: Vec<CliCandidate>) -> Self {
+        self.candidates = candidates;
+        self
+    }
 }
 
 /// Error when connecting to Claude Code CLI
@@ -98,6 +119,9 @@ pub struct ProcessError {
     pub exit_code: Option<i32>,
     /// stderr output
     pub stderr: Option<String>,
+    /// Configured resource limit the process died from, when stderr or the exit
+    /// signal shows it
+    pub limit: Option<ResourceLimitKind>,
 }
 
 impl ProcessError {
@@ -107,8 +131,15 @@ impl ProcessError {
             message: message.into(),
             exit_code,
             stderr,
+            limit: None,
         }
     }
+
+    /// Attach the resource limit the process ran into
+    pub fn with_limit(mut self, limit: Option<ResourceLimitKind>) -> Self {
+        self.limit = limit;
+        self
+    }
 }
 
 /// Error when JSON decoding fails
@@ -168,5 +199,93 @@ impl ImageValidationError {
     }
 }
 
+/// Error in a control request exchanged with the CLI
+#[derive(Debug, Error)]
+#[error("{message}")]
+pub struct ControlProtocolError {
+    /// Error message; for [`ControlErrorKind::Cli`], the message the CLI sent
+    pub message: String,
+    /// What went wrong
+    pub kind: ControlErrorKind,
+    /// Subtype of the control request that failed, if known
+    pub subtype: Option<String>,
+}
+
+impl ControlProtocolError {
+    /// Create a new control protocol error of kind [`ControlErrorKind::Protocol`]
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            kind: ControlErrorKind::Protocol,
+            subtype: None,
+        }
+    }
+
+    /// Set what went wrong
+    pub fn with_kind(mut self, kind: ControlErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Attach the subtype of the control request that failed
+    pub fn with_subtype(mut self, subtype: impl Into<String>) -> Self {
+        self.subtype = Some(subtype.into());
+        self
+    }
+}
+
+/// Why a control request failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlErrorKind {
+    /// A control message was malformed or could not be handled
+    Protocol,
+    /// The CLI answered with an error response
+    Cli,
+    /// The CLI did not answer within the timeout
+    Timeout,
+    /// The connection closed before the CLI answered
+    Disconnected,
+}
+
+/// Error when the message queue fills up under [`OverflowPolicy::Fail`](crate::types::config::OverflowPolicy::Fail)
+#[derive(Debug, Error)]
+#[error("consumer fell behind, queue is full at {capacity} messages")]
+pub struct QueueOverflowError {
+    /// Configured queue capacity
+    pub capacity: usize,
+}
+
+impl QueueOverflowError {
+    /// Create a new queue overflow error
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity }
+    }
+}
+
+/// Error when the connected CLI is older than a capability requires
+#[derive(Debug, Error)]
+#[error(
+    "{capability} requires Claude Code CLI {required_version} or newer, but {cli_version} is installed"
+)]
+pub struct UnsupportedCapabilityError {
+    /// Capability that is missing
+    pub capability: Capability,
+    /// First CLI version that supports it
+    pub required_version: &'static str,
+    /// Version of the connected CLI
+    pub cli_version: String,
+}
+
+impl UnsupportedCapabilityError {
+    /// Create a new unsupported capability error
+    pub fn new(capability: Capability, cli_version: impl Into<String>) -> Self {
+        Self {
+            capability,
+            required_version: capability.min_cli_version(),
+            cli_version: cli_version.into(),
+        }
+    }
+}
+
 /// Result type for the Claude Agent SDK
 pub type Result<T> = std::result::Result<T, ClaudeError>;
@@ -1,31 +1,65 @@
 //! Internal client implementation
 
 use futures::stream::StreamExt;
+use std::sync::Arc;
 
-use crate::errors::Result;
+use crate::errors::{ClaudeError, Result};
 use crate::types::config::ClaudeAgentOptions;
 use crate::types::messages::Message;
 
 use super::message_parser::MessageParser;
 use super::transport::subprocess::QueryPrompt;
-use super::transport::{SubprocessTransport, Transport};
+use super::transport::{SubprocessTransport, Transport, tap_transport};
 
 /// Internal client for processing queries
 pub struct InternalClient {
-    transport: SubprocessTransport,
+    transport: Arc<dyn Transport>,
+    /// Prompt to write after connecting (`None` when the transport sends it itself)
+    prompt: Option<QueryPrompt>,
 }
 
 impl InternalClient {
     /// Create a new client
-    pub fn new(prompt: QueryPrompt, options: ClaudeAgentOptions) -> Result<Self> {
-        let transport = SubprocessTransport::new(prompt, options)?;
-        Ok(Self { transport })
+    pub async fn new(prompt: QueryPrompt, options: ClaudeAgentOptions) -> Result<Self> {
+        let transport = SubprocessTransport::new_async(prompt, options.clone()).await?;
+        Ok(Self {
+            transport: tap_transport(Arc::new(transport), &options),
+            prompt: None,
+        })
+    }
+
+    /// Create a client that sends the prompt over a custom transport
+    pub fn with_transport(prompt: QueryPrompt, transport: Arc<dyn Transport>) -> Self {
+        Self {
+            transport,
+            prompt: Some(prompt),
+        }
+    }
+
+    /// Connect the transport and send the prompt if the transport doesn't
+    pub async fn connect(&self) -> Result<()> {
+        self.transport.connect().await?;
+
+        if let Some(user_message) = self.prompt.as_ref().and_then(QueryPrompt::to_user_message) {
+            let message_str = serde_json::to_string(&user_message).map_err(|e| {
+                ClaudeError::Transport(format!("Failed to serialize user message: {}", e))
+            })?;
+            self.transport.write(&message_str).await?;
+            self.transport.end_input().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Consume the client, returning the connected transport
+    pub fn into_transport(self) -> Arc<dyn Transport> {
+        self.transport
     }
 
     /// Connect and get messages
     pub async fn execute(self) -> Result<Vec<Message>> {
         // Connect
-        self.transport.connect().await?;
+        self.connect().await?;
 
         // Collect all messages
         let mut messages = Vec::new();
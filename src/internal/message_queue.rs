@@ -0,0 +1,366 @@
+//! Queue of regular messages between the CLI reader and the client's streams
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use tracing::warn;
+
+use crate::errors::{ClaudeError, QueueOverflowError, Result};
+use crate::types::config::OverflowPolicy;
+
+/// Snapshot of the message queue's state
+///
+/// Returned by [`ClaudeClient::queue_metrics`](crate::ClaudeClient::queue_metrics).
+/// A `depth` above `capacity`, or a growing `blocked_sends` count, means the
+/// consumer is falling behind the CLI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueMetrics {
+    /// Messages read from the CLI and waiting to be consumed, including those
+    /// staged behind a full queue
+    pub depth: usize,
+    /// Configured capacity, `None` when unbounded; up to as many messages again
+    /// can be staged behind a full queue
+    pub capacity: Option<usize>,
+    /// Largest depth observed since connecting
+    pub peak_depth: usize,
+    /// Times a message waited for the consumer to make room
+    pub blocked_sends: u64,
+    /// `stream_event` messages dropped under [`OverflowPolicy::DropStreamEvents`]
+    pub dropped_stream_events: u64,
+    /// Whether the queue overflowed under [`OverflowPolicy::Fail`]
+    pub overflowed: bool,
+}
+
+/// Message channel with an optional capacity and overflow policy
+///
+/// The reader stages messages in a second channel of the same capacity, and a
+/// forwarding task moves them into the queue, so control frames keep being
+/// handled while the consumer is behind. Once staging is full as well the
+/// reader waits, and the CLI blocks writing to its stdout.
+pub(crate) struct MessageQueue {
+    /// Taken by [`close`](Self::close)
+    tx: Mutex<Option<flume::Sender<Result<serde_json::Value>>>>,
+    rx: flume::Receiver<Result<serde_json::Value>>,
+    capacity: Option<usize>,
+    policy: OverflowPolicy,
+    peak_depth: AtomicUsize,
+    blocked_sends: AtomicU64,
+    dropped_stream_events: AtomicU64,
+    overflowed: AtomicBool,
+    /// Messages staged or being forwarded, not yet in the queue
+    staged: AtomicUsize,
+}
+
+impl MessageQueue {
+    /// Create a queue; `None` capacity means unbounded
+    pub(crate) fn new(capacity: Option<usize>, policy: OverflowPolicy) -> Self {
+        let (tx, rx) = match capacity {
+            Some(capacity) => flume::bounded(capacity.max(1)),
+            None => flume::unbounded(),
+        };
+
+        Self {
+            tx: Mutex::new(Some(tx)),
+            rx,
+            capacity,
+            policy,
+            peak_depth: AtomicUsize::new(0),
+            blocked_sends: AtomicU64::new(0),
+            dropped_stream_events: AtomicU64::new(0),
+            overflowed: AtomicBool::new(false),
+            staged: AtomicUsize::new(0),
+        }
+    }
+
+    /// Channel the reader stages messages in, bounded like the queue
+    pub(crate) fn staging(&self) -> (StagingSender, StagingReceiver) {
+        match self.capacity {
+            Some(capacity) => flume::bounded(capacity.max(1)),
+            None => flume::unbounded(),
+        }
+    }
+
+    /// Stage an item read from the CLI, waiting while staging is full
+    ///
+    /// Under [`OverflowPolicy::DropStreamEvents`], a `stream_event` is dropped
+    /// instead when the queue or staging is full. Returns `false` once the queue
+    /// has overflowed or forwarding has stopped, and the reader should stop.
+    pub(crate) async fn stage(
+        &self,
+        staging: &StagingSender,
+        item: Result<serde_json::Value>,
+    ) -> bool {
+        if self.has_overflowed() {
+            return false;
+        }
+        if self.policy == OverflowPolicy::DropStreamEvents
+            && is_stream_event(&item)
+            && (self.rx.is_full() || staging.is_full())
+        {
+            self.dropped_stream_events.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+
+        // Counted before sending so the forwarder never sees it go negative
+        self.staged.fetch_add(1, Ordering::Relaxed);
+        self.record_depth();
+        let sent = match staging.try_send(item) {
+            Ok(()) => true,
+            Err(flume::TrySendError::Full(item)) => {
+                self.blocked_sends.fetch_add(1, Ordering::Relaxed);
+                staging.send_async(item).await.is_ok()
+            }
+            Err(flume::TrySendError::Disconnected(_)) => false,
+        };
+        if !sent {
+            self.staged.fetch_sub(1, Ordering::Relaxed);
+        }
+        sent
+    }
+
+    /// Move staged items into the queue until staging closes or the queue overflows
+    pub(crate) async fn forward(&self, staging: StagingReceiver) {
+        while let Ok(item) = staging.recv_async().await {
+            let delivered = match item {
+                Ok(message) => self.push(message).await,
+                Err(e) => {
+                    self.push_error(e).await;
+                    true
+                }
+            };
+            self.staged.fetch_sub(1, Ordering::Relaxed);
+            if !delivered {
+                let abandoned = staging.drain().count();
+                self.staged.fetch_sub(abandoned, Ordering::Relaxed);
+                break;
+            }
+        }
+    }
+
+    /// Receiver for consuming messages; flume receivers are cheap to clone
+    pub(crate) fn receiver(&self) -> flume::Receiver<Result<serde_json::Value>> {
+        self.rx.clone()
+    }
+
+    /// Enqueue a message from the CLI, applying the overflow policy
+    ///
+    /// Returns `false` once the queue has overflowed and the reader should stop.
+    pub(crate) async fn push(&self, message: serde_json::Value) -> bool {
+        let Some(tx) = self.sender() else {
+            return true;
+        };
+        let message = match tx.try_send(Ok(message)) {
+            Ok(()) => {
+                self.record_depth();
+                return true;
+            }
+            Err(flume::TrySendError::Full(message)) => message,
+            Err(flume::TrySendError::Disconnected(_)) => return true,
+        };
+
+        match self.policy {
+            OverflowPolicy::DropStreamEvents if is_stream_event(&message) => {
+                self.dropped_stream_events.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            OverflowPolicy::Block | OverflowPolicy::DropStreamEvents => {
+                self.blocked_sends.fetch_add(1, Ordering::Relaxed);
+                let _ = tx.send_async(message).await;
+                self.record_depth();
+                true
+            }
+            OverflowPolicy::Fail => {
+                self.overflowed.store(true, Ordering::SeqCst);
+                let capacity = self.capacity.unwrap_or_default();
+                warn!(
+                    "Message queue full at {} messages, failing stream",
+                    capacity
+                );
+                // Delivered once the consumer drains a slot, after the buffered messages
+                let error = ClaudeError::QueueOverflow(QueueOverflowError::new(capacity));
+                let _ = tx.send_async(Err(error)).await;
+                false
+            }
+        }
+    }
+
+    /// Enqueue an error for one message the transport could not deliver; never dropped
+    pub(crate) async fn push_error(&self, error: ClaudeError) {
+        if let Some(tx) = self.sender() {
+            let _ = tx.send_async(Err(error)).await;
+            self.record_depth();
+        }
+    }
+
+    /// Enqueue an SDK-generated message; never dropped
+    pub(crate) async fn push_sdk(&self, message: serde_json::Value) {
+        if let Some(tx) = self.sender() {
+            let _ = tx.send_async(Ok(message)).await;
+            self.record_depth();
+        }
+    }
+
+    /// Stop accepting messages; receivers end once they have drained the buffer
+    pub(crate) fn close(&self) {
+        self.tx.lock().unwrap().take();
+    }
+
+    fn sender(&self) -> Option<flume::Sender<Result<serde_json::Value>>> {
+        self.tx.lock().unwrap().clone()
+    }
+
+    /// Whether the queue overflowed under [`OverflowPolicy::Fail`]
+    pub(crate) fn has_overflowed(&self) -> bool {
+        self.overflowed.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn metrics(&self) -> QueueMetrics {
+        QueueMetrics {
+            depth: self.depth(),
+            capacity: self.capacity,
+            peak_depth: self.peak_depth.load(Ordering::Relaxed),
+            blocked_sends: self.blocked_sends.load(Ordering::Relaxed),
+            dropped_stream_events: self.dropped_stream_events.load(Ordering::Relaxed),
+            overflowed: self.has_overflowed(),
+        }
+    }
+
+    fn depth(&self) -> usize {
+        self.rx.len() + self.staged.load(Ordering::Relaxed)
+    }
+
+    fn record_depth(&self) {
+        self.peak_depth.fetch_max(self.depth(), Ordering::Relaxed);
+    }
+}
+
+type StagingSender = flume::Sender<Result<serde_json::Value>>;
+type StagingReceiver = flume::Receiver<Result<serde_json::Value>>;
+
+fn is_stream_event(message: &Result<serde_json::Value>) -> bool {
+    matches!(
+        message,
+        Ok(value) if value.get("type").and_then(|v| v.as_str()) == Some("stream_event")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn stream_event() -> serde_json::Value {
+        json!({"type": "stream_event", "uuid": "u", "session_id": "s", "event": {}})
+    }
+
+    #[tokio::test]
+    async fn test_unbounded_tracks_peak_depth() {
+        let queue = MessageQueue::new(None, OverflowPolicy::Fail);
+        for _ in 0..3 {
+            assert!(queue.push(json!({"type": "assistant"})).await);
+        }
+        let _ = queue.receiver().recv_async().await;
+
+        let metrics = queue.metrics();
+        assert_eq!(metrics.depth, 2);
+        assert_eq!(metrics.peak_depth, 3);
+        assert_eq!(metrics.capacity, None);
+    }
+
+    #[tokio::test]
+    async fn test_drop_stream_events_when_full() {
+        let queue = MessageQueue::new(Some(1), OverflowPolicy::DropStreamEvents);
+        assert!(queue.push(stream_event()).await);
+        assert!(queue.push(stream_event()).await);
+        assert!(queue.push(stream_event()).await);
+
+        let metrics = queue.metrics();
+        assert_eq!(metrics.depth, 1);
+        assert_eq!(metrics.dropped_stream_events, 2);
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_consumer() {
+        let queue = std::sync::Arc::new(MessageQueue::new(Some(1), OverflowPolicy::Block));
+        assert!(queue.push(json!({"type": "assistant"})).await);
+
+        let pusher = {
+            let queue = std::sync::Arc::clone(&queue);
+            tokio::spawn(async move { queue.push(json!({"type": "result"})).await })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(!pusher.is_finished());
+
+        let rx = queue.receiver();
+        let first = rx.recv_async().await.unwrap().unwrap();
+        assert_eq!(first["type"], "assistant");
+        assert!(pusher.await.unwrap());
+        assert_eq!(queue.metrics().blocked_sends, 1);
+    }
+
+    #[tokio::test]
+    async fn test_block_stages_up_to_capacity_behind_a_full_queue() {
+        let queue = std::sync::Arc::new(MessageQueue::new(Some(1), OverflowPolicy::Block));
+        let (staging_tx, staging_rx) = queue.staging();
+        let forwarder = {
+            let queue = std::sync::Arc::clone(&queue);
+            tokio::spawn(async move { queue.forward(staging_rx).await })
+        };
+
+        // One message in the queue, one held by the forwarder, one staged
+        for _ in 0..3 {
+            assert!(
+                queue
+                    .stage(&staging_tx, Ok(json!({"type": "assistant"})))
+                    .await
+            );
+        }
+        let reader = {
+            let queue = std::sync::Arc::clone(&queue);
+            let staging_tx = staging_tx.clone();
+            tokio::spawn(async move {
+                queue
+                    .stage(&staging_tx, Ok(json!({"type": "result"})))
+                    .await
+            })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(!reader.is_finished());
+        assert_eq!(queue.metrics().depth, 4);
+
+        let rx = queue.receiver();
+        for _ in 0..4 {
+            assert!(rx.recv_async().await.unwrap().is_ok());
+        }
+        assert!(reader.await.unwrap());
+
+        drop(staging_tx);
+        forwarder.await.unwrap();
+        assert_eq!(queue.metrics().depth, 0);
+        assert_eq!(queue.metrics().peak_depth, 4);
+    }
+
+    #[tokio::test]
+    async fn test_fail_delivers_error_after_buffered_messages() {
+        let queue = std::sync::Arc::new(MessageQueue::new(Some(1), OverflowPolicy::Fail));
+        assert!(queue.push(json!({"type": "assistant"})).await);
+
+        let pusher = {
+            let queue = std::sync::Arc::clone(&queue);
+            tokio::spawn(async move { queue.push(json!({"type": "result"})).await })
+        };
+        while !queue.has_overflowed() {
+            tokio::task::yield_now().await;
+        }
+
+        let rx = queue.receiver();
+        assert!(rx.recv_async().await.unwrap().is_ok());
+        assert!(matches!(
+            rx.recv_async().await.unwrap(),
+            Err(ClaudeError::QueueOverflow(QueueOverflowError {
+                capacity: 1
+            }))
+        ));
+        assert!(!pusher.await.unwrap());
+        assert!(queue.metrics().overflowed);
+    }
+}
@@ -2,7 +2,18 @@
 
 pub mod client;
 pub mod message_parser;
+pub mod message_queue;
+#[cfg(target_os = "linux")]
+pub mod netns;
+#[cfg(target_os = "linux")]
+pub mod network_proxy;
 pub mod query_full;
 #[cfg(target_os = "linux")]
+pub mod resource_limits;
+pub mod runtime_hooks;
+#[cfg(target_os = "linux")]
 pub mod sandbox;
+#[cfg(target_os = "linux")]
+pub mod seccomp;
+pub mod supervisor;
 pub mod transport;
@@ -0,0 +1,290 @@
+//! User and network namespace isolation for the CLI subprocess (Linux only)
+//!
+//! The child unshares into a fresh user and network namespace that only has
+//! loopback. It binds the proxy listener inside that namespace and passes it to
+//! the parent over a socket pair, so the parent can serve connections from the
+//! CLI while dialing out from the host's network namespace.
+
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+use crate::errors::{ClaudeError, ProcessError};
+
+/// Setup steps, reported to the parent when one fails
+const STAGE_OK: u8 = 0;
+const STAGE_UNSHARE: u8 = 1;
+const STAGE_ID_MAP: u8 = 2;
+const STAGE_LOOPBACK: u8 = 3;
+const STAGE_LISTEN: u8 = 4;
+
+/// Stage byte followed by a native-endian errno
+const REPORT_LEN: usize = 5;
+
+/// Namespace setup prepared in the parent, performed in the child before `exec`
+pub struct PreparedIsolation {
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    proxy_port: u16,
+    channel: OwnedFd,
+}
+
+/// Parent end of the channel the child reports on
+pub struct IsolationReceiver {
+    channel: OwnedFd,
+}
+
+/// Prepare namespace isolation with the proxy listening on `proxy_port` inside it
+pub fn prepare_isolation(
+    proxy_port: u16,
+) -> std::io::Result<(PreparedIsolation, IsolationReceiver)> {
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for the two descriptors socketpair returns
+    if unsafe {
+        libc::socketpair(
+            libc::AF_UNIX,
+            libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC,
+            0,
+            fds.as_mut_ptr(),
+        )
+    } != 0
+    {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: socketpair succeeded, so both descriptors are open and owned by us
+    let (parent, child) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+    // SAFETY: getuid/getgid cannot fail
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let isolation = PreparedIsolation {
+        uid_map: format!("{uid} {uid} 1\n").into_bytes(),
+        gid_map: format!("{gid} {gid} 1\n").into_bytes(),
+        proxy_port,
+        channel: child,
+    };
+    Ok((isolation, IsolationReceiver { channel: parent }))
+}
+
+impl PreparedIsolation {
+    /// Move the calling process into the new namespaces and hand the proxy listener to the parent
+    ///
+    /// Unshares the user and network namespaces, maps the caller's IDs, brings up
+    /// loopback and listens on the proxy port. On failure the parent is told which
+    /// of these steps failed and with what errno.
+    pub fn enter(&self) -> std::io::Result<()> {
+        match self.setup() {
+            Ok(listener) => {
+                let sent = self.report(STAGE_OK, 0, Some(listener.as_raw_fd()));
+                drop(listener);
+                sent
+            }
+            Err((stage, error)) => {
+                let errno = error.raw_os_error().unwrap_or(libc::EINVAL);
+                let _ = self.report(stage, errno, None);
+                Err(error)
+            }
+        }
+    }
+
+    fn setup(&self) -> Result<OwnedFd, (u8, std::io::Error)> {
+        // SAFETY: unshare only affects the calling (single-threaded, forked) process
+        if unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) } != 0 {
+            return Err((STAGE_UNSHARE, std::io::Error::last_os_error()));
+        }
+
+        // Map our own IDs so files keep their owners; setgroups must be denied first
+        write_file(c"/proc/self/setgroups", b"deny")
+            .and_then(|_| write_file(c"/proc/self/uid_map", &self.uid_map))
+            .and_then(|_| write_file(c"/proc/self/gid_map", &self.gid_map))
+            .map_err(|e| (STAGE_ID_MAP, e))?;
+
+        bring_up_loopback().map_err(|e| (STAGE_LOOPBACK, e))?;
+        listen_on_loopback(self.proxy_port).map_err(|e| (STAGE_LISTEN, e))
+    }
+
+    fn report(&self, stage: u8, errno: i32, fd: Option<RawFd>) -> std::io::Result<()> {
+        let mut payload = [0u8; REPORT_LEN];
+        payload[0] = stage;
+        payload[1..].copy_from_slice(&errno.to_ne_bytes());
+        let mut iov = libc::iovec {
+            iov_base: payload.as_mut_ptr().cast(),
+            iov_len: REPORT_LEN,
+        };
+        // Aligned buffer for one SCM_RIGHTS control message
+        let mut control = [0u64; 4];
+
+        // SAFETY: msghdr is plain data; all pointers refer to live local buffers
+        unsafe {
+            let mut msg: libc::msghdr = std::mem::zeroed();
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            if let Some(fd) = fd {
+                msg.msg_control = control.as_mut_ptr().cast();
+                msg.msg_controllen = libc::CMSG_SPACE(size_of::<RawFd>() as u32) as _;
+                let cmsg = libc::CMSG_FIRSTHDR(&msg);
+                (*cmsg).cmsg_level = libc::SOL_SOCKET;
+                (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+                (*cmsg).cmsg_len = libc::CMSG_LEN(size_of::<RawFd>() as u32) as _;
+                std::ptr::write_unaligned(libc::CMSG_DATA(cmsg).cast::<RawFd>(), fd);
+            }
+            if libc::sendmsg(self.channel.as_raw_fd(), &msg, 0) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl IsolationReceiver {
+    /// Receive the proxy listener after a successful spawn
+    pub fn receive_listener(self) -> Result<std::net::TcpListener, ClaudeError> {
+        match self.receive()? {
+            (STAGE_OK, _, Some(listener)) => Ok(std::net::TcpListener::from(listener)),
+            (stage, errno, _) => Err(setup_error(stage, errno)),
+        }
+    }
+
+    /// Explain a failed spawn if the namespace setup was what failed
+    pub fn failure(self) -> Option<ClaudeError> {
+        match self.receive() {
+            Ok((STAGE_OK, ..)) | Err(_) => None,
+            Ok((stage, errno, _)) => Some(setup_error(stage, errno)),
+        }
+    }
+
+    fn receive(&self) -> Result<(u8, i32, Option<OwnedFd>), ClaudeError> {
+        let mut payload = [0u8; REPORT_LEN];
+        let mut iov = libc::iovec {
+            iov_base: payload.as_mut_ptr().cast(),
+            iov_len: REPORT_LEN,
+        };
+        let mut control = [0u64; 4];
+
+        // SAFETY: msghdr is plain data; all pointers refer to live local buffers,
+        // and the kernel only fills in a descriptor it has installed for us
+        unsafe {
+            let mut msg: libc::msghdr = std::mem::zeroed();
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr().cast();
+            msg.msg_controllen = size_of_val(&control) as _;
+            let received = libc::recvmsg(
+                self.channel.as_raw_fd(),
+                &mut msg,
+                libc::MSG_DONTWAIT | libc::MSG_CMSG_CLOEXEC,
+            );
+            if received != REPORT_LEN as isize {
+                return Err(ClaudeError::Process(ProcessError::new(
+                    format!(
+                        "Network isolation setup did not report back: {}",
+                        std::io::Error::last_os_error()
+                    ),
+                    None,
+                    None,
+                )));
+            }
+
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            let fd = (!cmsg.is_null()
+                && (*cmsg).cmsg_level == libc::SOL_SOCKET
+                && (*cmsg).cmsg_type == libc::SCM_RIGHTS)
+                .then(|| {
+                    OwnedFd::from_raw_fd(std::ptr::read_unaligned(
+                        libc::CMSG_DATA(cmsg).cast::<RawFd>(),
+                    ))
+                });
+            let errno = i32::from_ne_bytes(payload[1..].try_into().unwrap());
+            Ok((payload[0], errno, fd))
+        }
+    }
+}
+
+fn setup_error(stage: u8, errno: i32) -> ClaudeError {
+    let cause = std::io::Error::from_raw_os_error(errno);
+    let message = match stage {
+        STAGE_UNSHARE => format!(
+            "Network isolation requires unprivileged user namespaces, which are disabled on this host ({}). \
+             Check the sysctls user.max_user_namespaces, kernel.unprivileged_userns_clone and \
+             kernel.apparmor_restrict_unprivileged_userns",
+            cause
+        ),
+        STAGE_ID_MAP => format!(
+            "Failed to map user and group IDs in the new user namespace: {}",
+            cause
+        ),
+        STAGE_LOOPBACK => format!(
+            "Failed to bring up loopback in the new network namespace: {}",
+            cause
+        ),
+        _ => format!(
+            "Failed to listen for the network proxy in the new network namespace: {}",
+            cause
+        ),
+    };
+    ClaudeError::Process(ProcessError::new(message, None, None))
+}
+
+fn write_file(path: &std::ffi::CStr, contents: &[u8]) -> std::io::Result<()> {
+    // SAFETY: `path` is NUL-terminated and `contents` is a live buffer
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let fd = OwnedFd::from_raw_fd(fd);
+        if libc::write(fd.as_raw_fd(), contents.as_ptr().cast(), contents.len()) < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// A new network namespace starts with `lo` down
+fn bring_up_loopback() -> std::io::Result<()> {
+    // SAFETY: ifreq is plain data, and the ioctls only access it for the call
+    unsafe {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let fd = OwnedFd::from_raw_fd(fd);
+
+        let mut request: libc::ifreq = std::mem::zeroed();
+        request.ifr_name[0] = b'l' as libc::c_char;
+        request.ifr_name[1] = b'o' as libc::c_char;
+        if libc::ioctl(fd.as_raw_fd(), libc::SIOCGIFFLAGS as _, &mut request) < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        request.ifr_ifru.ifru_flags |= (libc::IFF_UP | libc::IFF_RUNNING) as libc::c_short;
+        if libc::ioctl(fd.as_raw_fd(), libc::SIOCSIFFLAGS as _, &request) < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn listen_on_loopback(port: u16) -> std::io::Result<OwnedFd> {
+    // SAFETY: sockaddr_in is plain data and outlives the bind call
+    unsafe {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let fd = OwnedFd::from_raw_fd(fd);
+
+        let mut addr: libc::sockaddr_in = std::mem::zeroed();
+        addr.sin_family = libc::AF_INET as libc::sa_family_t;
+        addr.sin_port = port.to_be();
+        addr.sin_addr.s_addr = u32::from(std::net::Ipv4Addr::LOCALHOST).to_be();
+        if libc::bind(
+            fd.as_raw_fd(),
+            (&addr as *const libc::sockaddr_in).cast(),
+            size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        ) < 0
+        {
+            return Err(std::io::Error::last_os_error());
+        }
+        if libc::listen(fd.as_raw_fd(), 128) < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(fd)
+    }
+}
@@ -0,0 +1,239 @@
+//! HTTP CONNECT proxy that lets a network-isolated CLI reach allowed destinations
+
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, warn};
+
+/// Upper bound on the size of a request head
+const MAX_REQUEST_HEAD: usize = 8192;
+
+/// Port assumed for destinations given without one
+const DEFAULT_PORT: u16 = 443;
+
+/// Destinations the proxy connects to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AllowList(Vec<(String, u16)>);
+
+impl AllowList {
+    /// Parse `host[:port]` patterns; `*.example.com` also matches subdomains
+    pub(crate) fn new(destinations: &[String]) -> Self {
+        Self(
+            destinations
+                .iter()
+                .filter_map(|destination| {
+                    let (host, port) = split_host_port(destination)?;
+                    Some((host.to_ascii_lowercase(), port.unwrap_or(DEFAULT_PORT)))
+                })
+                .collect(),
+        )
+    }
+
+    pub(crate) fn allows(&self, host: &str, port: u16) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.0.iter().any(|(pattern, allowed_port)| {
+            *allowed_port == port
+                && match pattern.strip_prefix("*.") {
+                    Some(domain) => host
+                        .strip_suffix(domain)
+                        .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with('.')),
+                    None => *pattern == host,
+                }
+        })
+    }
+}
+
+/// Split `host:port`, `host` or `[v6]:port`
+fn split_host_port(value: &str) -> Option<(&str, Option<u16>)> {
+    if let Some(rest) = value.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']')?;
+        return match rest.strip_prefix(':') {
+            Some(port) => Some((host, Some(port.parse().ok()?))),
+            None if rest.is_empty() => Some((host, None)),
+            None => None,
+        };
+    }
+    match value.rsplit_once(':') {
+        Some((host, port)) => Some((host, Some(port.parse().ok()?))),
+        None => Some((value, None)),
+    }
+}
+
+/// Serve CONNECT requests on `listener` until the task is aborted
+pub(crate) fn spawn_proxy(
+    listener: std::net::TcpListener,
+    allowed: AllowList,
+) -> std::io::Result<tokio::task::JoinHandle<()>> {
+    listener.set_nonblocking(true)?;
+    let listener = TcpListener::from_std(listener)?;
+    let allowed = Arc::new(allowed);
+    Ok(tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("Network proxy failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+            let allowed = Arc::clone(&allowed);
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(stream, &allowed).await {
+                    debug!("Network proxy connection ended with an error: {}", e);
+                }
+            });
+        }
+    }))
+}
+
+async fn handle_connection(stream: TcpStream, allowed: &AllowList) -> std::io::Result<()> {
+    let mut client = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    read_head_line(&mut client, &mut request_line).await?;
+    // Skip the headers; CONNECT needs nothing from them
+    let mut total = request_line.len();
+    let mut header = String::new();
+    loop {
+        header.clear();
+        total += read_head_line(&mut client, &mut header).await?;
+        if total > MAX_REQUEST_HEAD {
+            return respond(&mut client, "431 Request Header Fields Too Large").await;
+        }
+        if header.trim_end().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or_default(), parts.next());
+    if method != "CONNECT" {
+        return respond(&mut client, "405 Method Not Allowed").await;
+    }
+    let Some((host, Some(port))) = target.and_then(split_host_port) else {
+        return respond(&mut client, "400 Bad Request").await;
+    };
+    if !allowed.allows(host, port) {
+        warn!("Network proxy blocked a connection to {}:{}", host, port);
+        return respond(&mut client, "403 Forbidden").await;
+    }
+
+    let mut upstream = match TcpStream::connect((host, port)).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            debug!(
+                "Network proxy failed to connect to {}:{}: {}",
+                host, port, e
+            );
+            return respond(&mut client, "502 Bad Gateway").await;
+        }
+    };
+    debug!("Network proxy connected to {}:{}", host, port);
+    client
+        .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+        .await?;
+    // Bytes the client sent after the request head are still buffered in `client`
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+async fn read_head_line(
+    client: &mut BufReader<TcpStream>,
+    line: &mut String,
+) -> std::io::Result<usize> {
+    let read = (&mut *client)
+        .take(MAX_REQUEST_HEAD as u64)
+        .read_line(line)
+        .await?;
+    if read == 0 || !line.ends_with('\n') {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "incomplete proxy request",
+        ));
+    }
+    Ok(read)
+}
+
+async fn respond(client: &mut BufReader<TcpStream>, status: &str) -> std::io::Result<()> {
+    client
+        .write_all(
+            format!(
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            )
+            .as_bytes(),
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allow_list_matching() {
+        let allowed = AllowList::new(&[
+            "api.anthropic.com".to_string(),
+            "*.example.com:8443".to_string(),
+            "[::1]:9000".to_string(),
+        ]);
+        assert!(allowed.allows("api.anthropic.com", 443));
+        assert!(allowed.allows("API.Anthropic.com", 443));
+        assert!(!allowed.allows("api.anthropic.com", 80));
+        assert!(allowed.allows("example.com", 8443));
+        assert!(allowed.allows("a.b.example.com", 8443));
+        assert!(!allowed.allows("badexample.com", 8443));
+        assert!(allowed.allows("::1", 9000));
+        assert!(!allowed.allows("evil.com", 443));
+    }
+
+    #[tokio::test]
+    async fn test_proxy_tunnels_allowed_and_blocks_others() {
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_port = upstream.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = upstream.accept().await.unwrap();
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            stream.write_all(&buf).await.unwrap();
+        });
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = spawn_proxy(
+            listener,
+            AllowList::new(&[format!("127.0.0.1:{}", upstream_port)]),
+        )
+        .unwrap();
+
+        // Allowed: the tunnel echoes, including bytes sent along with the request
+        let mut client = TcpStream::connect(proxy_addr).await.unwrap();
+        client
+            .write_all(
+                format!(
+                    "CONNECT 127.0.0.1:{0} HTTP/1.1\r\nHost: 127.0.0.1:{0}\r\n\r\nping",
+                    upstream_port
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        let expected = b"HTTP/1.1 200 Connection Established\r\n\r\nping";
+        let mut response = vec![0u8; expected.len()];
+        client.read_exact(&mut response).await.unwrap();
+        assert_eq!(response, expected);
+
+        // Blocked
+        let mut client = TcpStream::connect(proxy_addr).await.unwrap();
+        client
+            .write_all(b"CONNECT evil.com:443 HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 403"));
+
+        proxy.abort();
+    }
+}
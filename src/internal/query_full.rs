@@ -4,18 +4,29 @@ use dashmap::DashMap;
 use futures::stream::StreamExt;
 use serde_json::json;
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::sync::OnceLock;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 use tokio::sync::oneshot;
+use tracing::{debug, warn};
 
-use crate::errors::{ClaudeError, Result};
-use crate::types::hooks::{HookCallback, HookContext, HookInput, HookMatcher};
-use crate::types::mcp::McpSdkServerConfig;
+use crate::errors::{ClaudeError, ControlErrorKind, ControlProtocolError, Result};
+use crate::types::capabilities::{Capabilities, Capability};
+use crate::types::config::OverflowPolicy;
+use crate::types::hooks::{AbortSignal, HookCallback, HookContext, HookInput, HookMatcher};
+use crate::types::mcp::{McpSdkServerConfig, McpServerConfig, McpServerStatus};
 use crate::types::permissions::{CanUseToolCallback, PermissionResult, ToolPermissionContext};
+use crate::types::server_info::ServerInfo;
 
+use super::message_queue::{MessageQueue, QueueMetrics};
 use super::transport::Transport;
 
+/// How long to wait for the CLI to answer a control request by default
+const DEFAULT_CONTROL_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The CLI's answer to a control request: response data, or its error message
+type ControlReply = std::result::Result<serde_json::Value, String>;
+
 /// Control request from SDK to CLI
 #[allow(dead_code)]
 #[derive(Debug, serde::Serialize)]
@@ -37,11 +48,20 @@ struct ControlResponse {
 
 #[derive(Debug, serde::Deserialize)]
 struct ControlResponseData {
-    #[allow(dead_code)]
     subtype: String,
     request_id: String,
-    #[serde(flatten)]
-    data: serde_json::Value,
+    #[serde(default)]
+    response: Option<serde_json::Value>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// The CLI's answer to `mcp_set_servers`
+#[derive(Debug, Default, serde::Deserialize)]
+struct McpSetServersResult {
+    /// Error per server that could not be added
+    #[serde(default)]
+    errors: HashMap<String, String>,
 }
 
 /// Control request from CLI to SDK
@@ -56,61 +76,125 @@ struct IncomingControlRequest {
 
 /// Full Query implementation with bidirectional control protocol
 pub struct QueryFull {
-    /// Transport for communication - uses &self methods via internal sync
-    pub(crate) transport: Arc<dyn Transport>,
+    /// Transport for communication - replaced when a supervised CLI is restarted
+    transport: RwLock<Arc<dyn Transport>>,
     /// Hook callbacks - concurrent access via DashMap
     hook_callbacks: Arc<DashMap<String, HookCallback>>,
     /// SDK MCP servers - concurrent access via DashMap
     sdk_mcp_servers: Arc<DashMap<String, McpSdkServerConfig>>,
+    /// MCP servers added after connect; the lock serializes changes to the set
+    runtime_mcp_servers: tokio::sync::Mutex<HashMap<String, McpServerConfig>>,
     next_callback_id: Arc<AtomicU64>,
     request_counter: Arc<AtomicU64>,
     /// Pending control request responses - concurrent access via DashMap
-    pending_responses: Arc<DashMap<String, oneshot::Sender<serde_json::Value>>>,
-    message_tx: flume::Sender<serde_json::Value>,
-    /// Message receiver - cloneable without mutex thanks to flume
-    pub(crate) message_rx: flume::Receiver<serde_json::Value>,
-    /// Initialization result - set once during initialize(), read many times
-    initialization_result: OnceLock<serde_json::Value>,
-    /// Callback for tool usage permission (canUseTool)
-    can_use_tool_callback: Arc<Option<CanUseToolCallback>>,
+    pending_responses: Arc<DashMap<String, oneshot::Sender<ControlReply>>>,
+    /// How long to wait for a control response unless a call overrides it
+    control_request_timeout: Duration,
+    /// Regular messages for the client's streams
+    message_queue: Arc<MessageQueue>,
+    /// Initialization result - set by initialize(), read many times
+    initialization_result: RwLock<Option<ServerInfo>>,
+    /// Cancellation signals for control requests from the CLI still being handled
+    in_flight: Arc<DashMap<String, AbortSignal>>,
+    /// Callback for tool usage permission (canUseTool) - swappable after start
+    can_use_tool_callback: Arc<RwLock<Option<CanUseToolCallback>>>,
+    /// Most recent session ID seen on the message stream
+    last_session_id: Arc<Mutex<Option<String>>>,
+    /// Set once disconnect has started, so a closing CLI isn't treated as a crash
+    closing: AtomicBool,
+    /// Task reading the current transport, started by [`start`](Self::start)
+    reader: Mutex<Option<tokio::task::AbortHandle>>,
 }
 
 impl QueryFull {
-    /// Create a new Query
-    pub fn new(transport: Box<dyn Transport>) -> Self {
-        let (message_tx, message_rx) = flume::unbounded();
-
+    /// Create a new Query with a pre-existing Arc transport
+    pub fn new_with_transport(transport: Arc<dyn Transport>) -> Self {
         Self {
-            transport: Arc::from(transport),
+            transport: RwLock::new(transport),
             hook_callbacks: Arc::new(DashMap::new()),
             sdk_mcp_servers: Arc::new(DashMap::new()),
+            runtime_mcp_servers: tokio::sync::Mutex::new(HashMap::new()),
             next_callback_id: Arc::new(AtomicU64::new(0)),
             request_counter: Arc::new(AtomicU64::new(0)),
             pending_responses: Arc::new(DashMap::new()),
-            message_tx,
-            message_rx,
-            initialization_result: OnceLock::new(),
-            can_use_tool_callback: Arc::new(None),
+            control_request_timeout: DEFAULT_CONTROL_REQUEST_TIMEOUT,
+            message_queue: Arc::new(MessageQueue::new(None, OverflowPolicy::default())),
+            initialization_result: RwLock::new(None),
+            in_flight: Arc::new(DashMap::new()),
+            can_use_tool_callback: Arc::new(RwLock::new(None)),
+            last_session_id: Arc::new(Mutex::new(None)),
+            closing: AtomicBool::new(false),
+            reader: Mutex::new(None),
         }
     }
 
-    /// Create a new Query with a pre-existing Arc transport (for testing)
-    #[cfg(feature = "testing")]
-    pub fn new_with_transport(transport: Arc<dyn Transport>) -> Self {
-        let (message_tx, message_rx) = flume::unbounded();
+    /// Current transport
+    pub(crate) fn transport(&self) -> Arc<dyn Transport> {
+        Arc::clone(&self.transport.read().unwrap())
+    }
 
-        Self {
-            transport,
-            hook_callbacks: Arc::new(DashMap::new()),
-            sdk_mcp_servers: Arc::new(DashMap::new()),
-            next_callback_id: Arc::new(AtomicU64::new(0)),
-            request_counter: Arc::new(AtomicU64::new(0)),
-            pending_responses: Arc::new(DashMap::new()),
-            message_tx,
-            message_rx,
-            initialization_result: OnceLock::new(),
-            can_use_tool_callback: Arc::new(None),
+    /// Swap in the transport of a restarted CLI
+    ///
+    /// Pending control requests and hook callback IDs belong to the old process,
+    /// so they are dropped; call [`start`](Self::start) and
+    /// [`initialize`](Self::initialize) afterwards.
+    pub(crate) fn replace_transport(&self, transport: Arc<dyn Transport>) {
+        *self.transport.write().unwrap() = transport;
+        self.pending_responses.clear();
+        self.hook_callbacks.clear();
+    }
+
+    /// Most recent session ID reported by the CLI
+    pub(crate) fn last_session_id(&self) -> Option<String> {
+        self.last_session_id.lock().unwrap().clone()
+    }
+
+    /// Push an SDK-generated message onto the message stream
+    pub(crate) async fn emit_message(&self, message: serde_json::Value) {
+        self.message_queue.push_sdk(message).await;
+    }
+
+    /// End the message stream once the buffered messages are consumed
+    pub(crate) fn close_messages(&self) {
+        self.message_queue.close();
+    }
+
+    /// Receiver for the message stream
+    pub(crate) fn message_receiver(&self) -> flume::Receiver<Result<serde_json::Value>> {
+        self.message_queue.receiver()
+    }
+
+    /// Whether the message queue overflowed, which stops the reader for good
+    pub(crate) fn has_overflowed(&self) -> bool {
+        self.message_queue.has_overflowed()
+    }
+
+    /// Current message queue metrics
+    pub(crate) fn queue_metrics(&self) -> QueueMetrics {
+        self.message_queue.metrics()
+    }
+
+    /// Mark the query as shutting down, failing control requests still awaiting an answer
+    pub(crate) fn begin_shutdown(&self) {
+        self.closing.store(true, Ordering::SeqCst);
+        self.pending_responses.clear();
+    }
+
+    /// Whether disconnect has started
+    pub(crate) fn is_closing(&self) -> bool {
+        self.closing.load(Ordering::SeqCst)
+    }
+
+    /// Stop the reader and close the transport of a connection that failed to come up
+    ///
+    /// The reader is aborted rather than left to see the stream end, since a custom
+    /// transport's stream may outlive `close`.
+    pub(crate) async fn abandon(&self) {
+        self.begin_shutdown();
+        if let Some(reader) = self.reader.lock().unwrap().take() {
+            reader.abort();
         }
+        let _ = self.transport().close().await;
     }
 
     /// Set SDK MCP servers
@@ -121,16 +205,31 @@ impl QueryFull {
         }
     }
 
-    /// Set the can_use_tool callback for permission handling
-    pub fn set_can_use_tool(&mut self, callback: Option<CanUseToolCallback>) {
-        self.can_use_tool_callback = Arc::new(callback);
+    /// Bound the message queue; must be called before [`start`](Self::start)
+    pub fn set_message_queue(&mut self, capacity: Option<usize>, policy: OverflowPolicy) {
+        self.message_queue = Arc::new(MessageQueue::new(capacity, policy));
+    }
+
+    /// Set how long to wait for control responses by default
+    pub fn set_control_request_timeout(&mut self, timeout: Duration) {
+        self.control_request_timeout = timeout;
+    }
+
+    /// Set the can_use_tool callback for permission handling, returning the previous one
+    ///
+    /// Takes effect for permission requests received afterwards.
+    pub fn set_can_use_tool(
+        &self,
+        callback: Option<CanUseToolCallback>,
+    ) -> Option<CanUseToolCallback> {
+        std::mem::replace(&mut *self.can_use_tool_callback.write().unwrap(), callback)
     }
 
     /// Initialize with hooks
     pub async fn initialize(
         &self,
         hooks: Option<HashMap<String, Vec<HookMatcher>>>,
-    ) -> Result<serde_json::Value> {
+    ) -> Result<ServerInfo> {
         // Build hooks configuration
         let mut hooks_config: HashMap<String, Vec<serde_json::Value>> = HashMap::new();
 
@@ -174,11 +273,12 @@ impl QueryFull {
         });
 
         let response = self.send_control_request(request).await?;
+        let info = ServerInfo::from_response(response);
 
-        // Store initialization result for get_server_info() (set once, read many)
-        let _ = self.initialization_result.set(response.clone());
+        // Store initialization result for get_server_info()
+        *self.initialization_result.write().unwrap() = Some(info.clone());
 
-        Ok(response)
+        Ok(info)
     }
 
     /// Start reading messages in background
@@ -186,13 +286,15 @@ impl QueryFull {
     /// Returns a receiver that signals when the background task completes.
     /// The caller should store this and await it during disconnect.
     pub async fn start(&self) -> Result<oneshot::Receiver<()>> {
-        let transport = Arc::clone(&self.transport);
-        let transport_for_hooks = Arc::clone(&self.transport);
+        let transport = self.transport();
+        let transport_for_hooks = Arc::clone(&transport);
         let hook_callbacks = Arc::clone(&self.hook_callbacks);
         let sdk_mcp_servers = Arc::clone(&self.sdk_mcp_servers);
         let can_use_tool = Arc::clone(&self.can_use_tool_callback);
         let pending_responses = Arc::clone(&self.pending_responses);
-        let message_tx = self.message_tx.clone();
+        let in_flight = Arc::clone(&self.in_flight);
+        let message_queue = Arc::clone(&self.message_queue);
+        let last_session_id = Arc::clone(&self.last_session_id);
 
         // Create a channel to signal when background task is ready
         let (ready_tx, ready_rx) = oneshot::channel();
@@ -200,7 +302,16 @@ impl QueryFull {
         // Create a channel to signal when background task completes
         let (shutdown_tx, shutdown_rx) = oneshot::channel();
 
-        tokio::spawn(async move {
+        // Regular messages are staged for a forwarding task, so a full queue only holds
+        // back the client's streams until staging fills up too; control frames keep
+        // being handled meanwhile
+        let (staging_tx, staging_rx) = message_queue.staging();
+        let forwarder = {
+            let message_queue = Arc::clone(&message_queue);
+            tokio::spawn(async move { message_queue.forward(staging_rx).await })
+        };
+
+        let reader = tokio::spawn(async move {
             // No lock needed - Transport uses &self methods with internal sync
             let mut stream = transport.read_messages();
 
@@ -222,48 +333,113 @@ impl QueryFull {
                                     if let Some((_, tx)) =
                                         pending_responses.remove(&response.response.request_id)
                                     {
-                                        let _ = tx.send(response.response.data);
+                                        let data = response.response;
+                                        let reply = if data.subtype == "error" {
+                                            Err(data
+                                                .error
+                                                .unwrap_or_else(|| "Unknown error".to_string()))
+                                        } else {
+                                            Ok(data.response.unwrap_or_default())
+                                        };
+                                        let _ = tx.send(reply);
                                     }
                                 }
                             }
                             Some("control_request") => {
                                 // Handle incoming control request (e.g., hook callback, MCP message)
-                                if let Ok(request) = serde_json::from_value::<IncomingControlRequest>(
-                                    message.clone(),
-                                ) {
-                                    let transport_clone = Arc::clone(&transport_for_hooks);
-                                    let hook_callbacks_clone = Arc::clone(&hook_callbacks);
-                                    let sdk_mcp_servers_clone = Arc::clone(&sdk_mcp_servers);
-                                    let can_use_tool_clone = Arc::clone(&can_use_tool);
-
-                                    tokio::spawn(async move {
-                                        if let Err(e) = Self::handle_control_request(
-                                            request,
-                                            transport_clone,
-                                            hook_callbacks_clone,
-                                            sdk_mcp_servers_clone,
-                                            can_use_tool_clone,
-                                        )
-                                        .await
-                                        {
-                                            eprintln!("Error handling control request: {}", e);
+                                let transport_clone = Arc::clone(&transport_for_hooks);
+                                let hook_callbacks_clone = Arc::clone(&hook_callbacks);
+                                let sdk_mcp_servers_clone = Arc::clone(&sdk_mcp_servers);
+                                let can_use_tool_clone = Arc::clone(&can_use_tool);
+                                let in_flight_clone = Arc::clone(&in_flight);
+
+                                // Registered before spawning, so a cancel right behind it finds it
+                                let request_id = message
+                                    .get("request_id")
+                                    .and_then(|v| v.as_str())
+                                    .map(String::from);
+                                let signal = AbortSignal::new();
+                                if let Some(ref id) = request_id {
+                                    in_flight.insert(id.clone(), signal.clone());
+                                }
+
+                                tokio::spawn(async move {
+                                    let handled = Self::handle_control_request(
+                                        message,
+                                        signal.clone(),
+                                        transport_clone,
+                                        hook_callbacks_clone,
+                                        sdk_mcp_servers_clone,
+                                        can_use_tool_clone,
+                                    );
+                                    // Stop awaiting the handler once the CLI cancels it
+                                    let result = tokio::select! {
+                                        result = handled => Some(result),
+                                        _ = signal.aborted() => None,
+                                    };
+                                    if let Some(id) = request_id {
+                                        in_flight_clone.remove(&id);
+                                    }
+                                    match result {
+                                        Some(Err(e)) => {
+                                            warn!("Failed to answer control request: {}", e)
                                         }
-                                    });
+                                        None => debug!("Control request cancelled by the CLI"),
+                                        Some(Ok(())) => {}
+                                    }
+                                });
+                            }
+                            Some("control_cancel_request") => {
+                                // The CLI gave up on a request; it expects no response
+                                if let Some(request_id) =
+                                    message.get("request_id").and_then(|v| v.as_str())
+                                    && let Some((_, signal)) = in_flight.remove(request_id)
+                                {
+                                    signal.abort();
                                 }
                             }
                             _ => {
-                                // Regular message - send to stream
-                                let _ = message_tx.send(message);
+                                // Remember the session so a restarted CLI can resume it
+                                if let Some(session_id) =
+                                    message.get("session_id").and_then(|v| v.as_str())
+                                {
+                                    *last_session_id.lock().unwrap() = Some(session_id.to_string());
+                                }
+
+                                // Regular message - stage it, stopping if the queue overflowed
+                                if !message_queue.stage(&staging_tx, Ok(message)).await {
+                                    break;
+                                }
                             }
                         }
                     }
-                    Err(_) => break,
+                    // A bad line (oversized, malformed) only loses that message; the
+                    // transport ends its stream when the connection itself is gone
+                    Err(e) => {
+                        warn!("Failed to read message from the CLI: {}", e);
+                        if !message_queue.stage(&staging_tx, Err(e)).await {
+                            break;
+                        }
+                    }
                 }
             }
 
+            // Nothing will answer requests still waiting on this transport, and
+            // nothing can receive answers to the requests still being handled
+            pending_responses.clear();
+            in_flight.retain(|_, signal| {
+                signal.abort();
+                false
+            });
+
+            // Let the forwarder deliver what it still holds before signalling the end
+            drop(staging_tx);
+            let _ = forwarder.await;
+
             // Signal that background task has completed
             let _ = shutdown_tx.send(());
         });
+        *self.reader.lock().unwrap() = Some(reader.abort_handle());
 
         // Wait for background task to be ready before returning
         ready_rx
@@ -274,29 +450,98 @@ impl QueryFull {
     }
 
     /// Handle incoming control request from CLI
+    ///
+    /// Every request that carries a `request_id` gets a `control_response`: `success`
+    /// with the handler's result, or `error` with the reason it failed, so the CLI
+    /// never waits on an answer. Only a failure to write the response is returned.
     async fn handle_control_request(
-        request: IncomingControlRequest,
+        message: serde_json::Value,
+        signal: AbortSignal,
         transport: Arc<dyn Transport>,
         hook_callbacks: Arc<DashMap<String, HookCallback>>,
         sdk_mcp_servers: Arc<DashMap<String, McpSdkServerConfig>>,
-        can_use_tool_callback: Arc<Option<CanUseToolCallback>>,
+        can_use_tool_callback: Arc<RwLock<Option<CanUseToolCallback>>>,
     ) -> Result<()> {
-        let request_id = request.request_id;
-        let request_data = request.request;
+        let (request_id, result) =
+            match serde_json::from_value::<IncomingControlRequest>(message.clone()) {
+                Ok(request) => {
+                    let result = Self::process_control_request(
+                        &request.request,
+                        signal,
+                        hook_callbacks,
+                        sdk_mcp_servers,
+                        can_use_tool_callback,
+                    )
+                    .await;
+                    (request.request_id, result)
+                }
+                Err(e) => {
+                    let Some(request_id) = message.get("request_id").and_then(|v| v.as_str())
+                    else {
+                        warn!("Ignoring control request without a request_id: {}", e);
+                        return Ok(());
+                    };
+                    (
+                        request_id.to_string(),
+                        Err(ClaudeError::ControlProtocol(ControlProtocolError::new(
+                            format!("Malformed control request: {}", e),
+                        ))),
+                    )
+                }
+            };
+
+        let response = match result {
+            Ok(response_data) => json!({
+                "subtype": "success",
+                "request_id": request_id,
+                "response": response_data
+            }),
+            Err(e) => {
+                warn!("Control request {} failed: {}", request_id, e);
+                json!({
+                    "subtype": "error",
+                    "request_id": request_id,
+                    "error": control_error_message(&e)
+                })
+            }
+        };
+        let response = json!({
+            "type": "control_response",
+            "response": response
+        });
+
+        let response_str = serde_json::to_string(&response)
+            .map_err(|e| ClaudeError::Transport(format!("Failed to serialize response: {}", e)))?;
+
+        // Write via transport - stdin/stdout have separate locks, no deadlock
+        transport.write(&response_str).await
+    }
 
+    /// Run the handler for an incoming control request and return its response data
+    async fn process_control_request(
+        request_data: &serde_json::Value,
+        signal: AbortSignal,
+        hook_callbacks: Arc<DashMap<String, HookCallback>>,
+        sdk_mcp_servers: Arc<DashMap<String, McpSdkServerConfig>>,
+        can_use_tool_callback: Arc<RwLock<Option<CanUseToolCallback>>>,
+    ) -> Result<serde_json::Value> {
         let subtype = request_data
             .get("subtype")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| ClaudeError::ControlProtocol("Missing subtype".to_string()))?;
+            .ok_or_else(|| {
+                ClaudeError::ControlProtocol(ControlProtocolError::new("Missing subtype"))
+            })?;
 
-        let response_data: serde_json::Value = match subtype {
+        let response_data = match subtype {
             "hook_callback" => {
                 // Execute hook callback
                 let callback_id = request_data
                     .get("callback_id")
                     .and_then(|v| v.as_str())
                     .ok_or_else(|| {
-                        ClaudeError::ControlProtocol("Missing callback_id".to_string())
+                        ClaudeError::ControlProtocol(ControlProtocolError::new(
+                            "Missing callback_id",
+                        ))
                     })?;
 
                 // Clone the callback Arc to release the DashMap guard before async call
@@ -304,30 +549,38 @@ impl QueryFull {
                     .get(callback_id)
                     .map(|r| r.clone())
                     .ok_or_else(|| {
-                        ClaudeError::ControlProtocol(format!(
+                        ClaudeError::ControlProtocol(ControlProtocolError::new(format!(
                             "Hook callback not found: {}",
                             callback_id
-                        ))
+                        )))
                     })?;
 
                 // Parse hook input
                 let input_json = request_data.get("input").cloned().unwrap_or(json!({}));
                 let hook_input: HookInput = serde_json::from_value(input_json).map_err(|e| {
-                    ClaudeError::ControlProtocol(format!("Failed to parse hook input: {}", e))
+                    ClaudeError::ControlProtocol(ControlProtocolError::new(format!(
+                        "Failed to parse hook input: {}",
+                        e
+                    )))
                 })?;
 
                 let tool_use_id = request_data
                     .get("tool_use_id")
                     .and_then(|v| v.as_str())
                     .map(String::from);
-                let context = HookContext::default();
+                let context = HookContext {
+                    signal: Some(signal),
+                };
 
                 // Call the hook
                 let hook_output = callback(hook_input, tool_use_id, context).await;
 
                 // Convert to JSON
                 serde_json::to_value(&hook_output).map_err(|e| {
-                    ClaudeError::ControlProtocol(format!("Failed to serialize hook output: {}", e))
+                    ClaudeError::ControlProtocol(ControlProtocolError::new(format!(
+                        "Failed to serialize hook output: {}",
+                        e
+                    )))
                 })?
             }
             "can_use_tool" => {
@@ -338,10 +591,7 @@ impl QueryFull {
                     .unwrap_or("")
                     .to_string();
 
-                let tool_input = request_data
-                    .get("input")
-                    .cloned()
-                    .unwrap_or(json!({}));
+                let tool_input = request_data.get("input").cloned().unwrap_or(json!({}));
 
                 let suggestions = request_data
                     .get("suggestions")
@@ -349,21 +599,22 @@ impl QueryFull {
                     .unwrap_or_default();
 
                 let context = ToolPermissionContext {
-                    signal: None,
+                    signal: Some(signal),
                     suggestions,
                 };
 
-                if let Some(ref callback) = *can_use_tool_callback {
+                let callback = can_use_tool_callback.read().unwrap().clone();
+                if let Some(callback) = callback {
                     let result = callback(tool_name, tool_input, context).await;
                     serde_json::to_value(&result).map_err(|e| {
-                        ClaudeError::ControlProtocol(format!(
+                        ClaudeError::ControlProtocol(ControlProtocolError::new(format!(
                             "Failed to serialize permission result: {}",
                             e
-                        ))
+                        )))
                     })?
                 } else {
                     // No callback configured - default allow
-                    serde_json::to_value(&PermissionResult::Allow(Default::default()))
+                    serde_json::to_value(PermissionResult::Allow(Default::default()))
                         .unwrap_or(json!({"behavior": "allow"}))
                 }
             }
@@ -373,13 +624,15 @@ impl QueryFull {
                     .get("server_name")
                     .and_then(|v| v.as_str())
                     .ok_or_else(|| {
-                        ClaudeError::ControlProtocol(
-                            "Missing server_name for mcp_message".to_string(),
-                        )
+                        ClaudeError::ControlProtocol(ControlProtocolError::new(
+                            "Missing server_name for mcp_message",
+                        ))
                     })?;
 
                 let mcp_message = request_data.get("message").ok_or_else(|| {
-                    ClaudeError::ControlProtocol("Missing message for mcp_message".to_string())
+                    ClaudeError::ControlProtocol(ControlProtocolError::new(
+                        "Missing message for mcp_message",
+                    ))
                 })?;
 
                 let mcp_response =
@@ -389,34 +642,35 @@ impl QueryFull {
                 json!({"mcp_response": mcp_response})
             }
             _ => {
-                return Err(ClaudeError::ControlProtocol(format!(
-                    "Unsupported control request subtype: {}",
-                    subtype
+                debug!("Rejecting unsupported control request subtype {}", subtype);
+                return Err(ClaudeError::ControlProtocol(ControlProtocolError::new(
+                    format!("Unsupported control request subtype: {}", subtype),
                 )));
             }
         };
 
-        // Send success response
-        let response = json!({
-            "type": "control_response",
-            "response": {
-                "subtype": "success",
-                "request_id": request_id,
-                "response": response_data
-            }
-        });
-
-        let response_str = serde_json::to_string(&response)
-            .map_err(|e| ClaudeError::Transport(format!("Failed to serialize response: {}", e)))?;
-
-        // Write via transport - stdin/stdout have separate locks, no deadlock
-        transport.write(&response_str).await?;
+        Ok(response_data)
+    }
 
-        Ok(())
+    /// Send control request to CLI, waiting up to the default timeout
+    async fn send_control_request(&self, request: serde_json::Value) -> Result<serde_json::Value> {
+        self.send_control_request_with_timeout(request, None).await
     }
 
     /// Send control request to CLI
-    async fn send_control_request(&self, request: serde_json::Value) -> Result<serde_json::Value> {
+    ///
+    /// Waits up to `timeout`, or the default when `None`. An error response from the
+    /// CLI becomes a [`ControlErrorKind::Cli`] error carrying its message.
+    async fn send_control_request_with_timeout(
+        &self,
+        request: serde_json::Value,
+        timeout: Option<Duration>,
+    ) -> Result<serde_json::Value> {
+        let subtype = request
+            .get("subtype")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
         let request_id = format!(
             "req_{}_{}",
             self.request_counter.fetch_add(1, Ordering::SeqCst),
@@ -426,6 +680,11 @@ impl QueryFull {
         // Create oneshot channel for response
         let (tx, rx) = oneshot::channel();
         self.pending_responses.insert(request_id.clone(), tx);
+        // Drops the entry however this call ends, including the caller dropping it
+        let _pending = PendingRequest {
+            pending_responses: &self.pending_responses,
+            request_id: &request_id,
+        };
 
         // Build and send request
         let control_request = json!({
@@ -438,23 +697,47 @@ impl QueryFull {
             .map_err(|e| ClaudeError::Transport(format!("Failed to serialize request: {}", e)))?;
 
         // Write via transport - stdin/stdout have separate locks, no deadlock
-        self.transport.write(&request_str).await?;
+        self.transport().write(&request_str).await?;
 
         // Wait for response
-        let response = rx.await.map_err(|_| {
-            ClaudeError::ControlProtocol("Control request response channel closed".to_string())
-        })?;
+        let timeout = timeout.unwrap_or(self.control_request_timeout);
+        let reply = match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(_)) => {
+                return Err(ControlProtocolError::new(format!(
+                    "Connection closed before the CLI answered the {} request",
+                    subtype
+                ))
+                .with_kind(ControlErrorKind::Disconnected)
+                .with_subtype(subtype)
+                .into());
+            }
+            Err(_) => {
+                return Err(ControlProtocolError::new(format!(
+                    "CLI did not answer the {} request within {:?}",
+                    subtype, timeout
+                ))
+                .with_kind(ControlErrorKind::Timeout)
+                .with_subtype(subtype)
+                .into());
+            }
+        };
 
-        Ok(response)
+        reply.map_err(|message| {
+            ControlProtocolError::new(message)
+                .with_kind(ControlErrorKind::Cli)
+                .with_subtype(subtype)
+                .into()
+        })
     }
 
     /// Receive messages
     #[allow(dead_code)]
     pub async fn receive_messages(&self) -> Vec<serde_json::Value> {
         let mut messages = Vec::new();
-        let rx = self.message_rx.clone();
+        let rx = self.message_queue.receiver();
 
-        while let Ok(message) = rx.recv_async().await {
+        while let Ok(Ok(message)) = rx.recv_async().await {
             messages.push(message);
         }
 
@@ -462,12 +745,13 @@ impl QueryFull {
     }
 
     /// Send interrupt signal to Claude
-    pub async fn interrupt(&self) -> Result<()> {
+    pub async fn interrupt(&self, timeout: Option<Duration>) -> Result<()> {
         let request = json!({
             "subtype": "interrupt"
         });
 
-        self.send_control_request(request).await?;
+        self.send_control_request_with_timeout(request, timeout)
+            .await?;
         Ok(())
     }
 
@@ -475,6 +759,7 @@ impl QueryFull {
     pub async fn set_permission_mode(
         &self,
         mode: crate::types::config::PermissionMode,
+        timeout: Option<Duration>,
     ) -> Result<()> {
         let mode_str = match mode {
             crate::types::config::PermissionMode::Default => "default",
@@ -488,18 +773,20 @@ impl QueryFull {
             "mode": mode_str
         });
 
-        self.send_control_request(request).await?;
+        self.send_control_request_with_timeout(request, timeout)
+            .await?;
         Ok(())
     }
 
     /// Change AI model dynamically
-    pub async fn set_model(&self, model: Option<&str>) -> Result<()> {
+    pub async fn set_model(&self, model: Option<&str>, timeout: Option<Duration>) -> Result<()> {
         let request = json!({
             "subtype": "set_model",
             "model": model
         });
 
-        self.send_control_request(request).await?;
+        self.send_control_request_with_timeout(request, timeout)
+            .await?;
         Ok(())
     }
 
@@ -513,23 +800,161 @@ impl QueryFull {
     /// # Arguments
     /// * `user_message_id` - UUID of the user message to rewind to. This should be
     ///   the `uuid` field from a `UserMessage` received during the conversation.
-    pub async fn rewind_files(&self, user_message_id: &str) -> Result<()> {
+    pub async fn rewind_files(
+        &self,
+        user_message_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        self.capabilities().require(Capability::FileCheckpointing)?;
         let request = json!({
             "subtype": "rewind_files",
             "user_message_id": user_message_id
         });
 
-        self.send_control_request(request).await?;
+        self.send_control_request_with_timeout(request, timeout)
+            .await?;
+        Ok(())
+    }
+
+    /// Add or replace an MCP server on the running CLI
+    ///
+    /// An SDK server is routed to before the CLI connects to it, and dropped again
+    /// if the CLI rejects it.
+    pub async fn add_mcp_server(&self, name: String, config: McpServerConfig) -> Result<()> {
+        let mut runtime_servers = self.runtime_mcp_servers.lock().await;
+        if !runtime_servers.contains_key(&name) && self.sdk_mcp_servers.contains_key(&name) {
+            return Err(ClaudeError::InvalidConfig(format!(
+                "MCP server '{}' is already configured in options",
+                name
+            )));
+        }
+
+        let mut servers = runtime_servers.clone();
+        servers.insert(name.clone(), config.clone());
+        let replaced = match &config {
+            McpServerConfig::Sdk(sdk_config) => self
+                .sdk_mcp_servers
+                .insert(name.clone(), sdk_config.clone()),
+            _ => self.sdk_mcp_servers.remove(&name).map(|(_, server)| server),
+        };
+
+        let result = self.send_mcp_servers(&servers).await.and_then(|result| {
+            match result.errors.get(&name) {
+                Some(error) => Err(ClaudeError::ControlProtocol(
+                    ControlProtocolError::new(format!(
+                        "Failed to add MCP server '{}': {}",
+                        name, error
+                    ))
+                    .with_kind(ControlErrorKind::Cli)
+                    .with_subtype("mcp_set_servers"),
+                )),
+                None => Ok(()),
+            }
+        });
+        match result {
+            Ok(()) => *runtime_servers = servers,
+            Err(_) => match replaced {
+                Some(server) => {
+                    self.sdk_mcp_servers.insert(name, server);
+                }
+                None => {
+                    self.sdk_mcp_servers.remove(&name);
+                }
+            },
+        }
+        result
+    }
+
+    /// Remove an MCP server added with [`add_mcp_server`](Self::add_mcp_server)
+    pub async fn remove_mcp_server(&self, name: &str) -> Result<()> {
+        let mut runtime_servers = self.runtime_mcp_servers.lock().await;
+        if !runtime_servers.contains_key(name) {
+            return Err(ClaudeError::InvalidConfig(format!(
+                "MCP server '{}' was not added at runtime",
+                name
+            )));
+        }
+
+        let mut servers = runtime_servers.clone();
+        servers.remove(name);
+        self.send_mcp_servers(&servers).await?;
+        self.sdk_mcp_servers.remove(name);
+        *runtime_servers = servers;
+        Ok(())
+    }
+
+    /// Re-add the runtime MCP servers to a restarted CLI
+    pub(crate) async fn restore_mcp_servers(&self) -> Result<()> {
+        let runtime_servers = self.runtime_mcp_servers.lock().await;
+        if runtime_servers.is_empty() {
+            return Ok(());
+        }
+        let result = self.send_mcp_servers(&runtime_servers).await?;
+        for (name, error) in result.errors {
+            warn!("Failed to restore MCP server '{}': {}", name, error);
+        }
         Ok(())
     }
 
+    /// Replace the CLI's set of runtime MCP servers
+    async fn send_mcp_servers(
+        &self,
+        servers: &HashMap<String, McpServerConfig>,
+    ) -> Result<McpSetServersResult> {
+        let servers: serde_json::Map<String, serde_json::Value> = servers
+            .iter()
+            .map(|(name, config)| (name.clone(), config.to_cli_config()))
+            .collect();
+        let request = json!({
+            "subtype": "mcp_set_servers",
+            "servers": servers
+        });
+
+        let response = self.send_control_request(request).await?;
+        // A success with no body reports no errors; anything else must parse
+        if response.is_null() {
+            return Ok(McpSetServersResult::default());
+        }
+        serde_json::from_value(response).map_err(|e| {
+            ClaudeError::ControlProtocol(
+                ControlProtocolError::new(format!("Invalid mcp_set_servers response: {}", e))
+                    .with_subtype("mcp_set_servers"),
+            )
+        })
+    }
+
+    /// Connection state of every MCP server the CLI knows about
+    pub async fn mcp_status(&self) -> Result<Vec<McpServerStatus>> {
+        let request = json!({"subtype": "mcp_status"});
+        let mut response = self.send_control_request(request).await?;
+        let servers = response
+            .get_mut("mcpServers")
+            .map(serde_json::Value::take)
+            .ok_or_else(|| "missing mcpServers".to_string())
+            .and_then(|servers| serde_json::from_value(servers).map_err(|e| e.to_string()));
+        servers.map_err(|e| {
+            ClaudeError::ControlProtocol(
+                ControlProtocolError::new(format!("Invalid mcp_status response: {}", e))
+                    .with_subtype("mcp_status"),
+            )
+        })
+    }
+
     /// Get server initialization info
     ///
     /// Returns the initialization result that was obtained during connect().
     /// This includes information about available commands, output styles, and server capabilities.
-    /// This is lock-free since initialization_result uses OnceLock.
-    pub fn get_initialization_result(&self) -> Option<serde_json::Value> {
-        self.initialization_result.get().cloned()
+    /// After a supervised restart this reflects the restarted CLI.
+    pub fn get_initialization_result(&self) -> Option<ServerInfo> {
+        self.initialization_result.read().unwrap().clone()
+    }
+
+    /// Capabilities of the current CLI, from its version and `initialize` response
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities::detect(
+            self.transport().cli_version().as_deref(),
+            self.initialization_result.read().unwrap().as_ref(),
+        )
     }
 
     /// Handle SDK MCP request by routing to the appropriate server
@@ -543,7 +968,10 @@ impl QueryFull {
             .get(server_name)
             .map(|r| r.clone())
             .ok_or_else(|| {
-                ClaudeError::ControlProtocol(format!("SDK MCP server not found: {}", server_name))
+                ClaudeError::ControlProtocol(ControlProtocolError::new(format!(
+                    "SDK MCP server not found: {}",
+                    server_name
+                )))
             })?;
 
         // Call the server's handle_message method
@@ -551,6 +979,175 @@ impl QueryFull {
             .instance
             .handle_message(message)
             .await
-            .map_err(|e| ClaudeError::ControlProtocol(format!("MCP server error: {}", e)))
+            .map_err(|e| {
+                ClaudeError::ControlProtocol(ControlProtocolError::new(format!(
+                    "MCP server error: {}",
+                    e
+                )))
+            })
+    }
+}
+
+/// Removes a pending control request when its sender stops waiting
+struct PendingRequest<'a> {
+    pending_responses: &'a DashMap<String, oneshot::Sender<ControlReply>>,
+    request_id: &'a str,
+}
+
+impl Drop for PendingRequest<'_> {
+    fn drop(&mut self) {
+        self.pending_responses.remove(self.request_id);
+    }
+}
+
+/// Text for the `error` field of a control error response
+fn control_error_message(error: &ClaudeError) -> String {
+    match error {
+        ClaudeError::ControlProtocol(error) => error.message.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use futures::stream::Stream;
+    use std::pin::Pin;
+
+    /// A CLI that reads requests and never answers, until input ends
+    struct SilentTransport {
+        tx: Mutex<Option<flume::Sender<serde_json::Value>>>,
+        rx: flume::Receiver<serde_json::Value>,
+        /// Sent back as the successful response to every control request, if set
+        reply: Option<serde_json::Value>,
+    }
+
+    impl SilentTransport {
+        fn new() -> Self {
+            let (tx, rx) = flume::unbounded();
+            Self {
+                tx: Mutex::new(Some(tx)),
+                rx,
+                reply: None,
+            }
+        }
+
+        fn replying(reply: serde_json::Value) -> Self {
+            Self {
+                reply: Some(reply),
+                ..Self::new()
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Transport for SilentTransport {
+        async fn connect(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn write(&self, data: &str) -> Result<()> {
+            let request: serde_json::Value = serde_json::from_str(data).unwrap();
+            if let (Some(reply), Some(tx)) = (&self.reply, self.tx.lock().unwrap().as_ref()) {
+                let _ = tx.send(json!({
+                    "type": "control_response",
+                    "response": {
+                        "subtype": "success",
+                        "request_id": request["request_id"],
+                        "response": reply
+                    }
+                }));
+            }
+            Ok(())
+        }
+
+        fn read_messages(
+            &self,
+        ) -> Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send + '_>> {
+            Box::pin(self.rx.stream().map(Ok))
+        }
+
+        async fn close(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn is_ready(&self) -> bool {
+            true
+        }
+
+        async fn end_input(&self) -> Result<()> {
+            self.tx.lock().unwrap().take();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pending_requests_are_removed_on_timeout_drop_and_disconnect() {
+        let transport = Arc::new(SilentTransport::new());
+        let query = Arc::new(QueryFull::new_with_transport(
+            Arc::clone(&transport) as Arc<dyn Transport>
+        ));
+        let shutdown_rx = query.start().await.unwrap();
+
+        // Timeout
+        let result = query
+            .interrupt(Some(Duration::from_millis(20)))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            result,
+            ClaudeError::ControlProtocol(ControlProtocolError {
+                kind: ControlErrorKind::Timeout,
+                ..
+            })
+        ));
+        assert!(query.pending_responses.is_empty());
+
+        // The caller gives up and drops the future
+        let dropped =
+            tokio::time::timeout(Duration::from_millis(20), query.set_model(None, None)).await;
+        assert!(dropped.is_err());
+        assert!(query.pending_responses.is_empty());
+
+        // The connection closes while a request waits
+        let waiting = tokio::spawn({
+            let query = Arc::clone(&query);
+            async move { query.interrupt(None).await }
+        });
+        while query.pending_responses.is_empty() {
+            tokio::task::yield_now().await;
+        }
+        transport.end_input().await.unwrap();
+        shutdown_rx.await.unwrap();
+        assert!(matches!(
+            waiting.await.unwrap(),
+            Err(ClaudeError::ControlProtocol(ControlProtocolError {
+                kind: ControlErrorKind::Disconnected,
+                ..
+            }))
+        ));
+        assert!(query.pending_responses.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_malformed_mcp_responses_are_protocol_errors() {
+        for reply in [json!([1, 2]), json!({"errors": "none"})] {
+            let query = QueryFull::new_with_transport(Arc::new(SilentTransport::replying(reply)));
+            query.start().await.unwrap();
+
+            for result in [
+                query.mcp_status().await.map(drop),
+                query.send_mcp_servers(&HashMap::new()).await.map(drop),
+            ] {
+                assert!(matches!(
+                    result,
+                    Err(ClaudeError::ControlProtocol(ControlProtocolError {
+                        kind: ControlErrorKind::Protocol,
+                        ..
+                    }))
+                ));
+            }
+        }
     }
 }
@@ -0,0 +1,201 @@
+//! Resource limits for the CLI subprocess (Linux only)
+
+use crate::types::config::{IoPriority, ResourceLimitKind, ResourceLimits};
+
+/// Extra CPU seconds between `SIGXCPU` (soft limit) and `SIGKILL` (hard limit)
+///
+/// Keeping the hard limit above the soft one makes the kernel send `SIGXCPU`
+/// first, so the exit can be attributed to the CPU limit.
+const CPU_HARD_LIMIT_GRACE_SECS: u64 = 1;
+
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+
+#[cfg(target_env = "gnu")]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(target_env = "gnu"))]
+type Resource = libc::c_int;
+
+/// Apply rlimits, niceness and I/O priority to the calling process
+///
+/// Limits above the inherited hard limit are clamped to it rather than failing,
+/// since raising a hard limit requires privileges.
+pub fn apply_resource_limits(limits: &ResourceLimits) -> std::io::Result<()> {
+    if let Some(seconds) = limits.cpu_seconds {
+        set_rlimit(
+            libc::RLIMIT_CPU,
+            seconds,
+            seconds.saturating_add(CPU_HARD_LIMIT_GRACE_SECS),
+        )?;
+    }
+    let others = [
+        (libc::RLIMIT_AS, limits.address_space),
+        (libc::RLIMIT_NOFILE, limits.open_files),
+        (libc::RLIMIT_NPROC, limits.processes),
+        (libc::RLIMIT_CORE, limits.core_size),
+        (libc::RLIMIT_FSIZE, limits.file_size),
+    ];
+    for (resource, value) in others {
+        if let Some(value) = value {
+            set_rlimit(resource, value, value)?;
+        }
+    }
+
+    if let Some(nice) = limits.nice {
+        // SAFETY: setpriority has no memory-safety preconditions
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    if let Some(priority) = limits.io_priority {
+        let (class, level) = match priority {
+            IoPriority::RealTime(level) => (1, level.min(7)),
+            IoPriority::BestEffort(level) => (2, level.min(7)),
+            IoPriority::Idle => (3, 0),
+        };
+        let ioprio = (class << IOPRIO_CLASS_SHIFT) | libc::c_int::from(level);
+        // SAFETY: ioprio_set takes only integer arguments
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+fn set_rlimit(resource: Resource, soft: u64, hard: u64) -> std::io::Result<()> {
+    let mut current = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: `current` is a valid, writable rlimit
+    if unsafe { libc::getrlimit(resource, &mut current) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let hard = hard.min(current.rlim_max);
+    let limit = libc::rlimit {
+        rlim_cur: soft.min(hard),
+        rlim_max: hard,
+    };
+    // SAFETY: `limit` is a valid rlimit
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The configured limit a failed CLI most likely ran into
+///
+/// CPU and file size limits are identified by their signals. The others make
+/// system calls fail, so they are only reported when stderr names the specific
+/// failure: ENOMEM for the address space, EMFILE for open files, and a failed
+/// fork or spawn for processes. A crash alone (e.g. SIGSEGV) is not attributed.
+pub fn exceeded_limit(
+    limits: &ResourceLimits,
+    signal: Option<i32>,
+    stderr_tail: &[String],
+) -> Option<ResourceLimitKind> {
+    match signal {
+        Some(libc::SIGXCPU) if limits.cpu_seconds.is_some() => {
+            return Some(ResourceLimitKind::CpuTime);
+        }
+        Some(libc::SIGXFSZ) if limits.file_size.is_some() => {
+            return Some(ResourceLimitKind::FileSize);
+        }
+        _ => {}
+    }
+
+    let lines: Vec<String> = stderr_tail.iter().map(|line| line.to_lowercase()).collect();
+    let mentions = |needles: &[&str]| {
+        lines
+            .iter()
+            .any(|line| needles.iter().any(|needle| line.contains(needle)))
+    };
+
+    if limits.address_space.is_some()
+        && mentions(&[
+            "enomem",
+            "cannot allocate memory",
+            "fatal process out of memory",
+        ])
+    {
+        return Some(ResourceLimitKind::AddressSpace);
+    }
+    if limits.open_files.is_some() && mentions(&["emfile", "too many open files"]) {
+        return Some(ResourceLimitKind::OpenFiles);
+    }
+    // EAGAIN on its own is too common to blame on the process limit
+    let failed_fork = lines.iter().any(|line| {
+        (line.contains("fork") || line.contains("spawn"))
+            && (line.contains("eagain") || line.contains("resource temporarily unavailable"))
+    });
+    if limits.processes.is_some() && failed_fork {
+        return Some(ResourceLimitKind::Processes);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exceeded_limit_from_signal() {
+        let limits = ResourceLimits::builder()
+            .cpu_seconds(1)
+            .file_size(1024)
+            .build();
+        assert_eq!(
+            exceeded_limit(&limits, Some(libc::SIGXCPU), &[]),
+            Some(ResourceLimitKind::CpuTime)
+        );
+        assert_eq!(
+            exceeded_limit(&limits, Some(libc::SIGXFSZ), &[]),
+            Some(ResourceLimitKind::FileSize)
+        );
+        // Signals for limits that were not configured are not attributed
+        assert_eq!(
+            exceeded_limit(&ResourceLimits::default(), Some(libc::SIGXCPU), &[]),
+            None
+        );
+        // Nor are crashes that any bug could cause
+        let limits = ResourceLimits::builder().address_space(1 << 30).build();
+        assert_eq!(exceeded_limit(&limits, Some(libc::SIGSEGV), &[]), None);
+        assert_eq!(exceeded_limit(&limits, Some(libc::SIGABRT), &[]), None);
+    }
+
+    #[test]
+    fn test_exceeded_limit_from_stderr() {
+        let limits = ResourceLimits::builder()
+            .address_space(1 << 30)
+            .open_files(64)
+            .processes(32)
+            .build();
+        let oom = vec!["Error: ENOMEM: not enough memory, read".to_string()];
+        assert_eq!(
+            exceeded_limit(&limits, None, &oom),
+            Some(ResourceLimitKind::AddressSpace)
+        );
+        // V8's own heap limit is not the address space limit
+        let heap = vec!["FATAL ERROR: JavaScript heap out of memory".to_string()];
+        assert_eq!(exceeded_limit(&limits, None, &heap), None);
+        let emfile = vec!["Error: EMFILE: too many open files, open 'a.txt'".to_string()];
+        assert_eq!(
+            exceeded_limit(&limits, None, &emfile),
+            Some(ResourceLimitKind::OpenFiles)
+        );
+        let fork = vec!["bash: fork: retry: Resource temporarily unavailable".to_string()];
+        assert_eq!(
+            exceeded_limit(&limits, None, &fork),
+            Some(ResourceLimitKind::Processes)
+        );
+        let eagain = vec!["Error: read EAGAIN".to_string()];
+        assert_eq!(exceeded_limit(&limits, None, &eagain), None);
+        assert_eq!(
+            exceeded_limit(&limits, None, &["some other failure".to_string()]),
+            None
+        );
+    }
+}
@@ -0,0 +1,291 @@
+//! Hooks added and removed after connect
+//!
+//! The CLI only learns about hooks in the `initialize` request, and offers no
+//! control request to change them later. With
+//! [`runtime_hooks`](crate::ClaudeAgentOptions::runtime_hooks) enabled, the SDK
+//! registers one gate hook per event at `initialize`; each gate runs whichever
+//! runtime hooks are currently registered for its event.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use regex::Regex;
+use tracing::warn;
+
+use crate::errors::{ClaudeError, Result};
+use crate::types::hooks::{
+    HookCallback, HookContext, HookEvent, HookHandle, HookInput, HookJsonOutput, HookMatcher,
+    SyncHookJsonOutput,
+};
+
+/// Every event a gate is registered for
+const EVENTS: [HookEvent; 6] = [
+    HookEvent::PreToolUse,
+    HookEvent::PostToolUse,
+    HookEvent::UserPromptSubmit,
+    HookEvent::Stop,
+    HookEvent::SubagentStop,
+    HookEvent::PreCompact,
+];
+
+/// Hooks registered at runtime, shared by the client and the gates
+#[derive(Default)]
+pub struct RuntimeHooks {
+    next_id: AtomicU64,
+    /// Keyed by ID, so iteration follows registration order
+    hooks: RwLock<BTreeMap<u64, RuntimeHook>>,
+}
+
+struct RuntimeHook {
+    event: HookEvent,
+    pattern: Pattern,
+    matcher: HookMatcher,
+}
+
+impl RuntimeHooks {
+    /// Register a matcher for `event`
+    ///
+    /// Fails if `matcher.matcher` is not a valid regular expression.
+    pub fn add(&self, event: HookEvent, matcher: HookMatcher) -> Result<HookHandle> {
+        let pattern = Pattern::new(matcher.matcher.as_deref())?;
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.hooks.write().unwrap().insert(
+            id,
+            RuntimeHook {
+                event,
+                pattern,
+                matcher,
+            },
+        );
+        Ok(HookHandle { id, event })
+    }
+
+    /// Unregister a matcher; returns whether it was still registered
+    pub fn remove(&self, handle: HookHandle) -> bool {
+        self.hooks.write().unwrap().remove(&handle.id).is_some()
+    }
+
+    /// Gate hooks to register at `initialize`, one catch-all matcher per event
+    pub fn gates(self: &Arc<Self>) -> HashMap<HookEvent, Vec<HookMatcher>> {
+        EVENTS
+            .into_iter()
+            .map(|event| {
+                let hooks = Arc::clone(self);
+                let gate: HookCallback = Arc::new(move |input, tool_use_id, context| {
+                    let hooks = Arc::clone(&hooks);
+                    Box::pin(
+                        async move { hooks.dispatch(event, input, tool_use_id, context).await },
+                    )
+                });
+                (
+                    event,
+                    vec![HookMatcher::builder().hooks(vec![gate]).build()],
+                )
+            })
+            .collect()
+    }
+
+    /// Run the hooks registered for `event` that match `input`, in registration order
+    ///
+    /// Every matching hook runs; the first one to return a non-empty output decides
+    /// the result.
+    async fn dispatch(
+        &self,
+        event: HookEvent,
+        input: HookInput,
+        tool_use_id: Option<String>,
+        context: HookContext,
+    ) -> HookJsonOutput {
+        let matchers: Vec<HookMatcher> = self
+            .hooks
+            .read()
+            .unwrap()
+            .values()
+            .filter(|hook| hook.event == event && matches(&hook.pattern, &input))
+            .map(|hook| hook.matcher.clone())
+            .collect();
+
+        let mut result = None;
+        for matcher in matchers {
+            for hook in &matcher.hooks {
+                let output = hook(input.clone(), tool_use_id.clone(), context.clone());
+                let output = match matcher.timeout {
+                    Some(timeout) => {
+                        match tokio::time::timeout(Duration::from_secs_f64(timeout), output).await {
+                            Ok(output) => output,
+                            Err(_) => {
+                                warn!("Runtime {:?} hook timed out after {}s", event, timeout);
+                                continue;
+                            }
+                        }
+                    }
+                    None => output.await,
+                };
+                if result.is_none() && !is_empty(&output) {
+                    result = Some(output);
+                }
+            }
+        }
+        result.unwrap_or_else(|| HookJsonOutput::Sync(SyncHookJsonOutput::default()))
+    }
+}
+
+/// A hook matcher pattern, interpreted the way the CLI interprets it
+///
+/// No pattern, an empty one or `*` matches everything. A pattern of only letters,
+/// digits, `_` and `|` lists exact names. Anything else is a regular expression
+/// that may match anywhere in the name, so `Notebook.*` matches `NotebookEdit`.
+enum Pattern {
+    Any,
+    Exact(Vec<String>),
+    Regex(Regex),
+}
+
+impl Pattern {
+    fn new(pattern: Option<&str>) -> Result<Self> {
+        let pattern = match pattern {
+            None | Some("") | Some("*") => return Ok(Pattern::Any),
+            Some(pattern) => pattern,
+        };
+        if pattern
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '|')
+        {
+            return Ok(Pattern::Exact(
+                pattern.split('|').map(str::to_string).collect(),
+            ));
+        }
+        Regex::new(pattern).map(Pattern::Regex).map_err(|e| {
+            ClaudeError::InvalidConfig(format!("Invalid hook matcher '{}': {}", pattern, e))
+        })
+    }
+
+    fn matches(&self, target: &str) -> bool {
+        match self {
+            Pattern::Any => true,
+            Pattern::Exact(names) => names.iter().any(|name| name == target),
+            Pattern::Regex(regex) => regex.is_match(target),
+        }
+    }
+}
+
+/// Whether a matcher pattern applies to `input`
+///
+/// Patterns match the tool name, or the compaction trigger for `PreCompact`.
+/// Events without either match any pattern.
+fn matches(pattern: &Pattern, input: &HookInput) -> bool {
+    let target = match input {
+        HookInput::PreToolUse(input) => &input.tool_name,
+        HookInput::PostToolUse(input) => &input.tool_name,
+        HookInput::PreCompact(input) => &input.trigger,
+        _ => return true,
+    };
+    pattern.matches(target)
+}
+
+/// Whether a hook output leaves the CLI's behavior unchanged
+fn is_empty(output: &HookJsonOutput) -> bool {
+    serde_json::to_value(output).is_ok_and(|value| value == serde_json::json!({}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::hooks::PreToolUseHookInput;
+    use std::sync::Mutex;
+
+    fn pre_tool_use(tool_name: &str) -> HookInput {
+        HookInput::PreToolUse(PreToolUseHookInput {
+            session_id: "session".to_string(),
+            transcript_path: "/tmp/transcript".to_string(),
+            cwd: "/tmp".to_string(),
+            permission_mode: None,
+            tool_name: tool_name.to_string(),
+            tool_input: serde_json::json!({}),
+        })
+    }
+
+    fn recording_hook(name: &'static str, calls: Arc<Mutex<Vec<&'static str>>>) -> HookCallback {
+        Arc::new(move |_, _, _| {
+            calls.lock().unwrap().push(name);
+            Box::pin(async { HookJsonOutput::Sync(SyncHookJsonOutput::default()) })
+        })
+    }
+
+    #[test]
+    fn test_matches_tool_name_patterns() {
+        let matches = |pattern: Option<&str>, tool_name: &str| {
+            matches(&Pattern::new(pattern).unwrap(), &pre_tool_use(tool_name))
+        };
+        assert!(matches(None, "Bash"));
+        assert!(matches(Some("*"), "Read"));
+        assert!(matches(Some("Bash"), "Bash"));
+        assert!(!matches(Some("Bash"), "Write"));
+        // Plain names match exactly, not as a prefix
+        assert!(!matches(Some("Bash"), "BashOutput"));
+        assert!(matches(Some("Edit|Write"), "Write"));
+        // Anything else is an unanchored regex
+        assert!(matches(Some("Notebook.*"), "NotebookEdit"));
+        assert!(matches(Some("mcp__db__.*"), "mcp__db__query"));
+        assert!(!matches(Some("^mcp__db__.*"), "mcp__other__query"));
+        assert!(matches(Some("Web(Fetch|Search)"), "WebSearch"));
+        assert!(matches!(
+            Pattern::new(Some("Edit(")),
+            Err(ClaudeError::InvalidConfig(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_gate_runs_registered_hooks_until_removed() {
+        let hooks = Arc::new(RuntimeHooks::default());
+        let gate = Arc::clone(&hooks.gates()[&HookEvent::PreToolUse][0].hooks[0]);
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let audit = hooks
+            .add(
+                HookEvent::PreToolUse,
+                HookMatcher::builder()
+                    .hooks(vec![recording_hook("audit", Arc::clone(&calls))])
+                    .build(),
+            )
+            .unwrap();
+        let block: HookCallback = Arc::new(|_, _, _| {
+            Box::pin(async {
+                HookJsonOutput::Sync(SyncHookJsonOutput::builder().decision("block").build())
+            })
+        });
+        hooks
+            .add(
+                HookEvent::PreToolUse,
+                HookMatcher::builder()
+                    .matcher("Bash")
+                    .hooks(vec![block])
+                    .build(),
+            )
+            .unwrap();
+        hooks
+            .add(
+                HookEvent::PostToolUse,
+                HookMatcher::builder()
+                    .hooks(vec![recording_hook("post", Arc::clone(&calls))])
+                    .build(),
+            )
+            .unwrap();
+
+        let output = gate(pre_tool_use("Bash"), None, HookContext::default()).await;
+        assert!(matches!(
+            output,
+            HookJsonOutput::Sync(SyncHookJsonOutput { decision: Some(ref d), .. }) if d == "block"
+        ));
+        let output = gate(pre_tool_use("Read"), None, HookContext::default()).await;
+        assert!(is_empty(&output));
+        assert_eq!(*calls.lock().unwrap(), ["audit", "audit"]);
+
+        assert!(hooks.remove(audit));
+        assert!(!hooks.remove(audit));
+        gate(pre_tool_use("Read"), None, HookContext::default()).await;
+        assert_eq!(calls.lock().unwrap().len(), 2);
+    }
+}
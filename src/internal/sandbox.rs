@@ -1,56 +1,268 @@
-//! Landlock filesystem sandbox for restricting subprocess write access (Linux only).
+//! Landlock sandbox for restricting subprocess filesystem and network access (Linux only).
 
-use std::path::PathBuf;
+use std::io::Read;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
 
 use landlock::{
-    Access, AccessFs, PathBeneath, PathFd, Ruleset, RulesetAttr, RulesetCreatedAttr,
-    RulesetStatus, ABI,
+    ABI, Access, AccessFs, AccessNet, BitFlags, CompatLevel, Compatible, LandlockStatus, NetPort,
+    PathBeneath, PathFd, Ruleset, RulesetAttr, RulesetCreated, RulesetCreatedAttr, RulesetStatus,
 };
 
-/// Apply a Landlock sandbox that restricts filesystem writes to the given directories.
+use crate::types::config::{LandlockMode, LandlockSandboxConfig};
+use crate::types::sandbox::{LandlockEnforcement, LandlockStatus as SandboxLandlockStatus};
+
+type SandboxResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Filesystem rights are requested at ABI v3 (Linux 6.2+, includes Truncate)
+const FS_ABI: ABI = ABI::V3;
+
+/// A Landlock ruleset built in the parent, enforced in the child before `exec`
 ///
-/// - Reads are allowed everywhere (`/`).
-/// - Execute is allowed everywhere (needed for bash, git, etc.).
-/// - Writes are allowed only in `writable_roots`, `/tmp`, and `~/.claude`.
+/// Building the ruleset walks directories and opens paths, which is not safe
+/// to do between `fork` and `exec`; only `restrict_self` runs in the child.
+pub struct PreparedSandbox {
+    ruleset: Option<RulesetCreated>,
+    mode: LandlockMode,
+    report: OwnedFd,
+}
+
+impl PreparedSandbox {
+    /// Enforce the ruleset on the calling process and report the result to the parent
+    pub fn restrict_self(&mut self) -> std::io::Result<()> {
+        let Some(ruleset) = self.ruleset.take() else {
+            return Ok(());
+        };
+        let status = ruleset.restrict_self().map_err(std::io::Error::other)?;
+        if self.mode == LandlockMode::Strict && status.ruleset != RulesetStatus::FullyEnforced {
+            return Err(std::io::Error::other(
+                "Landlock sandbox is not fully enforced by this kernel",
+            ));
+        }
+
+        let enforcement = match status.ruleset {
+            RulesetStatus::FullyEnforced => 2,
+            RulesetStatus::PartiallyEnforced => 1,
+            RulesetStatus::NotEnforced => 0,
+        };
+        let abi = match status.landlock {
+            LandlockStatus::Available {
+                effective_abi,
+                kernel_abi,
+            } => kernel_abi.unwrap_or(effective_abi as i32).clamp(0, 255) as u8,
+            _ => 0,
+        };
+        let report = [enforcement, abi];
+        // SAFETY: writes two bytes from a live buffer to an fd we own
+        let written = unsafe { libc::write(self.report.as_raw_fd(), report.as_ptr().cast(), 2) };
+        if written != 2 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// Parent side of the pipe the child reports its Landlock status on
+pub struct StatusReceiver(std::fs::File);
+
+impl StatusReceiver {
+    /// Read the status the child reported before `exec`
+    ///
+    /// Only call this after the spawn succeeded; the child has written its
+    /// report by then, so this doesn't block.
+    pub fn receive(mut self) -> std::io::Result<SandboxLandlockStatus> {
+        let mut report = [0u8; 2];
+        self.0.read_exact(&mut report)?;
+        let enforcement = match report[0] {
+            2 => LandlockEnforcement::FullyEnforced,
+            1 => LandlockEnforcement::PartiallyEnforced,
+            _ => LandlockEnforcement::NotEnforced,
+        };
+        Ok(SandboxLandlockStatus {
+            enforcement,
+            abi: Some(u32::from(report[1])).filter(|abi| *abi > 0),
+        })
+    }
+}
+
+fn status_pipe() -> std::io::Result<(StatusReceiver, OwnedFd)> {
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for the two descriptors pipe2 returns
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: pipe2 succeeded, so both descriptors are open and owned by us
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    Ok((StatusReceiver(std::fs::File::from(read)), write))
+}
+
+/// Build the Landlock ruleset described by `config`
 ///
-/// Falls back gracefully if the kernel doesn't support Landlock.
-pub fn apply_landlock_sandbox(
-    writable_roots: &[PathBuf],
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let abi = ABI::V3; // Linux 6.2+, includes Truncate
+/// - Reads and execution are allowed beneath `readable_roots` (everywhere if unset).
+/// - Writes are allowed beneath `writable_roots` and the existing `extra_writable_paths`,
+///   or everywhere if `writable_roots` is empty.
+/// - Nothing beneath `denied_paths` is accessible.
+/// - TCP bind/connect are limited to the listed ports when a port list is set.
+///
+/// In [`LandlockMode::Strict`], features the kernel doesn't support are errors.
+pub fn prepare_landlock_sandbox(
+    config: &LandlockSandboxConfig,
+) -> SandboxResult<(PreparedSandbox, StatusReceiver)> {
+    let compat_level = match config.mode {
+        LandlockMode::BestEffort => CompatLevel::BestEffort,
+        LandlockMode::Strict => CompatLevel::HardRequirement,
+    };
+    let read_access = AccessFs::from_read(FS_ABI);
+    let all_access = AccessFs::from_all(FS_ABI);
 
-    let read_access = AccessFs::from_read(abi);
-    let all_access = AccessFs::from_all(abi);
+    let mut ruleset = Ruleset::default()
+        .set_compatibility(compat_level)
+        .handle_access(all_access)?;
+    if config.tcp_bind_ports.is_some() {
+        ruleset = ruleset.handle_access(AccessNet::BindTcp)?;
+    }
+    if config.tcp_connect_ports.is_some() {
+        ruleset = ruleset.handle_access(AccessNet::ConnectTcp)?;
+    }
+    let mut ruleset = ruleset.create()?;
 
-    let mut ruleset = Ruleset::default().handle_access(all_access)?.create()?;
+    let denied: Vec<PathBuf> = config
+        .denied_paths
+        .iter()
+        .map(|path| canonical(path))
+        .collect();
 
-    // Allow read access everywhere
-    ruleset = ruleset.add_rule(PathBeneath::new(PathFd::new("/")?, read_access))?;
+    let readable_roots = match &config.readable_roots {
+        Some(roots) => roots.clone(),
+        None => vec![PathBuf::from("/")],
+    };
+    for root in &readable_roots {
+        for path in paths_excluding(&canonical(root), &denied) {
+            // Unreadable entries (e.g. other users' files) are inaccessible anyway
+            if let Ok(fd) = PathFd::new(&path) {
+                ruleset = ruleset.add_rule(PathBeneath::new(fd, access_for(&path, read_access)))?;
+            }
+        }
+    }
 
-    // Allow execute everywhere (needed for bash, git, etc.)
-    ruleset = ruleset.add_rule(PathBeneath::new(PathFd::new("/")?, AccessFs::Execute))?;
+    if config.writable_roots.is_empty() {
+        // No writable roots leaves writes unrestricted, apart from the denied paths
+        ruleset = add_write_rules(ruleset, Path::new("/"), &denied, all_access)?;
+    }
+    for root in &config.writable_roots {
+        let root = canonical(root);
+        // Fail early on a missing writable root rather than silently dropping it
+        PathFd::new(&root)?;
+        ruleset = add_write_rules(ruleset, &root, &denied, all_access)?;
+    }
+    for path in config
+        .extra_writable_paths
+        .iter()
+        .filter(|_| !config.writable_roots.is_empty())
+    {
+        if path.exists() {
+            ruleset = add_write_rules(ruleset, &canonical(path), &denied, all_access)?;
+        }
+    }
 
-    // Allow write access to specified writable roots
-    for root in writable_roots {
-        ruleset = ruleset.add_rule(PathBeneath::new(PathFd::new(root)?, all_access))?;
+    for port in config.tcp_bind_ports.iter().flatten() {
+        ruleset = ruleset.add_rule(NetPort::new(*port, AccessNet::BindTcp))?;
+    }
+    for port in config.tcp_connect_ports.iter().flatten() {
+        ruleset = ruleset.add_rule(NetPort::new(*port, AccessNet::ConnectTcp))?;
     }
 
-    // Allow writes to /tmp (Claude Code needs temp files)
-    ruleset = ruleset.add_rule(PathBeneath::new(PathFd::new("/tmp")?, all_access))?;
+    let (receiver, report) = status_pipe()?;
+    let sandbox = PreparedSandbox {
+        ruleset: Some(ruleset),
+        mode: config.mode,
+        report,
+    };
+    Ok((sandbox, receiver))
+}
 
-    // Allow writes to home config dirs (Claude Code session state)
-    if let Ok(home) = std::env::var("HOME") {
-        let claude_dir = PathBuf::from(&home).join(".claude");
-        if claude_dir.exists() {
-            ruleset =
-                ruleset.add_rule(PathBeneath::new(PathFd::new(&claude_dir)?, all_access))?;
+fn add_write_rules(
+    mut ruleset: RulesetCreated,
+    root: &Path,
+    denied: &[PathBuf],
+    access: BitFlags<AccessFs>,
+) -> SandboxResult<RulesetCreated> {
+    for path in paths_excluding(root, denied) {
+        if let Ok(fd) = PathFd::new(&path) {
+            ruleset = ruleset.add_rule(PathBeneath::new(fd, access_for(&path, access)))?;
         }
     }
+    Ok(ruleset)
+}
+
+/// Directory-only rights are invalid on a file rule
+fn access_for(path: &Path, access: BitFlags<AccessFs>) -> BitFlags<AccessFs> {
+    if path.is_dir() {
+        access
+    } else {
+        access & AccessFs::from_file(FS_ABI)
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
 
-    let status = ruleset.restrict_self()?;
-    if status.ruleset == RulesetStatus::NotEnforced {
-        eprintln!("Warning: Landlock sandbox not enforced (kernel may not support it)");
+/// Paths whose rules together cover `root` except for everything beneath `denied`
+///
+/// Landlock only grants access, so a denied path is carved out by granting
+/// access to each of its siblings instead of to its parent, up to `root`.
+/// Symlinks are skipped so a rule can't follow one into a denied path.
+///
+/// The siblings are listed now, so entries created later directly inside a
+/// carved parent are not covered by any rule.
+fn paths_excluding(root: &Path, denied: &[PathBuf]) -> Vec<PathBuf> {
+    if denied.iter().any(|path| root.starts_with(path)) {
+        return Vec::new();
     }
+    if !denied.iter().any(|path| path.starts_with(root)) {
+        return vec![root.to_path_buf()];
+    }
+
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| !kind.is_symlink()))
+        .flat_map(|entry| paths_excluding(&entry.path(), denied))
+        .collect()
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths_excluding_carves_out_denied_paths() {
+        let root = std::env::temp_dir().join(format!("claude-landlock-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("home/.ssh")).unwrap();
+        std::fs::create_dir_all(root.join("home/project")).unwrap();
+        std::fs::write(root.join("home/.bashrc"), "").unwrap();
+        std::fs::create_dir_all(root.join("usr")).unwrap();
+        std::os::unix::fs::symlink(root.join("home/.ssh"), root.join("home/keys")).unwrap();
+
+        let mut paths = paths_excluding(&root, &[root.join("home/.ssh")]);
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                root.join("home/.bashrc"),
+                root.join("home/project"),
+                root.join("usr"),
+            ]
+        );
+
+        // Nothing to carve out keeps the root as a single rule
+        assert_eq!(paths_excluding(&root, &[]), vec![root.clone()]);
+        // A root beneath a denied path grants nothing
+        assert!(paths_excluding(&root.join("home/.ssh"), &[root.join("home")]).is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -0,0 +1,447 @@
+//! Seccomp BPF syscall filter for the CLI subprocess (Linux only)
+
+use std::collections::BTreeSet;
+
+use crate::errors::{ClaudeError, Result};
+use crate::types::config::{SeccompAction, SeccompPreset, SeccompProfile};
+
+/// Syscalls whose numbers are known on every supported architecture
+macro_rules! syscall_numbers {
+    ($($name:ident),* $(,)?) => {
+        fn common_syscall_number(name: &str) -> Option<libc::c_long> {
+            paste::paste! {
+                match name {
+                    $(stringify!($name) => Some(libc::[<SYS_ $name>]),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+syscall_numbers!(
+    accept,
+    accept4,
+    acct,
+    add_key,
+    adjtimex,
+    bind,
+    bpf,
+    capset,
+    chroot,
+    clock_adjtime,
+    clock_settime,
+    clone,
+    clone3,
+    connect,
+    delete_module,
+    execve,
+    execveat,
+    fanotify_init,
+    fchmod,
+    fchmodat,
+    fchown,
+    fchownat,
+    finit_module,
+    fsconfig,
+    fsmount,
+    fsopen,
+    fspick,
+    ftruncate,
+    init_module,
+    io_uring_enter,
+    io_uring_register,
+    io_uring_setup,
+    ioctl,
+    kcmp,
+    kexec_file_load,
+    kexec_load,
+    keyctl,
+    kill,
+    landlock_add_rule,
+    landlock_create_ruleset,
+    landlock_restrict_self,
+    linkat,
+    listen,
+    lookup_dcookie,
+    mbind,
+    memfd_create,
+    migrate_pages,
+    mkdirat,
+    mknodat,
+    mount,
+    move_mount,
+    move_pages,
+    name_to_handle_at,
+    open_by_handle_at,
+    open_tree,
+    perf_event_open,
+    personality,
+    pidfd_getfd,
+    pidfd_open,
+    pidfd_send_signal,
+    pivot_root,
+    prctl,
+    process_vm_readv,
+    process_vm_writev,
+    ptrace,
+    quotactl,
+    reboot,
+    recvfrom,
+    recvmsg,
+    renameat2,
+    request_key,
+    seccomp,
+    sendmsg,
+    sendto,
+    set_mempolicy,
+    setdomainname,
+    setgid,
+    setgroups,
+    sethostname,
+    setns,
+    setregid,
+    setresgid,
+    setresuid,
+    setreuid,
+    settimeofday,
+    setuid,
+    socket,
+    socketpair,
+    swapoff,
+    swapon,
+    symlinkat,
+    syslog,
+    tgkill,
+    tkill,
+    truncate,
+    umount2,
+    unlinkat,
+    unshare,
+    userfaultfd,
+    vhangup,
+);
+
+#[cfg(target_arch = "x86_64")]
+fn arch_syscall_number(name: &str) -> Option<libc::c_long> {
+    match name {
+        "_sysctl" => Some(libc::SYS__sysctl),
+        "chmod" => Some(libc::SYS_chmod),
+        "chown" => Some(libc::SYS_chown),
+        "fork" => Some(libc::SYS_fork),
+        "ioperm" => Some(libc::SYS_ioperm),
+        "iopl" => Some(libc::SYS_iopl),
+        "mknod" => Some(libc::SYS_mknod),
+        "rename" => Some(libc::SYS_rename),
+        "renameat" => Some(libc::SYS_renameat),
+        "unlink" => Some(libc::SYS_unlink),
+        "uselib" => Some(libc::SYS_uselib),
+        "vfork" => Some(libc::SYS_vfork),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn arch_syscall_number(_name: &str) -> Option<libc::c_long> {
+    None
+}
+
+/// `AUDIT_ARCH_*` value the kernel reports for this build's syscall ABI
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: Option<u32> = Some(0xC000_003E);
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: Option<u32> = Some(0xC000_00B7);
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH: Option<u32> = None;
+
+/// Syscall numbers with this bit set use the x32 ABI, which would bypass the filter
+#[cfg(target_arch = "x86_64")]
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+const DEFAULT_DENY: &[&str] = &[
+    "acct",
+    "add_key",
+    "adjtimex",
+    "bpf",
+    "clock_adjtime",
+    "clock_settime",
+    "delete_module",
+    "finit_module",
+    "fsconfig",
+    "fsmount",
+    "fsopen",
+    "fspick",
+    "init_module",
+    "kexec_file_load",
+    "kexec_load",
+    "keyctl",
+    "lookup_dcookie",
+    "mount",
+    "move_mount",
+    "open_by_handle_at",
+    "open_tree",
+    "perf_event_open",
+    "pivot_root",
+    "process_vm_readv",
+    "process_vm_writev",
+    "ptrace",
+    "quotactl",
+    "reboot",
+    "request_key",
+    "setdomainname",
+    "sethostname",
+    "settimeofday",
+    "swapoff",
+    "swapon",
+    "syslog",
+    "umount2",
+    "userfaultfd",
+    "vhangup",
+    #[cfg(target_arch = "x86_64")]
+    "_sysctl",
+    #[cfg(target_arch = "x86_64")]
+    "ioperm",
+    #[cfg(target_arch = "x86_64")]
+    "iopl",
+    #[cfg(target_arch = "x86_64")]
+    "uselib",
+];
+
+const STRICT_DENY: &[&str] = &[
+    "chroot",
+    "io_uring_enter",
+    "io_uring_register",
+    "io_uring_setup",
+    "kcmp",
+    "mbind",
+    "migrate_pages",
+    "move_pages",
+    "name_to_handle_at",
+    "personality",
+    "set_mempolicy",
+    "setns",
+    "unshare",
+];
+
+/// Pseudo-syscall name for raw and packet sockets, which are told apart by `socket` arguments
+const RAW_SOCKETS: &str = "raw_sockets";
+
+/// Pseudo-syscall name for IPv4 and IPv6 sockets
+const INET_SOCKETS: &str = "inet_sockets";
+
+/// Offsets into `struct seccomp_data`
+const DATA_NR: u32 = 0;
+const DATA_ARCH: u32 = 4;
+#[cfg(target_endian = "little")]
+const DATA_ARGS: u32 = 16;
+#[cfg(target_endian = "big")]
+const DATA_ARGS: u32 = 20;
+
+/// A compiled seccomp filter, installed in the child before `exec`
+pub struct SeccompFilter {
+    program: Vec<libc::sock_filter>,
+}
+
+impl SeccompFilter {
+    /// Compile `profile`, rejecting unknown syscall names
+    pub fn new(profile: &SeccompProfile) -> Result<Self> {
+        let arch = AUDIT_ARCH.ok_or_else(|| {
+            ClaudeError::InvalidConfig(
+                "Seccomp profiles are not supported on this architecture".to_string(),
+            )
+        })?;
+
+        let mut blocked: BTreeSet<String> = BTreeSet::new();
+        let preset: Vec<&str> = match profile.preset {
+            SeccompPreset::Default => [DEFAULT_DENY, &[RAW_SOCKETS]].concat(),
+            SeccompPreset::Strict => [DEFAULT_DENY, STRICT_DENY, &[RAW_SOCKETS]].concat(),
+            SeccompPreset::NetworkOff => [DEFAULT_DENY, &[RAW_SOCKETS, INET_SOCKETS]].concat(),
+            SeccompPreset::Empty => Vec::new(),
+        };
+        blocked.extend(preset.into_iter().map(str::to_string));
+        blocked.extend(profile.deny.iter().cloned());
+        for name in &profile.allow {
+            blocked.remove(name);
+        }
+
+        let mut numbers = Vec::new();
+        for name in &blocked {
+            if name == RAW_SOCKETS || name == INET_SOCKETS {
+                continue;
+            }
+            let number = common_syscall_number(name)
+                .or_else(|| arch_syscall_number(name))
+                .ok_or_else(|| {
+                    ClaudeError::InvalidConfig(format!(
+                        "Unknown syscall in seccomp profile: {}",
+                        name
+                    ))
+                })?;
+            numbers.push(number as u32);
+        }
+
+        let deny = match profile.action {
+            SeccompAction::Errno => libc::SECCOMP_RET_ERRNO | libc::EPERM as u32,
+            SeccompAction::KillProcess => libc::SECCOMP_RET_KILL_PROCESS,
+        };
+        Ok(Self {
+            program: compile(
+                arch,
+                &numbers,
+                blocked.contains(RAW_SOCKETS),
+                blocked.contains(INET_SOCKETS),
+                deny,
+            ),
+        })
+    }
+
+    /// Install the filter on the calling process
+    ///
+    /// Sets `no_new_privs` first, which the kernel requires before an unprivileged
+    /// process may load a filter. The filter is inherited across `exec`.
+    pub fn install(&self) -> std::io::Result<()> {
+        let program = libc::sock_fprog {
+            len: self.program.len() as u16,
+            filter: self.program.as_ptr() as *mut libc::sock_filter,
+        };
+        // SAFETY: prctl and seccomp only read `program`, which outlives both calls
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if libc::syscall(
+                libc::SYS_seccomp,
+                libc::SECCOMP_SET_MODE_FILTER,
+                0,
+                &program as *const libc::sock_fprog,
+            ) != 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn stmt(code: u32, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+/// Build the BPF program
+///
+/// Every check is followed by its own return, so all jumps stay short no
+/// matter how many syscalls are blocked.
+fn compile(
+    arch: u32,
+    numbers: &[u32],
+    raw_sockets: bool,
+    inet_sockets: bool,
+    deny: u32,
+) -> Vec<libc::sock_filter> {
+    use libc::{BPF_ABS, BPF_ALU, BPF_AND, BPF_JEQ, BPF_JMP, BPF_K, BPF_LD, BPF_RET, BPF_W};
+
+    let load = |offset| stmt(BPF_LD | BPF_W | BPF_ABS, offset);
+    let ret = |action| stmt(BPF_RET | BPF_K, action);
+    // Return `action` if the accumulator equals `k`, otherwise fall through
+    let deny_if = |k| [jump(BPF_JMP | BPF_JEQ | BPF_K, k, 0, 1), ret(deny)];
+
+    let mut program = vec![
+        load(DATA_ARCH),
+        jump(BPF_JMP | BPF_JEQ | BPF_K, arch, 1, 0),
+        ret(libc::SECCOMP_RET_KILL_PROCESS),
+        load(DATA_NR),
+    ];
+    #[cfg(target_arch = "x86_64")]
+    program.extend([
+        jump(BPF_JMP | libc::BPF_JGE | BPF_K, X32_SYSCALL_BIT, 0, 1),
+        ret(deny),
+    ]);
+    for number in numbers {
+        program.extend(deny_if(*number));
+    }
+
+    if raw_sockets || inet_sockets {
+        let mut socket_checks = vec![load(DATA_ARGS)];
+        let mut domains = Vec::new();
+        if raw_sockets {
+            domains.push(libc::AF_PACKET as u32);
+        }
+        if inet_sockets {
+            domains.extend([libc::AF_INET as u32, libc::AF_INET6 as u32]);
+        }
+        for domain in domains {
+            socket_checks.extend(deny_if(domain));
+        }
+        if raw_sockets {
+            // The type argument may carry SOCK_NONBLOCK / SOCK_CLOEXEC in its high bits
+            socket_checks.extend([load(DATA_ARGS + 8), stmt(BPF_ALU | BPF_AND | BPF_K, 0xf)]);
+            socket_checks.extend(deny_if(libc::SOCK_RAW as u32));
+        }
+        socket_checks.push(ret(libc::SECCOMP_RET_ALLOW));
+
+        program.push(jump(
+            BPF_JMP | BPF_JEQ | BPF_K,
+            libc::SYS_socket as u32,
+            0,
+            socket_checks.len() as u8,
+        ));
+        program.extend(socket_checks);
+    }
+
+    program.push(ret(libc::SECCOMP_RET_ALLOW));
+    program
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_resolve_on_this_architecture() {
+        for preset in [
+            SeccompPreset::Default,
+            SeccompPreset::Strict,
+            SeccompPreset::NetworkOff,
+            SeccompPreset::Empty,
+        ] {
+            let profile = SeccompProfile::builder().preset(preset).build();
+            assert!(SeccompFilter::new(&profile).is_ok(), "{:?}", preset);
+        }
+    }
+
+    #[test]
+    fn test_allow_and_deny_lists() {
+        let base = SeccompFilter::new(&SeccompProfile::default()).unwrap();
+        let adjusted = SeccompFilter::new(
+            &SeccompProfile::builder()
+                .deny(vec!["memfd_create".to_string()])
+                .allow(vec!["ptrace".to_string(), "mount".to_string()])
+                .build(),
+        )
+        .unwrap();
+        // One syscall check (two instructions) added, two removed
+        assert_eq!(adjusted.program.len(), base.program.len() - 2);
+
+        let unknown = SeccompProfile::builder()
+            .deny(vec!["not_a_syscall".to_string()])
+            .build();
+        assert!(matches!(
+            SeccompFilter::new(&unknown),
+            Err(ClaudeError::InvalidConfig(_))
+        ));
+    }
+}
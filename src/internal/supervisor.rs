@@ -0,0 +1,152 @@
+//! Supervised mode: restart a CLI process that exits unexpectedly
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::errors::Result;
+use crate::types::config::{ClaudeAgentOptions, RestartPolicy};
+use crate::types::hooks::HookMatcher;
+use crate::types::messages::ReconnectEvent;
+
+use super::query_full::QueryFull;
+use super::transport::{QueryPrompt, SubprocessTransport, tap_transport};
+
+/// Spawn a supervisor for a connected query
+///
+/// `reader_done` is the shutdown signal from [`QueryFull::start`]. The returned
+/// receiver fires once the supervisor stops, i.e. when the CLI exits during
+/// disconnect or the restart policy is exhausted. In the latter case the message
+/// stream ends after [`ReconnectEvent::Failed`].
+pub(crate) fn spawn(
+    query: Arc<QueryFull>,
+    reader_done: oneshot::Receiver<()>,
+    options: ClaudeAgentOptions,
+    hooks: Option<HashMap<String, Vec<HookMatcher>>>,
+    policy: RestartPolicy,
+) -> oneshot::Receiver<()> {
+    let (done_tx, done_rx) = oneshot::channel();
+
+    tokio::spawn(async move {
+        supervise(query, reader_done, options, hooks, policy).await;
+        let _ = done_tx.send(());
+    });
+
+    done_rx
+}
+
+async fn supervise(
+    query: Arc<QueryFull>,
+    mut reader_done: oneshot::Receiver<()>,
+    options: ClaudeAgentOptions,
+    hooks: Option<HashMap<String, Vec<HookMatcher>>>,
+    policy: RestartPolicy,
+) {
+    let mut attempt = 0;
+    let mut started_at = Instant::now();
+
+    loop {
+        let _ = (&mut reader_done).await;
+        if query.is_closing() || query.has_overflowed() {
+            return;
+        }
+
+        if started_at.elapsed() >= policy.reset_after {
+            attempt = 0;
+        }
+        warn!("Claude CLI exited unexpectedly, restarting");
+
+        loop {
+            if attempt >= policy.max_restarts {
+                fail(&query, attempt, "restart attempts exhausted".to_string()).await;
+                return;
+            }
+            attempt += 1;
+
+            tokio::time::sleep(policy.backoff(attempt)).await;
+            if query.is_closing() {
+                return;
+            }
+
+            let session_id = query.last_session_id();
+            match restart(&query, &options, session_id.clone(), hooks.clone()).await {
+                // Disconnect may have started while the new process was coming up
+                Ok(_) if query.is_closing() => return,
+                Ok(done) => {
+                    info!("Claude CLI restarted (attempt {})", attempt);
+                    reader_done = done;
+                    started_at = Instant::now();
+                    emit(
+                        &query,
+                        ReconnectEvent::Reconnected {
+                            attempt,
+                            session_id,
+                        },
+                    )
+                    .await;
+                    break;
+                }
+                Err(e) if attempt >= policy.max_restarts => {
+                    fail(&query, attempt, e.to_string()).await;
+                    return;
+                }
+                Err(e) => warn!("Claude CLI restart attempt {} failed: {}", attempt, e),
+            }
+        }
+    }
+}
+
+/// Spawn a fresh CLI resuming `session_id` and swap it into the query
+async fn restart(
+    query: &QueryFull,
+    options: &ClaudeAgentOptions,
+    session_id: Option<String>,
+    hooks: Option<HashMap<String, Vec<HookMatcher>>>,
+) -> Result<oneshot::Receiver<()>> {
+    let mut options = options.clone();
+    if let Some(session_id) = session_id {
+        options.resume = Some(session_id);
+        options.continue_conversation = false;
+        options.fork_session = false;
+    }
+
+    // Reap the exited process before replacing it
+    let _ = query.transport().close().await;
+
+    let transport = tap_transport(
+        Arc::new(SubprocessTransport::new_async(QueryPrompt::Streaming, options.clone()).await?),
+        &options,
+    );
+    transport.connect().await?;
+
+    let started = async {
+        query.replace_transport(Arc::clone(&transport));
+        let reader_done = query.start().await?;
+        query.initialize(hooks).await?;
+        query.restore_mcp_servers().await?;
+        Ok(reader_done)
+    }
+    .await;
+
+    // Don't leave the new process running if it failed to come up, or if
+    // disconnect started in the meantime
+    if started.is_err() || query.is_closing() {
+        let _ = transport.close().await;
+    }
+
+    started
+}
+
+/// Report that the restart policy is exhausted and end the message stream
+async fn fail(query: &QueryFull, attempts: u32, error: String) {
+    emit(query, ReconnectEvent::Failed { attempts, error }).await;
+    query.close_messages();
+}
+
+async fn emit(query: &QueryFull, event: ReconnectEvent) {
+    let mut message = serde_json::to_value(&event).unwrap_or_default();
+    message["type"] = serde_json::json!("sdk_reconnect");
+    query.emit_message(message).await;
+}
@@ -0,0 +1,351 @@
+//! Newline-delimited reading with a per-line size limit
+
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Default per-line limit when `max_buffer_size` is not set
+pub(crate) const DEFAULT_MAX_BUFFER_SIZE: usize = 10 * 1024 * 1024; // 10MB
+
+/// Outcome of reading one line
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum LineRead {
+    /// End of input with nothing buffered
+    Eof,
+    /// A complete line (without the newline) is in the buffer
+    Line,
+    /// The line exceeds the limit; the buffer holds its first `limit` bytes and
+    /// the rest is still unread
+    Oversized,
+}
+
+/// Read one line into `buf`, buffering at most `limit` bytes
+pub(crate) async fn read_line_bounded<R>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    limit: usize,
+) -> std::io::Result<LineRead>
+where
+    R: AsyncBufRead + Unpin,
+{
+    buf.clear();
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(if buf.is_empty() {
+                LineRead::Eof
+            } else {
+                LineRead::Line
+            });
+        }
+
+        let newline = available.iter().position(|b| *b == b'\n');
+        let chunk_len = newline.unwrap_or(available.len());
+        if buf.len() + chunk_len > limit {
+            let take = limit - buf.len();
+            buf.extend_from_slice(&available[..take]);
+            reader.consume(take);
+            return Ok(LineRead::Oversized);
+        }
+
+        buf.extend_from_slice(&available[..chunk_len]);
+        match newline {
+            Some(_) => {
+                reader.consume(chunk_len + 1);
+                return Ok(LineRead::Line);
+            }
+            None => reader.consume(chunk_len),
+        }
+    }
+}
+
+/// Line from a [`BoundedLines`] reader
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum BoundedLine {
+    /// A complete line, without the newline
+    Line(String),
+    /// A line over the limit, skipped; holds its size in bytes
+    Oversized(u64),
+    /// End of input; a final line without a newline is dropped
+    Eof,
+}
+
+/// Newline-delimited reader with a per-line limit that is safe to cancel
+///
+/// Unlike [`read_line_bounded`], a partly read line is kept between calls, so
+/// [`next_line`](Self::next_line) can be a branch of `select!`. Only lines ending
+/// in a newline are returned: a peer that goes away mid-line hasn't sent it.
+pub(crate) struct BoundedLines<R> {
+    reader: R,
+    buf: Vec<u8>,
+    limit: usize,
+    /// Bytes seen so far of an oversized line being skipped
+    skipped: Option<u64>,
+}
+
+impl<R: AsyncBufRead + Unpin> BoundedLines<R> {
+    pub(crate) fn new(reader: R, limit: usize) -> Self {
+        Self {
+            reader,
+            buf: Vec::new(),
+            limit,
+            skipped: None,
+        }
+    }
+
+    /// Change the limit for lines not yet started
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    /// Read the next line
+    ///
+    /// Invalid UTF-8 is an [`InvalidData`](std::io::ErrorKind::InvalidData) error.
+    pub(crate) async fn next_line(&mut self) -> std::io::Result<BoundedLine> {
+        loop {
+            let available = self.reader.fill_buf().await?;
+            if available.is_empty() {
+                self.buf.clear();
+                self.skipped = None;
+                return Ok(BoundedLine::Eof);
+            }
+
+            let newline = available.iter().position(|b| *b == b'\n');
+            let chunk_len = newline.unwrap_or(available.len());
+            match &mut self.skipped {
+                Some(skipped) => *skipped += chunk_len as u64,
+                None if self.buf.len() + chunk_len > self.limit => {
+                    self.skipped = Some((self.buf.len() + chunk_len) as u64);
+                    self.buf.clear();
+                }
+                None => self.buf.extend_from_slice(&available[..chunk_len]),
+            }
+            self.reader
+                .consume(chunk_len + usize::from(newline.is_some()));
+
+            if newline.is_some() {
+                if let Some(size) = self.skipped.take() {
+                    return Ok(BoundedLine::Oversized(size));
+                }
+                return String::from_utf8(std::mem::take(&mut self.buf))
+                    .map(BoundedLine::Line)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e));
+            }
+        }
+    }
+}
+
+/// Consume the rest of the current line, copying it to `sink`
+///
+/// Returns the number of bytes consumed, excluding the newline.
+pub(crate) async fn drain_line<R, W>(reader: &mut R, sink: &mut W) -> std::io::Result<u64>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut total = 0u64;
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(total);
+        }
+
+        let newline = available.iter().position(|b| *b == b'\n');
+        let chunk_len = newline.unwrap_or(available.len());
+        sink.write_all(&available[..chunk_len]).await?;
+        total += chunk_len as u64;
+
+        match newline {
+            Some(_) => {
+                reader.consume(chunk_len + 1);
+                return Ok(total);
+            }
+            None => reader.consume(chunk_len),
+        }
+    }
+}
+
+/// Write an oversized line to a new file in `dir`
+///
+/// `prefix` is the part already buffered; the remainder is streamed from `reader`.
+/// Returns the file path and the line's total size in bytes.
+pub(crate) async fn spill_line<R>(
+    reader: &mut R,
+    prefix: &[u8],
+    dir: &Path,
+) -> std::io::Result<(PathBuf, u64)>
+where
+    R: AsyncBufRead + Unpin,
+{
+    tokio::fs::create_dir_all(dir).await?;
+    let path = dir.join(format!("claude-message-{}.json", uuid::Uuid::new_v4()));
+    // Spilled messages hold conversation content, so only the owner may read them
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&path).await?;
+    file.write_all(prefix).await?;
+    let rest = drain_line(reader, &mut file).await?;
+    file.flush().await?;
+    Ok((path, prefix.len() as u64 + rest))
+}
+
+/// Top-level `"type"` of a JSON line, read from its first bytes
+///
+/// The CLI writes `type` as the first key, so a truncated prefix is enough.
+pub(crate) fn peek_message_type(prefix: &[u8]) -> Option<String> {
+    peek_string_field(prefix, "type")
+}
+
+/// Top-level `"request_id"` of a control message, read from its first bytes
+///
+/// The CLI writes `request_id` right after `type`, ahead of the request body.
+pub(crate) fn peek_request_id(prefix: &[u8]) -> Option<String> {
+    peek_string_field(prefix, "request_id")
+}
+
+/// First string value of `field` in a possibly truncated JSON prefix
+fn peek_string_field(prefix: &[u8], field: &str) -> Option<String> {
+    let text = String::from_utf8_lossy(prefix);
+    let key = format!("\"{}\"", field);
+    let start = text.find(&key)? + key.len();
+    let rest = text[start..].trim_start().strip_prefix(':')?.trim_start();
+    let rest = rest.strip_prefix('"')?;
+    rest.find('"').map(|end| rest[..end].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::BufReader;
+
+    #[tokio::test]
+    async fn test_read_line_bounded_splits_lines() {
+        let mut reader = BufReader::with_capacity(4, &b"{\"a\":1}\n\n{\"b\":2}"[..]);
+        let mut buf = Vec::new();
+
+        assert_eq!(
+            read_line_bounded(&mut reader, &mut buf, 64).await.unwrap(),
+            LineRead::Line
+        );
+        assert_eq!(buf, b"{\"a\":1}");
+        assert_eq!(
+            read_line_bounded(&mut reader, &mut buf, 64).await.unwrap(),
+            LineRead::Line
+        );
+        assert!(buf.is_empty());
+        assert_eq!(
+            read_line_bounded(&mut reader, &mut buf, 64).await.unwrap(),
+            LineRead::Line
+        );
+        assert_eq!(buf, b"{\"b\":2}");
+        assert_eq!(
+            read_line_bounded(&mut reader, &mut buf, 64).await.unwrap(),
+            LineRead::Eof
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oversized_line_is_not_fully_buffered() {
+        let input = format!("{}\nnext\n", "x".repeat(100));
+        let mut reader = BufReader::with_capacity(8, input.as_bytes());
+        let mut buf = Vec::new();
+
+        assert_eq!(
+            read_line_bounded(&mut reader, &mut buf, 10).await.unwrap(),
+            LineRead::Oversized
+        );
+        assert_eq!(buf.len(), 10);
+        let rest = drain_line(&mut reader, &mut tokio::io::sink())
+            .await
+            .unwrap();
+        assert_eq!(rest, 90);
+
+        // The limit applies per line, not cumulatively
+        assert_eq!(
+            read_line_bounded(&mut reader, &mut buf, 10).await.unwrap(),
+            LineRead::Line
+        );
+        assert_eq!(buf, b"next");
+    }
+
+    #[tokio::test]
+    async fn test_bounded_lines_skip_oversized_and_drop_partial_tail() {
+        let input = format!("ok\n{}\nnext\npartial", "x".repeat(100));
+        let mut lines = BoundedLines::new(BufReader::with_capacity(8, input.as_bytes()), 10);
+
+        assert_eq!(
+            lines.next_line().await.unwrap(),
+            BoundedLine::Line("ok".to_string())
+        );
+        assert_eq!(
+            lines.next_line().await.unwrap(),
+            BoundedLine::Oversized(100)
+        );
+        assert_eq!(
+            lines.next_line().await.unwrap(),
+            BoundedLine::Line("next".to_string())
+        );
+        assert_eq!(lines.next_line().await.unwrap(), BoundedLine::Eof);
+    }
+
+    #[tokio::test]
+    async fn test_bounded_lines_keep_partial_line_when_cancelled() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut lines = BoundedLines::new(BufReader::new(server), 64);
+
+        client.write_all(b"{\"a\":").await.unwrap();
+        let cancelled =
+            tokio::time::timeout(std::time::Duration::from_millis(20), lines.next_line()).await;
+        assert!(cancelled.is_err());
+
+        client.write_all(b"1}\n").await.unwrap();
+        assert_eq!(
+            lines.next_line().await.unwrap(),
+            BoundedLine::Line("{\"a\":1}".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_spill_line_writes_whole_line() {
+        let input = b"{\"type\":\"user\",\"data\":\"abcdef\"}\nnext\n";
+        let mut reader = BufReader::with_capacity(4, &input[..]);
+        let mut buf = Vec::new();
+        assert_eq!(
+            read_line_bounded(&mut reader, &mut buf, 16).await.unwrap(),
+            LineRead::Oversized
+        );
+
+        let dir = std::env::temp_dir().join(format!("claude-spill-{}", uuid::Uuid::new_v4()));
+        let (path, size) = spill_line(&mut reader, &buf, &dir).await.unwrap();
+        assert_eq!(size, 31);
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            b"{\"type\":\"user\",\"data\":\"abcdef\"}"
+        );
+        assert_eq!(peek_message_type(&buf), Some("user".to_string()));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        read_line_bounded(&mut reader, &mut buf, 16).await.unwrap();
+        assert_eq!(buf, b"next");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_peek_message_type() {
+        assert_eq!(
+            peek_message_type(br#"{ "type" : "control_request", "req"#),
+            Some("control_request".to_string())
+        );
+        assert_eq!(peek_message_type(br#"{"typ"#), None);
+        assert_eq!(
+            peek_request_id(br#"{"type":"control_request","request_id":"req_7","request":{"#),
+            Some("req_7".to_string())
+        );
+    }
+}
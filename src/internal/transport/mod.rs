@@ -1,7 +1,12 @@
 //! Transport layer for communicating with Claude Code CLI
 
+pub(crate) mod line_reader;
+pub mod socket;
 pub mod subprocess;
+mod tap;
 mod trait_def;
 
-pub use subprocess::SubprocessTransport;
-pub use trait_def::Transport;
+pub use socket::{ReconnectPolicy, RelayEndpoint, SocketTransport, SocketTransportConfig};
+pub use subprocess::{QueryPrompt, SubprocessTransport};
+pub(crate) use tap::tap_transport;
+pub use trait_def::{ShutdownStage, Transport};
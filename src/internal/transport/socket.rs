@@ -0,0 +1,547 @@
+//! Socket transport for reaching a Claude Code CLI behind a relay
+//!
+//! The relay (see [`crate::relay`]) spawns the CLI and bridges its stdio to a
+//! Unix domain socket or TCP listener. Both sides speak the same newline-delimited
+//! stream-json protocol as the CLI, plus a few `relay_*` frames for the handshake
+//! and lifecycle events.
+
+use async_trait::async_trait;
+use futures::stream::Stream;
+use std::fmt;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::Mutex;
+use tracing::warn;
+use typed_builder::TypedBuilder;
+
+use crate::errors::{ClaudeError, ConnectionError, JsonDecodeError, Result};
+
+use super::Transport;
+use super::line_reader::{BoundedLine, BoundedLines, DEFAULT_MAX_BUFFER_SIZE};
+
+/// Relay protocol version spoken by this SDK
+pub(crate) const RELAY_PROTOCOL_VERSION: u64 = 1;
+
+/// Longest handshake line either side accepts, before the peer is trusted
+pub(crate) const MAX_HANDSHAKE_LINE: usize = 64 * 1024;
+
+/// Relay frame types, distinguished from CLI messages by their `relay_` prefix
+pub(crate) mod frame {
+    /// Client greeting carrying the protocol version and auth token
+    pub const HELLO: &str = "relay_hello";
+    /// Relay acceptance of a client greeting, with the CLI's version if known
+    pub const WELCOME: &str = "relay_welcome";
+    /// Fatal relay error; the relay closes the connection after sending it
+    pub const ERROR: &str = "relay_error";
+    /// Client request to close the CLI's stdin
+    pub const END_INPUT: &str = "relay_end_input";
+    /// Client request to shut the CLI down
+    pub const CLOSE: &str = "relay_close";
+    /// Relay notice that the CLI output has ended
+    pub const EXIT: &str = "relay_exit";
+}
+
+/// Read half of a relay connection
+pub(crate) type RelayReader = BoundedLines<BufReader<Pin<Box<dyn AsyncRead + Send>>>>;
+/// Write half of a relay connection
+pub(crate) type RelayWriter = Pin<Box<dyn AsyncWrite + Send>>;
+
+/// Address of a relay endpoint
+///
+/// Parsed from `tcp://HOST:PORT` or `unix:///path/to/socket`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelayEndpoint {
+    /// TCP address (e.g. `127.0.0.1:7878`)
+    Tcp(String),
+    /// Unix domain socket path
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl RelayEndpoint {
+    /// Open a connection to the endpoint
+    pub(crate) async fn open(&self) -> std::io::Result<(RelayReader, RelayWriter)> {
+        match self {
+            RelayEndpoint::Tcp(addr) => {
+                let stream = tokio::net::TcpStream::connect(addr).await?;
+                stream.set_nodelay(true)?;
+                let (reader, writer) = stream.into_split();
+                Ok(split_boxed(reader, writer))
+            }
+            #[cfg(unix)]
+            RelayEndpoint::Unix(path) => {
+                let stream = tokio::net::UnixStream::connect(path).await?;
+                let (reader, writer) = stream.into_split();
+                Ok(split_boxed(reader, writer))
+            }
+        }
+    }
+}
+
+impl FromStr for RelayEndpoint {
+    type Err = ClaudeError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(addr) = s.strip_prefix("tcp://") {
+            return Ok(RelayEndpoint::Tcp(addr.to_string()));
+        }
+        #[cfg(unix)]
+        if let Some(path) = s.strip_prefix("unix://") {
+            return Ok(RelayEndpoint::Unix(PathBuf::from(path)));
+        }
+        Err(ClaudeError::InvalidConfig(format!(
+            "Invalid relay endpoint '{}'. Expected tcp://HOST:PORT or unix:///path",
+            s
+        )))
+    }
+}
+
+impl fmt::Display for RelayEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelayEndpoint::Tcp(addr) => write!(f, "tcp://{}", addr),
+            #[cfg(unix)]
+            RelayEndpoint::Unix(path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
+
+/// Box both halves of a split stream and wrap the read half in a line reader
+///
+/// The reader starts out limited to [`MAX_HANDSHAKE_LINE`]; raise the limit once
+/// the handshake is done.
+pub(crate) fn split_boxed(
+    reader: impl AsyncRead + Send + 'static,
+    writer: impl AsyncWrite + Send + 'static,
+) -> (RelayReader, RelayWriter) {
+    let reader: Pin<Box<dyn AsyncRead + Send>> = Box::pin(reader);
+    (
+        BoundedLines::new(BufReader::new(reader), MAX_HANDSHAKE_LINE),
+        Box::pin(writer),
+    )
+}
+
+/// Write a single newline-terminated line and flush it
+pub(crate) async fn write_line(writer: &mut RelayWriter, data: &str) -> std::io::Result<()> {
+    writer.write_all(data.as_bytes()).await?;
+    writer.write_all(b"\n").await?;
+    writer.flush().await
+}
+
+/// Reconnect behavior when the connection to the relay drops
+#[derive(Debug, Clone, TypedBuilder)]
+#[builder(doc)]
+pub struct ReconnectPolicy {
+    /// Maximum reconnect attempts before giving up (0 disables reconnecting)
+    #[builder(default = 5)]
+    pub max_attempts: u32,
+    /// Delay before the first attempt, doubled after each failure
+    #[builder(default = Duration::from_millis(200))]
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between attempts
+    #[builder(default = Duration::from_secs(5))]
+    pub max_backoff: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl ReconnectPolicy {
+    /// A policy that never reconnects
+    pub fn disabled() -> Self {
+        Self::builder().max_attempts(0).build()
+    }
+
+    /// Delay before the given (zero-based) attempt
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Configuration for [`SocketTransport`]
+#[derive(Debug, Clone, TypedBuilder)]
+#[builder(doc)]
+pub struct SocketTransportConfig {
+    /// Shared secret the relay expects in the handshake
+    #[builder(default, setter(into, strip_option))]
+    pub auth_token: Option<String>,
+    /// Reconnect behavior when the connection drops
+    #[builder(default)]
+    pub reconnect: ReconnectPolicy,
+    /// Timeout for opening a connection and completing the handshake
+    #[builder(default = Duration::from_secs(10))]
+    pub connect_timeout: Duration,
+    /// Largest message accepted from the relay, in bytes; longer ones are skipped
+    /// with an error
+    #[builder(default = DEFAULT_MAX_BUFFER_SIZE)]
+    pub max_buffer_size: usize,
+}
+
+impl Default for SocketTransportConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// Transport that talks to a Claude Code CLI through a relay over TCP or a Unix socket
+///
+/// The relay keeps the CLI running while the connection is re-established, so a
+/// dropped connection is retried according to [`ReconnectPolicy`] without losing
+/// CLI output. Control requests (hooks, `can_use_tool`, SDK MCP messages) pass
+/// through the relay unchanged and are handled by the client as usual. The relay
+/// reports the CLI's version, so options the CLI is too old for fail in `connect()`.
+///
+/// # Example
+///
+/// ```no_run
+/// use claude_agent_sdk_rs::{ClaudeAgentOptions, ClaudeClient, SocketTransport, SocketTransportConfig};
+/// use std::sync::Arc;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = SocketTransportConfig::builder().auth_token("secret").build();
+/// let transport = SocketTransport::new("tcp://10.0.0.5:7878".parse()?, config);
+///
+/// let mut client = ClaudeClient::with_transport(Arc::new(transport), ClaudeAgentOptions::default());
+/// client.connect().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SocketTransport {
+    endpoint: RelayEndpoint,
+    config: SocketTransportConfig,
+    /// Write half of the current connection
+    writer: Mutex<Option<RelayWriter>>,
+    /// Read half of the latest connection, picked up by the read stream
+    next_reader: Mutex<Option<RelayReader>>,
+    /// Incremented on every successful reconnect
+    generation: AtomicU64,
+    /// Serializes reconnect attempts between the read and write paths
+    reconnect_lock: Mutex<()>,
+    ready: AtomicBool,
+    closed: AtomicBool,
+    /// Set once the relay reports that the CLI session is over
+    ended: AtomicBool,
+    /// CLI version reported in the relay's welcome
+    cli_version: std::sync::Mutex<Option<String>>,
+}
+
+impl SocketTransport {
+    /// Create a transport for the given relay endpoint
+    pub fn new(endpoint: RelayEndpoint, config: SocketTransportConfig) -> Self {
+        Self {
+            endpoint,
+            config,
+            writer: Mutex::new(None),
+            next_reader: Mutex::new(None),
+            generation: AtomicU64::new(0),
+            reconnect_lock: Mutex::new(()),
+            ready: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            ended: AtomicBool::new(false),
+            cli_version: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Open a connection and complete the relay handshake
+    async fn open(&self) -> Result<(RelayReader, RelayWriter)> {
+        tokio::time::timeout(self.config.connect_timeout, self.handshake())
+            .await
+            .map_err(|_| {
+                ClaudeError::Connection(ConnectionError::new(format!(
+                    "Timed out connecting to relay at {}",
+                    self.endpoint
+                )))
+            })?
+    }
+
+    async fn handshake(&self) -> Result<(RelayReader, RelayWriter)> {
+        let (mut reader, mut writer) = self.endpoint.open().await.map_err(|e| {
+            ClaudeError::Connection(ConnectionError::new(format!(
+                "Failed to connect to relay at {}: {}",
+                self.endpoint, e
+            )))
+        })?;
+
+        let hello = serde_json::json!({
+            "type": frame::HELLO,
+            "version": RELAY_PROTOCOL_VERSION,
+            "token": self.config.auth_token,
+        });
+        write_line(&mut writer, &hello.to_string())
+            .await
+            .map_err(|e| {
+                ClaudeError::Transport(format!("Failed to send relay handshake: {}", e))
+            })?;
+
+        let line =
+            match reader.next_line().await.map_err(|e| {
+                ClaudeError::Transport(format!("Failed to read relay handshake: {}", e))
+            })? {
+                BoundedLine::Line(line) => line,
+                BoundedLine::Oversized(size) => {
+                    return Err(ClaudeError::Connection(ConnectionError::new(format!(
+                        "Relay handshake reply of {} bytes is too large",
+                        size
+                    ))));
+                }
+                BoundedLine::Eof => {
+                    return Err(ClaudeError::Connection(ConnectionError::new(
+                        "Relay closed the connection during handshake",
+                    )));
+                }
+            };
+        let reply: serde_json::Value = serde_json::from_str(&line).map_err(|e| {
+            ClaudeError::JsonDecode(JsonDecodeError::new(
+                format!("Invalid relay handshake reply: {}", e),
+                line.clone(),
+            ))
+        })?;
+
+        match reply.get("type").and_then(|v| v.as_str()) {
+            Some(frame::WELCOME) => {
+                *self.cli_version.lock().unwrap() = reply
+                    .get("cli_version")
+                    .and_then(|v| v.as_str())
+                    .map(String::from);
+                reader.set_limit(self.config.max_buffer_size);
+                Ok((reader, writer))
+            }
+            Some(frame::ERROR) => Err(ClaudeError::Connection(ConnectionError::new(format!(
+                "Relay rejected connection: {}",
+                reply["message"].as_str().unwrap_or("unknown error")
+            )))),
+            _ => Err(ClaudeError::Connection(ConnectionError::new(format!(
+                "Unexpected relay handshake reply: {}",
+                line
+            )))),
+        }
+    }
+
+    /// Re-establish the connection after a failure observed on `failed_generation`
+    ///
+    /// Returns immediately if another caller already reconnected.
+    async fn reconnect(&self, failed_generation: u64) -> Result<()> {
+        let _guard = self.reconnect_lock.lock().await;
+        if self.generation.load(Ordering::SeqCst) != failed_generation {
+            return Ok(());
+        }
+
+        self.writer.lock().await.take();
+        let policy = &self.config.reconnect;
+        for attempt in 0..policy.max_attempts {
+            if !self.should_reconnect() {
+                break;
+            }
+            tokio::time::sleep(policy.backoff(attempt)).await;
+
+            match self.open().await {
+                Ok((reader, writer)) => {
+                    *self.writer.lock().await = Some(writer);
+                    *self.next_reader.lock().await = Some(reader);
+                    self.generation.fetch_add(1, Ordering::SeqCst);
+                    return Ok(());
+                }
+                Err(e) => warn!(
+                    "Relay reconnect attempt {}/{} to {} failed: {}",
+                    attempt + 1,
+                    policy.max_attempts,
+                    self.endpoint,
+                    e
+                ),
+            }
+        }
+
+        self.ready.store(false, Ordering::SeqCst);
+        Err(ClaudeError::Connection(ConnectionError::new(format!(
+            "Lost connection to relay at {} after {} reconnect attempts",
+            self.endpoint, policy.max_attempts
+        ))))
+    }
+
+    async fn write_raw(&self, data: &str) -> Result<()> {
+        let mut writer_guard = self.writer.lock().await;
+        if let Some(ref mut writer) = *writer_guard {
+            write_line(writer, data)
+                .await
+                .map_err(|e| ClaudeError::Transport(format!("Failed to write to relay: {}", e)))
+        } else {
+            Err(ClaudeError::Transport(
+                "relay connection not available".to_string(),
+            ))
+        }
+    }
+
+    /// Whether a failed write or read should be retried on a new connection
+    fn should_reconnect(&self) -> bool {
+        !self.closed.load(Ordering::SeqCst) && !self.ended.load(Ordering::SeqCst)
+    }
+
+    async fn write_frame(&self, frame_type: &str) -> Result<()> {
+        self.write(&serde_json::json!({ "type": frame_type }).to_string())
+            .await
+    }
+}
+
+#[async_trait]
+impl Transport for SocketTransport {
+    async fn connect(&self) -> Result<()> {
+        let (reader, writer) = self.open().await?;
+        *self.writer.lock().await = Some(writer);
+        *self.next_reader.lock().await = Some(reader);
+        self.closed.store(false, Ordering::SeqCst);
+        self.ended.store(false, Ordering::SeqCst);
+        self.ready.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn write(&self, data: &str) -> Result<()> {
+        let generation = self.generation.load(Ordering::SeqCst);
+        match self.write_raw(data).await {
+            Ok(()) => Ok(()),
+            Err(e) if !self.should_reconnect() => Err(e),
+            Err(_) => {
+                self.reconnect(generation).await?;
+                self.write_raw(data).await
+            }
+        }
+    }
+
+    fn read_messages(&self) -> Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send + '_>> {
+        Box::pin(async_stream::stream! {
+            let mut reader = self.next_reader.lock().await.take();
+            let mut generation = self.generation.load(Ordering::SeqCst);
+
+            while let Some(ref mut lines) = reader {
+                let line = match lines.next_line().await {
+                    Ok(BoundedLine::Line(line)) => line,
+                    Ok(BoundedLine::Oversized(size)) => {
+                        yield Err(ClaudeError::Transport(format!(
+                            "Message of {} bytes exceeds maximum of {} bytes",
+                            size, self.config.max_buffer_size
+                        )));
+                        continue;
+                    }
+                    Ok(BoundedLine::Eof) | Err(_) => {
+                        if !self.should_reconnect() {
+                            break;
+                        }
+                        if let Err(e) = self.reconnect(generation).await {
+                            yield Err(e);
+                            break;
+                        }
+                        reader = self.next_reader.lock().await.take();
+                        generation = self.generation.load(Ordering::SeqCst);
+                        continue;
+                    }
+                };
+
+                let trimmed = line.trim();
+                if trimmed.is_empty() {
+                    continue;
+                }
+
+                match serde_json::from_str::<serde_json::Value>(trimmed) {
+                    Ok(json) => match json.get("type").and_then(|v| v.as_str()) {
+                        Some(frame::EXIT) => {
+                            self.ended.store(true, Ordering::SeqCst);
+                            break;
+                        }
+                        Some(frame::ERROR) => {
+                            self.ended.store(true, Ordering::SeqCst);
+                            yield Err(ClaudeError::Transport(format!(
+                                "Relay error: {}",
+                                json["message"].as_str().unwrap_or("unknown error")
+                            )));
+                            break;
+                        }
+                        _ => yield Ok(json),
+                    },
+                    Err(e) => {
+                        yield Err(ClaudeError::JsonDecode(JsonDecodeError::new(
+                            format!("Failed to parse JSON: {}", e),
+                            trimmed.to_string(),
+                        )));
+                    }
+                }
+            }
+        })
+    }
+
+    async fn close(&self) -> Result<()> {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let _ = self
+            .write_raw(&serde_json::json!({ "type": frame::CLOSE }).to_string())
+            .await;
+        if let Some(mut writer) = self.writer.lock().await.take() {
+            let _ = writer.shutdown().await;
+        }
+
+        self.ready.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+
+    fn cli_version(&self) -> Option<String> {
+        self.cli_version.lock().unwrap().clone()
+    }
+
+    async fn end_input(&self) -> Result<()> {
+        if self.ended.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.write_frame(frame::END_INPUT).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relay_endpoint_parse() {
+        assert_eq!(
+            "tcp://127.0.0.1:7878".parse::<RelayEndpoint>().unwrap(),
+            RelayEndpoint::Tcp("127.0.0.1:7878".to_string())
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            "unix:///tmp/claude.sock".parse::<RelayEndpoint>().unwrap(),
+            RelayEndpoint::Unix(PathBuf::from("/tmp/claude.sock"))
+        );
+        assert!("127.0.0.1:7878".parse::<RelayEndpoint>().is_err());
+    }
+
+    #[test]
+    fn test_relay_endpoint_display_roundtrip() {
+        let endpoint: RelayEndpoint = "tcp://localhost:9000".parse().unwrap();
+        assert_eq!(endpoint.to_string(), "tcp://localhost:9000");
+    }
+
+    #[test]
+    fn test_reconnect_backoff_is_capped() {
+        let policy = ReconnectPolicy::builder()
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(500))
+            .build();
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(10), Duration::from_millis(500));
+    }
+}
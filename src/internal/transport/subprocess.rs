@@ -2,16 +2,17 @@
 
 use async_trait::async_trait;
 use futures::stream::Stream;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::process::Stdio;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, ChildStdin, ChildStdout, Command};
 use tokio::sync::Mutex;
-use tracing::warn;
+use tracing::{debug, warn};
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
@@ -22,14 +23,18 @@ const CREATE_NO_WINDOW: u32 = 0x08000000;
 
 /// Create a std::process::Command that won't show a console window on Windows
 #[cfg(target_os = "windows")]
-fn create_sync_hidden_command<S: AsRef<std::ffi::OsStr>>(program: S) -> std::process::Command {
+pub(crate) fn create_sync_hidden_command<S: AsRef<std::ffi::OsStr>>(
+    program: S,
+) -> std::process::Command {
     let mut cmd = std::process::Command::new(program);
     cmd.creation_flags(CREATE_NO_WINDOW);
     cmd
 }
 
 #[cfg(not(target_os = "windows"))]
-fn create_sync_hidden_command<S: AsRef<std::ffi::OsStr>>(program: S) -> std::process::Command {
+pub(crate) fn create_sync_hidden_command<S: AsRef<std::ffi::OsStr>>(
+    program: S,
+) -> std::process::Command {
     std::process::Command::new(program)
 }
 
@@ -46,18 +51,28 @@ fn create_async_hidden_command<S: AsRef<std::ffi::OsStr>>(program: S) -> Command
     Command::new(program)
 }
 
-use crate::errors::{
-    ClaudeError, CliNotFoundError, ConnectionError, JsonDecodeError, ProcessError, Result,
-};
-use crate::types::config::ClaudeAgentOptions;
-use crate::types::messages::UserContentBlock;
+use crate::errors::{ClaudeError, ConnectionError, JsonDecodeError, ProcessError, Result};
+use crate::locator::{CliDiscovery, ExplicitPath};
+use crate::types::capabilities::Capabilities;
+use crate::types::config::{ClaudeAgentOptions, ResourceLimitKind};
+use crate::types::env::Redactor;
+use crate::types::messages::{ProcessExited, UserContentBlock};
+use crate::types::sandbox::SandboxStatus;
 use crate::version::{
     ENTRYPOINT, MIN_CLI_VERSION, SDK_VERSION, SKIP_VERSION_CHECK_ENV, check_version,
+    parse_version_output,
 };
 
-use super::Transport;
+use super::line_reader::{
+    DEFAULT_MAX_BUFFER_SIZE, LineRead, drain_line, peek_message_type, peek_request_id,
+    read_line_bounded, spill_line,
+};
+use super::{ShutdownStage, Transport};
 
-const DEFAULT_MAX_BUFFER_SIZE: usize = 10 * 1024 * 1024; // 10MB
+/// Number of stderr lines kept for exit reports
+const STDERR_TAIL_LINES: usize = 50;
+/// How long to wait for an exit status (and trailing stderr) once stdout closes
+const EXIT_STATUS_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Query prompt type
 #[derive(Clone)]
@@ -88,6 +103,27 @@ impl From<Vec<UserContentBlock>> for QueryPrompt {
     }
 }
 
+impl QueryPrompt {
+    /// Format the prompt as a stream-json user message
+    ///
+    /// Returns `None` for [`QueryPrompt::Streaming`], which has no initial prompt.
+    pub fn to_user_message(&self) -> Option<serde_json::Value> {
+        let content = match self {
+            QueryPrompt::Text(text) => serde_json::json!(text),
+            QueryPrompt::Content(blocks) => serde_json::json!(blocks),
+            QueryPrompt::Streaming => return None,
+        };
+
+        Some(serde_json::json!({
+            "type": "user",
+            "message": {
+                "role": "user",
+                "content": content
+            }
+        }))
+    }
+}
+
 /// Subprocess transport for communicating with Claude Code CLI
 ///
 /// All internal state that requires mutation is wrapped in synchronization primitives,
@@ -106,10 +142,40 @@ pub struct SubprocessTransport {
     max_buffer_size: usize,
     /// Ready state - uses AtomicBool for lock-free access
     ready: AtomicBool,
+    /// Set once shutdown starts, so the CLI exiting isn't reported as unexpected
+    closing: AtomicBool,
+    /// PID of the CLI, which is also its process group ID on Unix (0 before connect)
+    ///
+    /// Kept separately because `Child::id` is gone once the exit status is collected,
+    /// while the group may still have members to clean up.
+    pid: AtomicU32,
+    /// Most recent stderr lines, kept whether or not a callback is set
+    stderr_tail: Arc<std::sync::Mutex<VecDeque<String>>>,
+    /// Task draining stderr into `stderr_tail`
+    stderr_task: std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
+    /// Sandboxing in effect for the current process, as reported by the child
+    sandbox_status: std::sync::Mutex<SandboxStatus>,
+    /// Proxy serving a network-isolated CLI
+    proxy_task: std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
+    /// Masks the values of the env policy's `redact` variables in CLI output
+    redactor: Redactor,
+    /// CLI version found by the version check, if it ran
+    cli_version: std::sync::Mutex<Option<String>>,
+}
+
+/// Parent ends of the channels the child reports its isolation setup on
+#[cfg(target_os = "linux")]
+struct IsolationReceivers {
+    landlock: Option<crate::internal::sandbox::StatusReceiver>,
+    network: Option<crate::internal::netns::IsolationReceiver>,
 }
 
 impl SubprocessTransport {
     /// Create a new subprocess transport
+    ///
+    /// CLI discovery may run `npm config get prefix` and, with a version
+    /// requirement, each candidate's `--version`, blocking the calling thread.
+    /// From async code use [`new_async`](Self::new_async).
     pub fn new(prompt: QueryPrompt, options: ClaudeAgentOptions) -> Result<Self> {
         // Validate cwd early, before CLI lookup, for better error messages
         if let Some(ref cwd) = options.cwd {
@@ -127,14 +193,14 @@ impl SubprocessTransport {
             }
         }
 
-        let cli_path = if let Some(ref path) = options.cli_path {
-            path.clone()
-        } else {
-            Self::find_cli()?
-        };
-
         let cwd = options.cwd.clone().or_else(|| std::env::current_dir().ok());
+        let cli_path = Self::resolve_cli_path(&options, cwd.as_deref())?;
         let max_buffer_size = options.max_buffer_size.unwrap_or(DEFAULT_MAX_BUFFER_SIZE);
+        let redactor = options
+            .env_policy
+            .as_ref()
+            .map(|policy| policy.redactor(&options.env))
+            .unwrap_or_default();
 
         Ok(Self {
             cli_path,
@@ -146,112 +212,46 @@ impl SubprocessTransport {
             stdout: Arc::new(Mutex::new(None)),
             max_buffer_size,
             ready: AtomicBool::new(false),
+            closing: AtomicBool::new(false),
+            pid: AtomicU32::new(0),
+            stderr_tail: Arc::new(std::sync::Mutex::new(VecDeque::new())),
+            stderr_task: std::sync::Mutex::new(None),
+            sandbox_status: std::sync::Mutex::new(SandboxStatus::default()),
+            proxy_task: std::sync::Mutex::new(None),
+            redactor,
+            cli_version: std::sync::Mutex::new(None),
         })
     }
 
-    /// Find the Claude CLI executable
-    fn find_cli() -> Result<PathBuf> {
-        // Strategy 1: Try executing 'claude' directly from PATH
-        // This is the most reliable method as it respects the shell's PATH resolution
-        if let Ok(output) = create_sync_hidden_command("claude")
-            .arg("--version")
-            .output()
-            && output.status.success()
-        {
-            // 'claude' is in PATH and executable, return it as-is
-            // The OS will resolve it when we spawn the process
-            return Ok(PathBuf::from("claude"));
-        }
-
-        // Strategy 2: Use 'which' command to locate claude in PATH (Unix-like systems)
-        #[cfg(not(target_os = "windows"))]
-        if let Ok(output) = std::process::Command::new("which").arg("claude").output()
-            && output.status.success()
-        {
-            let path_str = String::from_utf8_lossy(&output.stdout);
-            let path = PathBuf::from(path_str.trim());
-            // Verify the path exists and is executable
-            if path.exists() && path.is_file() {
-                return Ok(path);
-            }
-        }
+    /// Create a new subprocess transport, running CLI discovery on the blocking thread pool
+    pub async fn new_async(prompt: QueryPrompt, options: ClaudeAgentOptions) -> Result<Self> {
+        tokio::task::spawn_blocking(move || Self::new(prompt, options))
+            .await
+            .map_err(|e| {
+                ClaudeError::Connection(ConnectionError::new(format!(
+                    "CLI discovery task failed: {}",
+                    e
+                )))
+            })?
+    }
 
-        // Strategy 3: Use 'where' command on Windows
-        #[cfg(target_os = "windows")]
-        if let Ok(output) = create_sync_hidden_command("where").arg("claude").output() {
-            if output.status.success() {
-                let path_str = String::from_utf8_lossy(&output.stdout);
-                // 'where' returns all matches, take the first one
-                if let Some(first_line) = path_str.lines().next() {
-                    let path = PathBuf::from(first_line.trim());
-                    if path.exists() && path.is_file() {
-                        return Ok(path);
-                    }
+    /// Pick the CLI executable from `cli_path` or `cli_discovery`
+    ///
+    /// An explicit `cli_path` is used as-is unless the discovery settings carry a
+    /// version requirement, in which case it must satisfy it.
+    fn resolve_cli_path(options: &ClaudeAgentOptions, cwd: Option<&Path>) -> Result<PathBuf> {
+        let discovery = options.cli_discovery.clone().unwrap_or_default();
+        match &options.cli_path {
+            Some(path) => match discovery.version_requirement {
+                Some(_) => CliDiscovery {
+                    locators: vec![Arc::new(ExplicitPath(path.clone()))],
+                    ..discovery
                 }
-            }
-        }
-
-        // Strategy 4: Check common installation locations
-        // Get home directory for path expansion
-        let home_dir = std::env::var("HOME")
-            .or_else(|_| std::env::var("USERPROFILE")) // Windows fallback
-            .ok()
-            .map(PathBuf::from);
-
-        // Common installation locations
-        let mut common_paths: Vec<PathBuf> = vec![];
-
-        // Unix-like paths
-        #[cfg(not(target_os = "windows"))]
-        {
-            common_paths.extend(vec![
-                PathBuf::from("/usr/local/bin/claude"),
-                PathBuf::from("/opt/homebrew/bin/claude"),
-                PathBuf::from("/usr/bin/claude"),
-            ]);
-
-            // Add home-relative paths if home directory is available
-            if let Some(ref home) = home_dir {
-                common_paths.push(home.join(".local/bin/claude"));
-                common_paths.push(home.join("bin/claude"));
-            }
-        }
-
-        // Windows paths
-        #[cfg(target_os = "windows")]
-        {
-            if let Some(ref home) = home_dir {
-                common_paths.extend(vec![
-                    home.join("AppData\\Local\\Programs\\Claude\\claude.exe"),
-                    home.join("AppData\\Roaming\\npm\\claude.cmd"),
-                    home.join("AppData\\Roaming\\npm\\claude.exe"),
-                ]);
-            }
-            common_paths.extend(vec![
-                PathBuf::from("C:\\Program Files\\Claude\\claude.exe"),
-                PathBuf::from("C:\\Program Files (x86)\\Claude\\claude.exe"),
-            ]);
-        }
-
-        // Check each common path
-        for path in common_paths {
-            if path.exists() && path.is_file() {
-                return Ok(path);
-            }
-        }
-
-        // Strategy 5: Check if CLAUDE_CLI_PATH environment variable is set
-        if let Ok(cli_path) = std::env::var("CLAUDE_CLI_PATH") {
-            let path = PathBuf::from(cli_path);
-            if path.exists() && path.is_file() {
-                return Ok(path);
-            }
+                .locate(cwd),
+                None => Ok(path.clone()),
+            },
+            None => discovery.locate(cwd),
         }
-
-        Err(ClaudeError::CliNotFound(CliNotFoundError::new(
-            "Claude Code CLI not found. Please ensure 'claude' is in your PATH or set CLAUDE_CLI_PATH environment variable.",
-            None,
-        )))
     }
 
     /// Build command arguments from options
@@ -420,7 +420,7 @@ impl SubprocessTransport {
         // Add additional directories
         for dir in &self.options.add_dirs {
             args.push("--add-dir".to_string());
-            args.push(dir.display().to_string());
+            args.push(self.cli_visible_path(dir));
         }
 
         // Add include partial messages
@@ -460,14 +460,14 @@ impl SubprocessTransport {
         for plugin in &self.options.plugins {
             if let Some(path) = plugin.path() {
                 args.push("--plugin-dir".to_string());
-                args.push(path.display().to_string());
+                args.push(self.cli_visible_path(path));
             }
         }
 
         // Add additional directories
         for dir in &self.options.add_dirs {
             args.push("--add-dir".to_string());
-            args.push(dir.display().to_string());
+            args.push(self.cli_visible_path(dir));
         }
 
         // Add MCP servers configuration
@@ -476,46 +476,7 @@ impl SubprocessTransport {
                 let mut servers_for_cli = serde_json::Map::new();
 
                 for (name, config) in servers {
-                    match config {
-                        crate::types::mcp::McpServerConfig::Sdk(sdk_config) => {
-                            // For SDK servers, pass type and name (instance stays in Rust)
-                            servers_for_cli.insert(
-                                name.clone(),
-                                serde_json::json!({
-                                    "type": "sdk",
-                                    "name": sdk_config.name
-                                }),
-                            );
-                        }
-                        crate::types::mcp::McpServerConfig::Stdio(stdio_config) => {
-                            // For stdio servers, serialize with type field
-                            // Note: type is optional for backwards compatibility
-                            if let Ok(mut value) = serde_json::to_value(stdio_config) {
-                                if let Some(obj) = value.as_object_mut() {
-                                    obj.insert("type".to_string(), serde_json::json!("stdio"));
-                                }
-                                servers_for_cli.insert(name.clone(), value);
-                            }
-                        }
-                        crate::types::mcp::McpServerConfig::Sse(sse_config) => {
-                            // For SSE servers, serialize with required type field
-                            if let Ok(mut value) = serde_json::to_value(sse_config) {
-                                if let Some(obj) = value.as_object_mut() {
-                                    obj.insert("type".to_string(), serde_json::json!("sse"));
-                                }
-                                servers_for_cli.insert(name.clone(), value);
-                            }
-                        }
-                        crate::types::mcp::McpServerConfig::Http(http_config) => {
-                            // For HTTP servers, serialize with required type field
-                            if let Ok(mut value) = serde_json::to_value(http_config) {
-                                if let Some(obj) = value.as_object_mut() {
-                                    obj.insert("type".to_string(), serde_json::json!("http"));
-                                }
-                                servers_for_cli.insert(name.clone(), value);
-                            }
-                        }
-                    }
+                    servers_for_cli.insert(name.clone(), config.to_cli_config());
                 }
 
                 if !servers_for_cli.is_empty() {
@@ -526,7 +487,7 @@ impl SubprocessTransport {
             crate::types::mcp::McpServers::Path(path) => {
                 // Path to config file - pass directly
                 args.push("--mcp-config".to_string());
-                args.push(path.display().to_string());
+                args.push(self.cli_visible_path(path));
             }
             crate::types::mcp::McpServers::Empty => {
                 // No MCP servers configured
@@ -544,6 +505,52 @@ impl SubprocessTransport {
         args
     }
 
+    /// Render a host path as the CLI sees it, translating through the launcher if set
+    fn cli_visible_path(&self, path: &Path) -> String {
+        match self.options.launcher {
+            Some(ref launcher) => launcher.map_path(path).display().to_string(),
+            None => path.display().to_string(),
+        }
+    }
+
+    /// Build the process command for running the CLI with the given arguments
+    ///
+    /// Runs the CLI directly, or through the configured launcher wrapper.
+    fn build_process_command(&self, args: Vec<String>, env: HashMap<String, String>) -> Command {
+        let Some(ref launcher) = self.options.launcher else {
+            let mut cmd = create_async_hidden_command(&self.cli_path);
+            self.apply_env_policy(&mut cmd);
+            cmd.args(&args).envs(&env);
+            if let Some(ref cwd) = self.cwd {
+                cmd.current_dir(cwd);
+            }
+            return cmd;
+        };
+
+        let launch = launcher.wrap(&self.cli_path, args, env, self.cwd.as_deref());
+        let mut cmd = create_async_hidden_command(&launch.program);
+        self.apply_env_policy(&mut cmd);
+        cmd.args(&launch.args);
+        for key in &launch.env_remove {
+            cmd.env_remove(key);
+        }
+        cmd.envs(&launch.env);
+        if let Some(ref cwd) = launch.cwd {
+            cmd.current_dir(cwd);
+        }
+        cmd
+    }
+
+    /// Replace the inherited environment with what the env policy keeps
+    ///
+    /// Must run before the SDK's own variables are set on `cmd`.
+    fn apply_env_policy(&self, cmd: &mut Command) {
+        if let Some(ref policy) = self.options.env_policy {
+            cmd.env_clear();
+            cmd.envs(policy.inherited(std::env::vars_os()));
+        }
+    }
+
     /// Build settings value, merging sandbox settings if provided.
     ///
     /// Returns the settings value as either:
@@ -597,15 +604,15 @@ impl SubprocessTransport {
         Some(serde_json::to_string(&serde_json::Value::Object(settings_obj)).unwrap_or_default())
     }
 
-    /// Check Claude CLI version
+    /// Check Claude CLI version, and that it supports the features the options turn on
     async fn check_claude_version(&self) -> Result<()> {
         // Skip if option is set OR environment variable is set
         if self.options.skip_version_check || std::env::var(SKIP_VERSION_CHECK_ENV).is_ok() {
             return Ok(());
         }
 
-        let output = create_async_hidden_command(&self.cli_path)
-            .arg("--version")
+        let output = self
+            .build_process_command(vec!["--version".to_string()], self.build_env())
             .output()
             .await
             .map_err(|e| {
@@ -615,15 +622,10 @@ impl SubprocessTransport {
                 )))
             })?;
 
-        let version_output = String::from_utf8_lossy(&output.stdout);
-        let version = version_output
-            .lines()
-            .next()
-            .and_then(|line| line.split_whitespace().next())
-            .unwrap_or("")
-            .trim();
+        let version =
+            parse_version_output(&String::from_utf8_lossy(&output.stdout)).unwrap_or_default();
 
-        if !check_version(version) {
+        if !check_version(&version) {
             warn!(
                 "Claude Code CLI ({}) version {} is below minimum required version {}. Some features may not work correctly.",
                 self.cli_path.display(),
@@ -632,6 +634,9 @@ impl SubprocessTransport {
             );
         }
 
+        Capabilities::detect(Some(&version), None).check_options(&self.options)?;
+        *self.cli_version.lock().unwrap() = Some(version);
+
         Ok(())
     }
 
@@ -652,8 +657,349 @@ impl SubprocessTransport {
             );
         }
 
+        // Route the isolated CLI's traffic through the SDK's proxy
+        if let Some(ref isolation) = self.options.network_isolation {
+            let proxy = format!("http://127.0.0.1:{}", isolation.proxy_port);
+            for key in ["HTTPS_PROXY", "HTTP_PROXY", "https_proxy", "http_proxy"] {
+                env.insert(key.to_string(), proxy.clone());
+            }
+            env.insert("NO_PROXY".to_string(), "localhost,127.0.0.1".to_string());
+        }
+
         env
     }
+
+    /// Most recent lines the CLI wrote to stderr, oldest first
+    pub fn stderr_tail(&self) -> Vec<String> {
+        self.stderr_tail.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Map a stdin write failure, reporting the exit status if the CLI has died
+    fn write_error(&self, error: std::io::Error) -> ClaudeError {
+        let status = self
+            .process
+            .lock()
+            .unwrap()
+            .as_mut()
+            .and_then(|process| process.try_wait().ok().flatten());
+        match status {
+            Some(status) => {
+                let stderr_tail = self.stderr_tail();
+                let limit = self.exceeded_limit(exit_signal(&status), &stderr_tail);
+                let cause = limit
+                    .map(|limit| format!(", likely from the {}", limit))
+                    .unwrap_or_default();
+                ClaudeError::Process(
+                    ProcessError::new(
+                        format!(
+                            "Claude CLI exited ({}){}, failed to write to stdin: {}",
+                            status, cause, error
+                        ),
+                        status.code(),
+                        Some(stderr_tail.join("\n")),
+                    )
+                    .with_limit(limit),
+                )
+            }
+            None => ClaudeError::Transport(format!("Failed to write to stdin: {}", error)),
+        }
+    }
+
+    /// The configured resource limit a dead CLI most likely ran into
+    #[cfg(target_os = "linux")]
+    fn exceeded_limit(
+        &self,
+        signal: Option<i32>,
+        stderr_tail: &[String],
+    ) -> Option<ResourceLimitKind> {
+        let limits = self.options.resource_limits.as_ref()?;
+        crate::internal::resource_limits::exceeded_limit(limits, signal, stderr_tail)
+    }
+
+    /// Resource limits are only applied on Linux
+    #[cfg(not(target_os = "linux"))]
+    fn exceeded_limit(
+        &self,
+        _signal: Option<i32>,
+        _stderr_tail: &[String],
+    ) -> Option<ResourceLimitKind> {
+        None
+    }
+
+    /// Exit report for a CLI that ended without being asked to
+    ///
+    /// The exit counts as unexpected while the SDK still had input to send, or when
+    /// the CLI failed before producing a result. Exits during shutdown never do.
+    async fn unexpected_exit(&self, saw_result: bool) -> Option<ProcessExited> {
+        let input_open = self.stdin.lock().await.is_some();
+
+        // stdout closes just before the process is reaped, so poll briefly
+        let deadline = tokio::time::Instant::now() + EXIT_STATUS_TIMEOUT;
+        let status = loop {
+            if self.closing.load(Ordering::SeqCst) {
+                return None;
+            }
+            let status = match self.process.lock().unwrap().as_mut() {
+                Some(process) => process.try_wait().ok()?,
+                None => return None,
+            };
+            match status {
+                Some(status) => break status,
+                None if tokio::time::Instant::now() >= deadline => return None,
+                None => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+
+        if !input_open && (status.success() || saw_result) {
+            return None;
+        }
+
+        // Let the stderr task pick up the last lines
+        let stderr_task = self.stderr_task.lock().unwrap().take();
+        if let Some(task) = stderr_task {
+            let _ = tokio::time::timeout(EXIT_STATUS_TIMEOUT, task).await;
+        }
+
+        let signal = exit_signal(&status);
+        let stderr_tail = self.stderr_tail();
+        Some(ProcessExited {
+            code: status.code(),
+            signal,
+            limit: self.exceeded_limit(signal, &stderr_tail),
+            stderr_tail,
+        })
+    }
+
+    /// Install the `pre_exec` hook that isolates the CLI before it starts (Linux only)
+    ///
+    /// The hook runs in the forked child before `exec`, where only async-signal-safe
+    /// calls are allowed: the parent may have held a lock in another thread at fork
+    /// time. So everything that allocates or touches the filesystem is prepared
+    /// here in the parent, and each step the hook calls only makes raw syscalls.
+    #[cfg(target_os = "linux")]
+    fn configure_isolation(&self, cmd: &mut Command) -> Result<IsolationReceivers> {
+        let resource_limits = self
+            .options
+            .resource_limits
+            .clone()
+            .filter(|limits| !limits.is_empty());
+        let (network, network_receiver) = match self.options.network_isolation.as_ref() {
+            Some(isolation) => {
+                let (network, receiver) = crate::internal::netns::prepare_isolation(
+                    isolation.proxy_port,
+                )
+                .map_err(|e| {
+                    ClaudeError::Process(ProcessError::new(
+                        format!("Failed to prepare network isolation: {}", e),
+                        None,
+                        None,
+                    ))
+                })?;
+                (Some(network), Some(receiver))
+            }
+            None => (None, None),
+        };
+        let (mut sandbox, landlock_receiver) = match self
+            .options
+            .landlock_sandbox
+            .as_ref()
+            .filter(|config| !config.is_empty())
+        {
+            Some(config) => crate::internal::sandbox::prepare_landlock_sandbox(config)
+                .map(|(sandbox, receiver)| (Some(sandbox), Some(receiver)))
+                .map_err(|e| {
+                    ClaudeError::Process(ProcessError::new(
+                        format!("Failed to prepare Landlock sandbox: {}", e),
+                        None,
+                        None,
+                    ))
+                })?,
+            None => (None, None),
+        };
+        let seccomp = self
+            .options
+            .seccomp_profile
+            .as_ref()
+            .map(crate::internal::seccomp::SeccompFilter::new)
+            .transpose()?;
+
+        if resource_limits.is_some() || network.is_some() || sandbox.is_some() || seccomp.is_some()
+        {
+            unsafe {
+                cmd.pre_exec(move || {
+                    if let Some(ref limits) = resource_limits {
+                        crate::internal::resource_limits::apply_resource_limits(limits)?;
+                    }
+                    if let Some(ref network) = network {
+                        network.enter()?;
+                    }
+                    if let Some(ref mut sandbox) = sandbox {
+                        sandbox.restrict_self()?;
+                    }
+                    // Last, so the steps above aren't subject to the filter
+                    if let Some(ref seccomp) = seccomp {
+                        seccomp.install()?;
+                    }
+                    Ok(())
+                });
+            }
+        }
+
+        Ok(IsolationReceivers {
+            landlock: landlock_receiver,
+            network: network_receiver,
+        })
+    }
+
+    /// Collect what the child reported and start the network proxy (Linux only)
+    #[cfg(target_os = "linux")]
+    fn finish_isolation(&self, receivers: IsolationReceivers) -> Result<()> {
+        let landlock = match receivers.landlock {
+            Some(receiver) => Some(receiver.receive().map_err(|e| {
+                ClaudeError::Process(ProcessError::new(
+                    format!("Failed to read Landlock status from the CLI process: {}", e),
+                    None,
+                    None,
+                ))
+            })?),
+            None => None,
+        };
+        if let Some(status) = landlock {
+            if status.is_fully_enforced() {
+                debug!("Landlock sandbox fully enforced (ABI {:?})", status.abi);
+            } else {
+                warn!(
+                    "Landlock sandbox {:?} (ABI {:?}); the CLI is less restricted than configured",
+                    status.enforcement, status.abi
+                );
+            }
+        }
+
+        let network_isolated = match (receivers.network, &self.options.network_isolation) {
+            (Some(receiver), Some(isolation)) => {
+                let listener = receiver.receive_listener()?;
+                let allowed =
+                    crate::internal::network_proxy::AllowList::new(&isolation.allowed_destinations);
+                let proxy = crate::internal::network_proxy::spawn_proxy(listener, allowed)
+                    .map_err(|e| {
+                        ClaudeError::Process(ProcessError::new(
+                            format!("Failed to start the network proxy: {}", e),
+                            None,
+                            None,
+                        ))
+                    })?;
+                if let Some(previous) = self.proxy_task.lock().unwrap().replace(proxy) {
+                    previous.abort();
+                }
+                true
+            }
+            _ => false,
+        };
+
+        *self.sandbox_status.lock().unwrap() = SandboxStatus {
+            landlock,
+            network_isolated,
+        };
+        Ok(())
+    }
+
+    /// Stop the CLI, escalating per the configured [`ShutdownPolicy`](crate::types::config::ShutdownPolicy)
+    async fn stop_process(&self, mut process: Child) -> Result<ShutdownStage> {
+        let policy = &self.options.shutdown_policy;
+        let pid = Some(self.pid.load(Ordering::SeqCst)).filter(|pid| *pid != 0);
+
+        let (stage, status) =
+            if let Ok(status) = tokio::time::timeout(policy.grace_period, process.wait()).await {
+                (ShutdownStage::Graceful, status)
+            } else {
+                debug!("Claude CLI still running after grace period, terminating");
+                self.escalate(&mut process, pid).await
+            };
+
+        // Stop anything left behind in the group, e.g. a tool process that outlived the CLI
+        #[cfg(unix)]
+        if let Some(pid) = pid {
+            self.stop_leftover_group(pid).await;
+        }
+
+        let status = status.map_err(|e| {
+            ClaudeError::Process(ProcessError::new(
+                format!("Failed to wait for process: {}", e),
+                None,
+                None,
+            ))
+        })?;
+
+        // Note: Claude CLI may exit with non-zero status (e.g., exit code 1) even after
+        // successfully completing a query. This is normal behavior - the Result message
+        // is the authoritative indicator of success/failure, not the exit code.
+        // We log a debug warning but don't fail here.
+        if stage == ShutdownStage::Graceful && !status.success() {
+            warn!(
+                "Claude CLI exited with non-zero status (exit code {:?}). This is often normal.",
+                status.code()
+            );
+        }
+
+        Ok(stage)
+    }
+
+    /// SIGTERM the process group, then SIGKILL it if it outlives `term_timeout`
+    #[cfg(unix)]
+    async fn escalate(
+        &self,
+        process: &mut Child,
+        pid: Option<u32>,
+    ) -> (ShutdownStage, std::io::Result<std::process::ExitStatus>) {
+        if let Some(pid) = pid {
+            signal_process_group(pid, libc::SIGTERM);
+            let term_timeout = self.options.shutdown_policy.term_timeout;
+            if let Ok(status) = tokio::time::timeout(term_timeout, process.wait()).await {
+                return (ShutdownStage::Terminated, status);
+            }
+            warn!("Claude CLI ignored SIGTERM, killing its process group");
+            signal_process_group(pid, libc::SIGKILL);
+        }
+        let _ = process.start_kill();
+        (ShutdownStage::Killed, process.wait().await)
+    }
+
+    /// SIGTERM what is left of the CLI's process group, then SIGKILL it after `term_timeout`
+    ///
+    /// The leader has been reaped by now, so its ID could in principle be reused.
+    /// While any member of the group is alive the kernel keeps the ID reserved, so
+    /// the group is only signalled after checking that it still exists.
+    #[cfg(unix)]
+    async fn stop_leftover_group(&self, pgid: u32) {
+        if !process_group_exists(pgid) {
+            return;
+        }
+        debug!("Stopping processes left in the Claude CLI's process group");
+        signal_process_group(pgid, libc::SIGTERM);
+
+        let deadline = tokio::time::Instant::now() + self.options.shutdown_policy.term_timeout;
+        while tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            if !process_group_exists(pgid) {
+                return;
+            }
+        }
+        if process_group_exists(pgid) {
+            warn!("Processes in the Claude CLI's process group ignored SIGTERM, killing them");
+            signal_process_group(pgid, libc::SIGKILL);
+        }
+    }
+
+    /// Kill the process; Windows has no SIGTERM equivalent
+    #[cfg(not(unix))]
+    async fn escalate(
+        &self,
+        process: &mut Child,
+        _pid: Option<u32>,
+    ) -> (ShutdownStage, std::io::Result<std::process::ExitStatus>) {
+        let _ = process.start_kill();
+        (ShutdownStage::Killed, process.wait().await)
+    }
 }
 
 #[async_trait]
@@ -668,40 +1014,50 @@ impl Transport for SubprocessTransport {
         let args = self.build_command();
         let env = self.build_env();
 
-        // Build command (hidden console window on Windows)
-        let mut cmd = create_async_hidden_command(&self.cli_path);
-        cmd.args(&args)
-            .stdin(Stdio::piped())
+        // Build command (hidden console window on Windows), wrapped by the launcher if set
+        let mut cmd = self.build_process_command(args, env);
+        cmd.stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .envs(&env);
+            .stderr(Stdio::piped());
 
-        if let Some(ref cwd) = self.cwd {
-            cmd.current_dir(cwd);
-        }
+        // Run the CLI in its own process group so shutdown also reaches the tool
+        // processes and MCP servers it spawns
+        #[cfg(unix)]
+        cmd.process_group(0);
 
-        // Apply Landlock filesystem sandbox (Linux only)
+        // Apply resource limits, namespaces, the Landlock sandbox and the seccomp filter
         #[cfg(target_os = "linux")]
-        if let Some(ref landlock) = self.options.landlock_sandbox {
-            if !landlock.writable_roots.is_empty() {
-                let writable_roots: Vec<std::path::PathBuf> = landlock.writable_roots.clone();
-                unsafe {
-                    cmd.pre_exec(move || {
-                        crate::internal::sandbox::apply_landlock_sandbox(&writable_roots)
-                            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
-                    });
-                }
-            }
+        let receivers = self.configure_isolation(&mut cmd)?;
+        #[cfg(not(target_os = "linux"))]
+        if self.options.network_isolation.is_some() {
+            return Err(ClaudeError::InvalidConfig(
+                "network_isolation is only supported on Linux".to_string(),
+            ));
         }
 
         // Spawn process
-        let mut child = cmd.spawn().map_err(|e| {
-            ClaudeError::Process(ProcessError::new(
-                format!("Failed to spawn Claude CLI process: {}", e),
-                None,
-                None,
-            ))
-        })?;
+        let spawned = cmd.spawn();
+        drop(cmd);
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                #[cfg(target_os = "linux")]
+                if let Some(error) = receivers.network.and_then(|network| network.failure()) {
+                    return Err(error);
+                }
+                return Err(ClaudeError::Process(ProcessError::new(
+                    format!("Failed to spawn Claude CLI process: {}", e),
+                    None,
+                    None,
+                )));
+            }
+        };
+
+        #[cfg(target_os = "linux")]
+        if let Err(e) = self.finish_isolation(receivers) {
+            let _ = child.start_kill();
+            return Err(e);
+        }
 
         // Take stdin and stdout
         let stdin = child.stdin.take().ok_or_else(|| {
@@ -712,27 +1068,42 @@ impl Transport for SubprocessTransport {
             ClaudeError::Connection(ConnectionError::new("Failed to get stdout".to_string()))
         })?;
 
-        let stderr = child.stderr.take();
-
-        // Spawn stderr handler if callback is provided
-        if let (Some(stderr), Some(callback)) = (stderr, &self.options.stderr_callback) {
-            let callback = Arc::clone(callback);
-            tokio::spawn(async move {
+        // Keep a tail of stderr for exit reports, forwarding lines to the callback if set
+        if let Some(stderr) = child.stderr.take() {
+            let callback = self.options.stderr_callback.clone();
+            let redactor = self.redactor.clone();
+            let stderr_tail = Arc::clone(&self.stderr_tail);
+            stderr_tail.lock().unwrap().clear();
+            let task = tokio::spawn(async move {
                 let mut reader = BufReader::new(stderr);
                 let mut line = String::new();
                 while let Ok(n) = reader.read_line(&mut line).await {
                     if n == 0 {
                         break;
                     }
-                    callback(line.clone());
+                    let redacted = redactor.redact(&line);
+                    {
+                        let mut tail = stderr_tail.lock().unwrap();
+                        if tail.len() == STDERR_TAIL_LINES {
+                            tail.pop_front();
+                        }
+                        tail.push_back(redacted.trim_end().to_string());
+                    }
+                    if let Some(callback) = &callback {
+                        callback(redacted.into_owned());
+                    }
                     line.clear();
                 }
             });
+            *self.stderr_task.lock().unwrap() = Some(task);
         }
 
         *self.stdin.lock().await = Some(stdin);
         *self.stdout.lock().await = Some(BufReader::new(stdout));
+        self.pid
+            .store(child.id().unwrap_or_default(), Ordering::SeqCst);
         *self.process.lock().unwrap() = Some(child);
+        self.closing.store(false, Ordering::SeqCst);
         self.ready.store(true, Ordering::SeqCst);
 
         // Send initial prompt based on type
@@ -742,15 +1113,9 @@ impl Transport for SubprocessTransport {
                 self.write(&text_owned).await?;
                 self.end_input().await?;
             }
-            QueryPrompt::Content(blocks) => {
+            QueryPrompt::Content(_) => {
                 // Format as JSON user message for stream-json input format
-                let user_message = serde_json::json!({
-                    "type": "user",
-                    "message": {
-                        "role": "user",
-                        "content": blocks
-                    }
-                });
+                let user_message = self.prompt.to_user_message();
                 let content_json = serde_json::to_string(&user_message).map_err(|e| {
                     ClaudeError::Transport(format!("Failed to serialize content blocks: {}", e))
                 })?;
@@ -768,19 +1133,13 @@ impl Transport for SubprocessTransport {
     async fn write(&self, data: &str) -> Result<()> {
         let mut stdin_guard = self.stdin.lock().await;
         if let Some(ref mut stdin) = *stdin_guard {
-            stdin
-                .write_all(data.as_bytes())
-                .await
-                .map_err(|e| ClaudeError::Transport(format!("Failed to write to stdin: {}", e)))?;
-            stdin
-                .write_all(b"\n")
-                .await
-                .map_err(|e| ClaudeError::Transport(format!("Failed to write newline: {}", e)))?;
-            stdin
-                .flush()
-                .await
-                .map_err(|e| ClaudeError::Transport(format!("Failed to flush stdin: {}", e)))?;
-            Ok(())
+            let written = async {
+                stdin.write_all(data.as_bytes()).await?;
+                stdin.write_all(b"\n").await?;
+                stdin.flush().await
+            }
+            .await;
+            written.map_err(|e| self.write_error(e))
         } else {
             Err(ClaudeError::Transport("stdin not available".to_string()))
         }
@@ -789,47 +1148,106 @@ impl Transport for SubprocessTransport {
     fn read_messages(&self) -> Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send + '_>> {
         let stdout = Arc::clone(&self.stdout);
         let max_buffer_size = self.max_buffer_size;
+        let spill_dir = self.options.spill_dir.clone();
 
         Box::pin(async_stream::stream! {
             let mut stdout_guard = stdout.lock().await;
             if let Some(ref mut reader) = *stdout_guard {
-                let mut line = String::new();
-                let mut buffer_size = 0;
+                let mut line = Vec::new();
+                let mut saw_result = false;
 
                 loop {
-                    line.clear();
-                    match reader.read_line(&mut line).await {
-                        Ok(0) => {
-                            // EOF
+                    match read_line_bounded(reader, &mut line, max_buffer_size).await {
+                        Ok(LineRead::Eof) => {
+                            if let Some(exit) = self.unexpected_exit(saw_result).await {
+                                warn!(
+                                    "Claude CLI exited unexpectedly (code {:?}, signal {:?})",
+                                    exit.code, exit.signal
+                                );
+                                let mut report = serde_json::to_value(&exit).unwrap_or_default();
+                                report["type"] = serde_json::json!("sdk_process_exited");
+                                yield Ok(report);
+                            }
                             break;
                         }
-                        Ok(n) => {
-                            buffer_size += n;
-                            if buffer_size > max_buffer_size {
-                                yield Err(ClaudeError::Transport(format!(
-                                    "Buffer size exceeded maximum of {} bytes",
-                                    max_buffer_size
-                                )));
-                                break;
-                            }
-
-                            let trimmed = line.trim();
+                        Ok(LineRead::Line) => {
+                            let trimmed = line.trim_ascii();
                             if trimmed.is_empty() {
                                 continue;
                             }
 
-                            match serde_json::from_str::<serde_json::Value>(trimmed) {
+                            match serde_json::from_slice::<serde_json::Value>(trimmed) {
                                 Ok(json) => {
+                                    saw_result |= json.get("type").and_then(|v| v.as_str()) == Some("result");
                                     yield Ok(json);
                                 }
                                 Err(e) => {
+                                    let line = String::from_utf8_lossy(trimmed);
                                     yield Err(ClaudeError::JsonDecode(JsonDecodeError::new(
                                         format!("Failed to parse JSON: {}", e),
-                                        trimmed.to_string(),
+                                        self.redactor.redact(&line),
                                     )));
                                 }
                             }
                         }
+                        Ok(LineRead::Oversized) => {
+                            let message_type = peek_message_type(&line);
+                            let spillable = message_type
+                                .as_deref()
+                                .is_none_or(|t| !t.starts_with("control_"));
+
+                            match &spill_dir {
+                                Some(dir) if spillable => {
+                                    match spill_line(reader, &line, dir).await {
+                                        Ok((path, size)) => {
+                                            debug!("Spilled {} byte message to {}", size, path.display());
+                                            yield Ok(serde_json::json!({
+                                                "type": "sdk_spilled",
+                                                "path": path,
+                                                "size": size,
+                                                "message_type": message_type,
+                                            }));
+                                        }
+                                        Err(e) => {
+                                            yield Err(ClaudeError::Transport(format!(
+                                                "Failed to spill oversized message: {}",
+                                                e
+                                            )));
+                                            break;
+                                        }
+                                    }
+                                }
+                                _ => {
+                                    // Skip the rest of the line so later messages still arrive
+                                    let rest = drain_line(reader, &mut tokio::io::sink()).await;
+                                    let error = format!(
+                                        "Message of {} bytes exceeds maximum of {} bytes",
+                                        line.len() as u64 + rest.as_ref().copied().unwrap_or(0),
+                                        max_buffer_size
+                                    );
+                                    // The CLI waits for an answer to every control request
+                                    if message_type.as_deref() == Some("control_request")
+                                        && let Some(request_id) = peek_request_id(&line)
+                                    {
+                                        let response = serde_json::json!({
+                                            "type": "control_response",
+                                            "response": {
+                                                "subtype": "error",
+                                                "request_id": request_id,
+                                                "error": error,
+                                            }
+                                        });
+                                        if let Err(e) = self.write(&response.to_string()).await {
+                                            warn!("Failed to reject oversized control request: {}", e);
+                                        }
+                                    }
+                                    yield Err(ClaudeError::Transport(error));
+                                    if rest.is_err() {
+                                        break;
+                                    }
+                                }
+                            }
+                        }
                         Err(e) => {
                             yield Err(ClaudeError::Transport(format!("Failed to read line: {}", e)));
                             break;
@@ -841,43 +1259,43 @@ impl Transport for SubprocessTransport {
     }
 
     async fn close(&self) -> Result<()> {
-        // Close stdin
+        self.shutdown().await.map(|_| ())
+    }
+
+    async fn shutdown(&self) -> Result<ShutdownStage> {
+        self.closing.store(true, Ordering::SeqCst);
+
+        // Close stdin so the CLI can finish and exit on its own
         if let Some(mut stdin) = self.stdin.lock().await.take() {
             let _ = stdin.shutdown().await;
         }
 
-        // Wait for process to exit
         // Take the process out of the mutex and drop the guard before awaiting
         let process_opt = self.process.lock().unwrap().take();
-        if let Some(mut process) = process_opt {
-            let status = process.wait().await.map_err(|e| {
-                ClaudeError::Process(ProcessError::new(
-                    format!("Failed to wait for process: {}", e),
-                    None,
-                    None,
-                ))
-            })?;
-
-            // Note: Claude CLI may exit with non-zero status (e.g., exit code 1) even after
-            // successfully completing a query. This is normal behavior - the Result message
-            // is the authoritative indicator of success/failure, not the exit code.
-            // We log a debug warning but don't fail here.
-            if !status.success() {
-                warn!(
-                    "Claude CLI exited with non-zero status (exit code {:?}). This is often normal.",
-                    status.code()
-                );
-            }
+        let stage = match process_opt {
+            Some(process) => self.stop_process(process).await?,
+            None => ShutdownStage::Graceful,
+        };
+        if let Some(proxy) = self.proxy_task.lock().unwrap().take() {
+            proxy.abort();
         }
 
         self.ready.store(false, Ordering::SeqCst);
-        Ok(())
+        Ok(stage)
     }
 
     fn is_ready(&self) -> bool {
         self.ready.load(Ordering::SeqCst)
     }
 
+    fn sandbox_status(&self) -> SandboxStatus {
+        self.sandbox_status.lock().unwrap().clone()
+    }
+
+    fn cli_version(&self) -> Option<String> {
+        self.cli_version.lock().unwrap().clone()
+    }
+
     async fn end_input(&self) -> Result<()> {
         if let Some(mut stdin) = self.stdin.lock().await.take() {
             stdin
@@ -891,10 +1309,46 @@ impl Transport for SubprocessTransport {
 
 impl Drop for SubprocessTransport {
     fn drop(&mut self) {
+        if let Ok(mut guard) = self.proxy_task.lock()
+            && let Some(proxy) = guard.take()
+        {
+            proxy.abort();
+        }
         if let Ok(mut guard) = self.process.lock()
             && let Some(mut process) = guard.take()
         {
+            #[cfg(unix)]
+            match self.pid.load(Ordering::SeqCst) {
+                0 => {}
+                pid => signal_process_group(pid, libc::SIGKILL),
+            }
             let _ = process.start_kill();
         }
     }
 }
+
+/// Signal that terminated the process (Unix only)
+fn exit_signal(status: &std::process::ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    return std::os::unix::process::ExitStatusExt::signal(status);
+    #[cfg(not(unix))]
+    return None;
+}
+
+/// Whether any process is left in the group led by `pgid`
+#[cfg(unix)]
+fn process_group_exists(pgid: u32) -> bool {
+    // SAFETY: signal 0 only checks that the group exists and may be signalled
+    unsafe { libc::kill(-(pgid as libc::pid_t), 0) == 0 }
+}
+
+/// Send `signal` to every process in the group led by `pgid`
+///
+/// Errors are ignored: the group may already be gone.
+#[cfg(unix)]
+fn signal_process_group(pgid: u32, signal: libc::c_int) {
+    // SAFETY: kill(2) has no memory safety requirements; a negative pid targets the group
+    unsafe {
+        libc::kill(-(pgid as libc::pid_t), signal);
+    }
+}
@@ -0,0 +1,94 @@
+//! Transport wrapper that feeds every frame to a protocol tap
+
+use async_trait::async_trait;
+use futures::stream::{Stream, StreamExt};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::errors::Result;
+use crate::types::config::ClaudeAgentOptions;
+use crate::types::env::Redactor;
+use crate::types::sandbox::SandboxStatus;
+use crate::types::tap::{ProtocolTap, TapDirection};
+
+use super::{ShutdownStage, Transport};
+
+/// Wrap `transport` with the tap from `options`, if one is set
+pub fn tap_transport(
+    transport: Arc<dyn Transport>,
+    options: &ClaudeAgentOptions,
+) -> Arc<dyn Transport> {
+    match &options.protocol_tap {
+        Some(tap) => Arc::new(TappedTransport {
+            inner: transport,
+            tap: tap.clone(),
+            redactor: options
+                .env_policy
+                .as_ref()
+                .map(|policy| policy.redactor(&options.env))
+                .unwrap_or_default(),
+        }),
+        None => transport,
+    }
+}
+
+/// Passes everything through to `inner`, recording each frame on the way
+struct TappedTransport {
+    inner: Arc<dyn Transport>,
+    tap: ProtocolTap,
+    /// Secrets from the environment policy, masked before the tap sees a frame
+    redactor: Redactor,
+}
+
+impl TappedTransport {
+    fn record(&self, direction: TapDirection, mut frame: serde_json::Value) {
+        self.redactor.redact_json(&mut frame);
+        self.tap.record(direction, frame);
+    }
+}
+
+#[async_trait]
+impl Transport for TappedTransport {
+    async fn connect(&self) -> Result<()> {
+        self.inner.connect().await
+    }
+
+    async fn write(&self, data: &str) -> Result<()> {
+        let frame = serde_json::from_str(data)
+            .unwrap_or_else(|_| serde_json::Value::String(data.to_string()));
+        self.record(TapDirection::Sent, frame);
+        self.inner.write(data).await
+    }
+
+    fn read_messages(&self) -> Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send + '_>> {
+        Box::pin(self.inner.read_messages().inspect(|message| {
+            if let Ok(frame) = message {
+                self.record(TapDirection::Received, frame.clone());
+            }
+        }))
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.inner.close().await
+    }
+
+    async fn shutdown(&self) -> Result<ShutdownStage> {
+        self.inner.shutdown().await
+    }
+
+    fn is_ready(&self) -> bool {
+        self.inner.is_ready()
+    }
+
+    fn sandbox_status(&self) -> SandboxStatus {
+        self.inner.sandbox_status()
+    }
+
+    fn cli_version(&self) -> Option<String> {
+        self.inner.cli_version()
+    }
+
+    async fn end_input(&self) -> Result<()> {
+        self.inner.end_input().await
+    }
+}
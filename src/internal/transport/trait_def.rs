@@ -5,9 +5,33 @@ use futures::stream::Stream;
 use std::pin::Pin;
 
 use crate::errors::Result;
+use crate::types::sandbox::SandboxStatus;
+
+/// How far the shutdown sequence had to escalate before the CLI exited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownStage {
+    /// The CLI exited on its own after stdin was closed (or was not running)
+    Graceful,
+    /// The CLI's process group had to be sent SIGTERM
+    Terminated,
+    /// The CLI's process group had to be sent SIGKILL
+    Killed,
+}
 
 /// Transport trait for communicating with Claude Code CLI
 ///
+/// A transport carries the newline-delimited stream-json protocol between the SDK
+/// and a Claude Code CLI process. [`SubprocessTransport`](crate::SubprocessTransport)
+/// spawns the CLI locally; custom implementations can reach a CLI launched any other
+/// way (a job runner, a container, a remote relay) and be passed to
+/// [`ClaudeClient::with_transport`](crate::ClaudeClient::with_transport) or
+/// [`query_with_transport`](crate::query_with_transport).
+///
+/// Implementations must:
+/// - write each `data` payload as a single line (the SDK never embeds newlines)
+/// - yield every JSON object the CLI prints, in order, from `read_messages`
+/// - end the stream from `read_messages` when the CLI output ends
+///
 /// All methods use `&self` because implementations handle their own
 /// internal synchronization (e.g., Mutex for stdin/stdout, AtomicBool for ready state).
 /// This allows the transport to be shared via `Arc<dyn Transport>` without an outer Mutex.
@@ -20,15 +44,41 @@ pub trait Transport: Send + Sync {
     async fn write(&self, data: &str) -> Result<()>;
 
     /// Read messages as a stream of JSON values
+    ///
+    /// The SDK calls this once per connection and keeps the stream alive
+    /// for the lifetime of the session.
     fn read_messages(&self) -> Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send + '_>>;
 
     /// Close the transport
     async fn close(&self) -> Result<()>;
 
+    /// Close the transport, reporting how forcefully the CLI had to be stopped
+    ///
+    /// The default implementation calls [`close`](Self::close) and reports
+    /// [`ShutdownStage::Graceful`].
+    async fn shutdown(&self) -> Result<ShutdownStage> {
+        self.close().await?;
+        Ok(ShutdownStage::Graceful)
+    }
+
     /// Check if the transport is ready
-    #[allow(dead_code)]
     fn is_ready(&self) -> bool;
 
+    /// OS-level sandboxing in effect for the CLI behind this transport
+    ///
+    /// The default implementation reports no sandbox.
+    fn sandbox_status(&self) -> SandboxStatus {
+        SandboxStatus::default()
+    }
+
+    /// Version of the CLI behind this transport, if known
+    ///
+    /// Used to work out [`Capabilities`](crate::Capabilities). The default
+    /// implementation reports none, so every capability is assumed.
+    fn cli_version(&self) -> Option<String> {
+        None
+    }
+
     /// End input stream (close stdin)
     async fn end_input(&self) -> Result<()>;
 }
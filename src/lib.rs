@@ -17,6 +17,8 @@
 //! - **Extended Thinking**: Configure maximum thinking tokens for complex reasoning
 //! - **Session Management**: Resume, fork, and manage conversation sessions
 //! - **Multimodal Input**: Send images alongside text using base64 or URLs
+//! - **Pluggable Transports**: Reach the CLI through your own [`Transport`] implementation
+//! - **Remote CLI**: Drive a CLI on another host through [`SocketTransport`] and the `claude-relay` binary
 //!
 //! ## Quick Start
 //!
@@ -236,28 +238,47 @@
 pub mod client;
 pub mod errors;
 mod internal;
+pub mod locator;
 pub mod query;
+pub mod relay;
 #[cfg(feature = "testing")]
 pub mod testing;
 pub mod types;
 pub mod version;
 
 // Re-export commonly used types
-pub use errors::{ClaudeError, ImageValidationError, Result};
+pub use errors::{ClaudeError, ImageValidationError, Result, UnsupportedCapabilityError};
 pub use types::{
+    capabilities::{Capabilities, Capability},
     config::*,
     efficiency::{EfficiencyConfig, ExecutionMetrics, MetricsSummary},
+    env::{EnvInheritance, EnvPolicy, Redactor},
     hooks::*,
+    launcher::{EnvForwarding, PathMapping, ProcessLauncher},
     mcp::{
-        McpServerConfig, McpServers, SdkMcpServer, SdkMcpTool, ToolHandler, ToolResult,
+        McpConnectionStatus, McpServerConfig, McpServerInfo, McpServerStatus, McpServers,
+        SdkMcpServer, SdkMcpTool, ToolHandler, ToolResult,
         ToolResultContent as McpToolResultContent, create_sdk_mcp_server,
     },
     messages::*,
     permissions::*,
     plugin::*,
+    sandbox::*,
+    server_info::*,
+    tap::{ProtocolTap, TapCallback, TapDirection, TapFile, TapFrame, TapRedaction},
+    usage::{CacheCreation, ModelUsage, ServerToolUse, Usage},
 };
 
 // Re-export public API
 pub use client::ClaudeClient;
-pub use query::{query, query_stream, query_stream_with_content, query_with_content};
-pub use version::get_claude_code_version;
+pub use internal::message_queue::QueueMetrics;
+pub use internal::transport::{
+    QueryPrompt, ReconnectPolicy, RelayEndpoint, ShutdownStage, SocketTransport,
+    SocketTransportConfig, SubprocessTransport, Transport,
+};
+pub use locator::{CliCandidate, CliDiscovery, CliLocator};
+pub use query::{
+    query, query_stream, query_stream_with_content, query_stream_with_transport,
+    query_with_content, query_with_transport,
+};
+pub use version::{VersionRequirement, get_claude_code_version};
@@ -0,0 +1,565 @@
+//! Discovery of the Claude Code CLI executable
+//!
+//! A [`CliDiscovery`] walks a list of [`CliLocator`] strategies in order and
+//! returns the first candidate that exists and, if a [`VersionRequirement`] is
+//! set, reports a matching `--version`. Custom strategies can be added by
+//! implementing [`CliLocator`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use claude_agent_sdk_rs::{ClaudeAgentOptions, CliDiscovery};
+//!
+//! let discovery = CliDiscovery::builder()
+//!     .version_requirement(">=2.0.30, <3".parse().unwrap())
+//!     .prefer_newest(true)
+//!     .build();
+//!
+//! let options = ClaudeAgentOptions::builder()
+//!     .cli_discovery(discovery)
+//!     .build();
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use typed_builder::TypedBuilder;
+
+use crate::errors::{ClaudeError, CliNotFoundError, Result};
+use crate::internal::transport::subprocess::create_sync_hidden_command;
+use crate::version::{VersionRequirement, parse_version, parse_version_output};
+
+/// Executable names tried in each directory
+#[cfg(not(target_os = "windows"))]
+const CLI_NAMES: &[&str] = &["claude"];
+#[cfg(target_os = "windows")]
+const CLI_NAMES: &[&str] = &["claude.exe", "claude.cmd"];
+
+/// A strategy for finding Claude Code CLI executables
+pub trait CliLocator: Send + Sync {
+    /// Short label used in error reports (e.g. `"PATH"`)
+    fn name(&self) -> String;
+
+    /// Candidate executables in priority order; they need not exist
+    ///
+    /// `cwd` is the working directory the CLI will run in, if known.
+    fn candidates(&self, cwd: Option<&Path>) -> Vec<PathBuf>;
+}
+
+/// A specific executable path
+#[derive(Debug, Clone)]
+pub struct ExplicitPath(pub PathBuf);
+
+impl CliLocator for ExplicitPath {
+    fn name(&self) -> String {
+        "explicit path".to_string()
+    }
+
+    fn candidates(&self, _cwd: Option<&Path>) -> Vec<PathBuf> {
+        vec![self.0.clone()]
+    }
+}
+
+/// Path taken from an environment variable (`CLAUDE_CLI_PATH` by default)
+#[derive(Debug, Clone)]
+pub struct EnvVar(pub String);
+
+impl Default for EnvVar {
+    fn default() -> Self {
+        Self("CLAUDE_CLI_PATH".to_string())
+    }
+}
+
+impl CliLocator for EnvVar {
+    fn name(&self) -> String {
+        format!("${}", self.0)
+    }
+
+    fn candidates(&self, _cwd: Option<&Path>) -> Vec<PathBuf> {
+        std::env::var_os(&self.0)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Every directory on `PATH`
+#[derive(Debug, Clone, Default)]
+pub struct SearchPath;
+
+impl CliLocator for SearchPath {
+    fn name(&self) -> String {
+        "PATH".to_string()
+    }
+
+    fn candidates(&self, _cwd: Option<&Path>) -> Vec<PathBuf> {
+        std::env::var_os("PATH")
+            .map(|path| std::env::split_paths(&path).collect::<Vec<_>>())
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|dir| executables_in(dir))
+            .collect()
+    }
+}
+
+/// The npm global prefix (`NPM_CONFIG_PREFIX`, or `npm config get prefix`)
+#[derive(Debug, Clone, Default)]
+pub struct NpmGlobal;
+
+impl CliLocator for NpmGlobal {
+    fn name(&self) -> String {
+        "npm global".to_string()
+    }
+
+    fn candidates(&self, _cwd: Option<&Path>) -> Vec<PathBuf> {
+        let prefix = ["NPM_CONFIG_PREFIX", "npm_config_prefix"]
+            .iter()
+            .find_map(|var| std::env::var_os(var).filter(|v| !v.is_empty()))
+            .map(PathBuf::from)
+            .or_else(|| {
+                let output =
+                    create_sync_hidden_command(if cfg!(windows) { "npm.cmd" } else { "npm" })
+                        .args(["config", "get", "prefix"])
+                        .output()
+                        .ok()
+                        .filter(|output| output.status.success())?;
+                let prefix = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                (!prefix.is_empty()).then(|| PathBuf::from(prefix))
+            });
+
+        match prefix {
+            Some(prefix) if cfg!(windows) => executables_in(&prefix),
+            Some(prefix) => executables_in(&prefix.join("bin")),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Installs managed by nvm (every Node version, newest first) and Volta
+#[derive(Debug, Clone, Default)]
+pub struct NodeVersionManagers;
+
+impl CliLocator for NodeVersionManagers {
+    fn name(&self) -> String {
+        "nvm/volta".to_string()
+    }
+
+    fn candidates(&self, _cwd: Option<&Path>) -> Vec<PathBuf> {
+        let home = home_dir();
+        let mut candidates = Vec::new();
+
+        let nvm_dir = std::env::var_os("NVM_DIR")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".nvm")));
+        if let Some(nvm_dir) = nvm_dir
+            && let Ok(entries) = std::fs::read_dir(nvm_dir.join("versions").join("node"))
+        {
+            let mut versions: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+            versions.sort_by_key(|dir| {
+                std::cmp::Reverse(
+                    dir.file_name()
+                        .and_then(|name| parse_version(&name.to_string_lossy())),
+                )
+            });
+            for dir in versions {
+                candidates.extend(executables_in(&dir.join("bin")));
+            }
+        }
+
+        let volta_home = std::env::var_os("VOLTA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".volta")));
+        if let Some(volta_home) = volta_home {
+            candidates.extend(executables_in(&volta_home.join("bin")));
+        }
+
+        candidates
+    }
+}
+
+/// `node_modules/.bin` in the working directory or any of its ancestors
+#[derive(Debug, Clone, Default)]
+pub struct ProjectLocal;
+
+impl CliLocator for ProjectLocal {
+    fn name(&self) -> String {
+        "project node_modules".to_string()
+    }
+
+    fn candidates(&self, cwd: Option<&Path>) -> Vec<PathBuf> {
+        let start = cwd
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::current_dir().ok());
+        start
+            .iter()
+            .flat_map(|start| start.ancestors())
+            .flat_map(|dir| executables_in(&dir.join("node_modules").join(".bin")))
+            .collect()
+    }
+}
+
+/// Well-known install locations (native installer, Homebrew, system packages)
+#[derive(Debug, Clone, Default)]
+pub struct CommonLocations;
+
+impl CliLocator for CommonLocations {
+    fn name(&self) -> String {
+        "common locations".to_string()
+    }
+
+    fn candidates(&self, _cwd: Option<&Path>) -> Vec<PathBuf> {
+        let home = home_dir();
+        let mut candidates = Vec::new();
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            if let Some(ref home) = home {
+                candidates.push(home.join(".claude/local/claude"));
+                candidates.push(home.join(".local/bin/claude"));
+                candidates.push(home.join("bin/claude"));
+            }
+            candidates.extend([
+                PathBuf::from("/usr/local/bin/claude"),
+                PathBuf::from("/opt/homebrew/bin/claude"),
+                PathBuf::from("/usr/bin/claude"),
+            ]);
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            if let Some(ref home) = home {
+                candidates.extend([
+                    home.join("AppData\\Local\\Programs\\Claude\\claude.exe"),
+                    home.join("AppData\\Roaming\\npm\\claude.cmd"),
+                    home.join("AppData\\Roaming\\npm\\claude.exe"),
+                ]);
+            }
+            candidates.extend([
+                PathBuf::from("C:\\Program Files\\Claude\\claude.exe"),
+                PathBuf::from("C:\\Program Files (x86)\\Claude\\claude.exe"),
+            ]);
+        }
+
+        candidates
+    }
+}
+
+/// A CLI executable inspected during discovery
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CliCandidate {
+    /// Path of the executable
+    pub path: PathBuf,
+    /// Name of the locator that produced it
+    pub source: String,
+    /// Whether an executable file exists there
+    pub exists: bool,
+    /// Version reported by `--version`, if it was queried and could be parsed
+    pub version: Option<String>,
+}
+
+impl fmt::Display for CliCandidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): ", self.path.display(), self.source)?;
+        match (&self.version, self.exists) {
+            (Some(version), _) => write!(f, "version {}", version),
+            (None, true) => write!(f, "version unknown"),
+            (None, false) => write!(f, "not found"),
+        }
+    }
+}
+
+/// Ordered CLI discovery with an optional version requirement
+#[derive(Clone, TypedBuilder)]
+#[builder(doc)]
+pub struct CliDiscovery {
+    /// Strategies to try, in priority order
+    #[builder(default = CliDiscovery::default_locators())]
+    pub locators: Vec<Arc<dyn CliLocator>>,
+    /// Only accept CLIs whose `--version` satisfies this requirement
+    #[builder(default, setter(strip_option))]
+    pub version_requirement: Option<VersionRequirement>,
+    /// Pick the newest matching CLI instead of the first one found
+    ///
+    /// Only applies with a version requirement, since versions are not queried otherwise.
+    #[builder(default)]
+    pub prefer_newest: bool,
+}
+
+impl Default for CliDiscovery {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl CliDiscovery {
+    /// The default search order: `$CLAUDE_CLI_PATH`, `PATH`, the npm global prefix,
+    /// nvm/Volta, common install locations, then the project's `node_modules`
+    ///
+    /// The project's `node_modules/.bin` comes last so that a CLI shipped with the
+    /// code being worked on never replaces the one the user installed.
+    pub fn default_locators() -> Vec<Arc<dyn CliLocator>> {
+        vec![
+            Arc::new(EnvVar::default()),
+            Arc::new(SearchPath),
+            Arc::new(NpmGlobal),
+            Arc::new(NodeVersionManagers),
+            Arc::new(CommonLocations),
+            Arc::new(ProjectLocal),
+        ]
+    }
+
+    /// Find a CLI executable
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClaudeError::CliNotFound`] listing every candidate inspected when
+    /// none qualifies.
+    pub fn locate(&self, cwd: Option<&Path>) -> Result<PathBuf> {
+        let mut inspected: Vec<CliCandidate> = Vec::new();
+        let mut seen = HashSet::new();
+        let mut matches: Vec<(PathBuf, String)> = Vec::new();
+
+        for locator in &self.locators {
+            for path in locator.candidates(cwd) {
+                let key = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+                if !seen.insert(key) {
+                    continue;
+                }
+
+                let exists = is_executable(&path);
+                let mut candidate = CliCandidate {
+                    path: path.clone(),
+                    source: locator.name(),
+                    exists,
+                    version: None,
+                };
+
+                let Some(requirement) = &self.version_requirement else {
+                    inspected.push(candidate);
+                    if exists {
+                        return Ok(path);
+                    }
+                    continue;
+                };
+
+                // Bare names such as `claude` are resolved by the OS when run
+                if exists || path.components().count() == 1 {
+                    candidate.version = query_version(&path);
+                }
+                let version = candidate.version.clone();
+                inspected.push(candidate);
+
+                if let Some(version) = version.filter(|v| requirement.matches(v)) {
+                    if !self.prefer_newest {
+                        return Ok(path);
+                    }
+                    matches.push((path, version));
+                }
+            }
+        }
+
+        if let Some((path, _)) = matches
+            .into_iter()
+            .max_by_key(|(_, version)| parse_version(version))
+        {
+            return Ok(path);
+        }
+
+        let mut message = match &self.version_requirement {
+            Some(requirement) => format!(
+                "No Claude Code CLI matching version requirement '{}' found.",
+                requirement
+            ),
+            None => "Claude Code CLI not found. Please ensure 'claude' is in your PATH or set CLAUDE_CLI_PATH environment variable.".to_string(),
+        };
+        if inspected.is_empty() {
+            message.push_str(" No candidates were found.");
+        } else {
+            message.push_str(" Candidates inspected:");
+            for candidate in &inspected {
+                message.push_str(&format!("\n  {}", candidate));
+            }
+        }
+
+        Err(ClaudeError::CliNotFound(
+            CliNotFoundError::new(message, None).with_candidates(inspected),
+        ))
+    }
+}
+
+/// Run `path --version` and parse the version number
+fn query_version(path: &Path) -> Option<String> {
+    let output = create_sync_hidden_command(path)
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    parse_version_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Whether `path` is a file that can be run; on Unix, one with an execute bit set
+fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.is_file()
+    }
+}
+
+fn executables_in(dir: &Path) -> Vec<PathBuf> {
+    CLI_NAMES.iter().map(|name| dir.join(name)).collect()
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Locator returning fixed paths
+    struct Fixed(Vec<PathBuf>);
+
+    impl CliLocator for Fixed {
+        fn name(&self) -> String {
+            "fixed".to_string()
+        }
+
+        fn candidates(&self, _cwd: Option<&Path>) -> Vec<PathBuf> {
+            self.0.clone()
+        }
+    }
+
+    fn fake_cli(dir: &Path, name: &str, version: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(
+            &path,
+            format!("#!/bin/sh\necho '{} (Claude Code)'\n", version),
+        )
+        .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("claude-locator-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_locate_first_existing_without_requirement() {
+        let dir = temp_dir();
+        let cli = fake_cli(&dir, "claude-a", "2.0.1");
+        let discovery = CliDiscovery::builder()
+            .locators(vec![Arc::new(Fixed(vec![
+                dir.join("missing"),
+                cli.clone(),
+            ]))])
+            .build();
+
+        assert_eq!(discovery.locate(None).unwrap(), cli);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_locate_skips_non_executable_files() {
+        let dir = temp_dir();
+        let plain = dir.join("claude-plain");
+        std::fs::write(&plain, "not a program").unwrap();
+        let cli = fake_cli(&dir, "claude-a", "2.0.1");
+        let discovery = CliDiscovery::builder()
+            .locators(vec![Arc::new(Fixed(vec![plain, cli.clone()]))])
+            .build();
+
+        assert_eq!(discovery.locate(None).unwrap(), cli);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_default_locators_put_project_local_last() {
+        let names: Vec<_> = CliDiscovery::default_locators()
+            .iter()
+            .map(|locator| locator.name())
+            .collect();
+        assert_eq!(names[1], "PATH");
+        assert_eq!(names.last().unwrap(), "project node_modules");
+    }
+
+    #[test]
+    fn test_locate_by_version_requirement() {
+        let dir = temp_dir();
+        let old = fake_cli(&dir, "claude-old", "1.9.0");
+        let mid = fake_cli(&dir, "claude-mid", "2.0.30");
+        let new = fake_cli(&dir, "claude-new", "2.4.0");
+        let locators: Vec<Arc<dyn CliLocator>> = vec![
+            Arc::new(Fixed(vec![old.clone()])),
+            Arc::new(Fixed(vec![mid.clone(), new.clone()])),
+        ];
+
+        let first = CliDiscovery::builder()
+            .locators(locators.clone())
+            .version_requirement(">=2.0.30, <3".parse().unwrap())
+            .build();
+        assert_eq!(first.locate(None).unwrap(), mid);
+
+        let newest = CliDiscovery::builder()
+            .locators(locators)
+            .version_requirement(">=2.0.30, <3".parse().unwrap())
+            .prefer_newest(true)
+            .build();
+        assert_eq!(newest.locate(None).unwrap(), new);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_locate_error_lists_candidates() {
+        let dir = temp_dir();
+        let old = fake_cli(&dir, "claude-old", "1.9.0");
+        let discovery = CliDiscovery::builder()
+            .locators(vec![Arc::new(Fixed(vec![
+                old.clone(),
+                dir.join("missing"),
+            ]))])
+            .version_requirement(">=2".parse().unwrap())
+            .build();
+
+        match discovery.locate(None) {
+            Err(ClaudeError::CliNotFound(error)) => {
+                assert_eq!(error.candidates.len(), 2);
+                assert_eq!(error.candidates[0].path, old);
+                assert_eq!(error.candidates[0].version.as_deref(), Some("1.9.0"));
+                assert!(!error.candidates[1].exists);
+                assert!(error.message.contains("version 1.9.0"), "{}", error.message);
+                assert!(error.message.contains("not found"), "{}", error.message);
+            }
+            other => panic!("expected CliNotFound, got {:?}", other),
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_project_local_walks_ancestors() {
+        let dir = temp_dir();
+        let nested = dir.join("packages/app");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let candidates = ProjectLocal.candidates(Some(&nested));
+        assert_eq!(candidates[0], nested.join("node_modules/.bin/claude"));
+        assert!(candidates.contains(&dir.join("node_modules/.bin/claude")));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
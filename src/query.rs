@@ -6,12 +6,13 @@
 use crate::errors::Result;
 use crate::internal::client::InternalClient;
 use crate::internal::message_parser::MessageParser;
+use crate::internal::transport::Transport;
 use crate::internal::transport::subprocess::QueryPrompt;
-use crate::internal::transport::{SubprocessTransport, Transport};
 use crate::types::config::ClaudeAgentOptions;
 use crate::types::messages::{Message, UserContentBlock};
 use futures::stream::{Stream, StreamExt};
 use std::pin::Pin;
+use std::sync::Arc;
 
 // =============================================================================
 // Internal helper functions (DRY principle)
@@ -22,7 +23,7 @@ use std::pin::Pin;
 /// This helper function extracts the common streaming logic used by both
 /// `query_stream()` and `query_stream_with_content()`.
 fn create_message_stream(
-    transport: SubprocessTransport,
+    transport: Arc<dyn Transport>,
 ) -> Pin<Box<dyn Stream<Item = Result<Message>> + Send>> {
     let stream = async_stream::stream! {
         let mut message_stream = transport.read_messages();
@@ -53,11 +54,11 @@ fn create_message_stream(
 async fn setup_streaming_transport(
     query_prompt: QueryPrompt,
     options: Option<ClaudeAgentOptions>,
-) -> Result<SubprocessTransport> {
+) -> Result<Arc<dyn Transport>> {
     let opts = options.unwrap_or_default();
-    let transport = SubprocessTransport::new(query_prompt, opts)?;
-    transport.connect().await?;
-    Ok(transport)
+    let client = InternalClient::new(query_prompt, opts).await?;
+    client.connect().await?;
+    Ok(client.into_transport())
 }
 
 // =============================================================================
@@ -101,7 +102,7 @@ pub async fn query(
     let query_prompt = QueryPrompt::Text(prompt.into());
     let opts = options.unwrap_or_default();
 
-    let client = InternalClient::new(query_prompt, opts)?;
+    let client = InternalClient::new(query_prompt, opts).await?;
     client.execute().await
 }
 
@@ -202,7 +203,7 @@ pub async fn query_with_content(
     let query_prompt = QueryPrompt::Content(content_blocks);
     let opts = options.unwrap_or_default();
 
-    let client = InternalClient::new(query_prompt, opts)?;
+    let client = InternalClient::new(query_prompt, opts).await?;
     client.execute().await
 }
 
@@ -261,3 +262,69 @@ pub async fn query_stream_with_content(
     let transport = setup_streaming_transport(query_prompt, options).await?;
     Ok(create_message_stream(transport))
 }
+
+/// Query Claude Code over a custom transport.
+///
+/// Like [`query`], but the CLI is reached through `transport` instead of being
+/// spawned locally. The prompt is written as a stream-json user message and input
+/// is closed afterwards, so the transport must start the CLI with
+/// `--input-format stream-json --output-format stream-json --verbose`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use claude_agent_sdk_rs::{ClaudeAgentOptions, QueryPrompt, SubprocessTransport, query_with_transport};
+/// use std::sync::Arc;
+///
+/// #[tokio::main]
+/// async fn main() -> anyhow::Result<()> {
+///     let transport =
+///         SubprocessTransport::new_async(QueryPrompt::Streaming, ClaudeAgentOptions::default()).await?;
+///     let messages = query_with_transport("What is 2 + 2?", Arc::new(transport)).await?;
+///     println!("Received {} messages", messages.len());
+///     Ok(())
+/// }
+/// ```
+pub async fn query_with_transport(
+    prompt: impl Into<String>,
+    transport: Arc<dyn Transport>,
+) -> Result<Vec<Message>> {
+    let query_prompt = QueryPrompt::Text(prompt.into());
+    let client = InternalClient::with_transport(query_prompt, transport);
+    client.execute().await
+}
+
+/// Query Claude Code over a custom transport with streaming responses.
+///
+/// Like [`query_stream`], but the CLI is reached through `transport`.
+/// See [`query_with_transport`] for the requirements on the transport.
+///
+/// # Examples
+///
+/// ```no_run
+/// use claude_agent_sdk_rs::{ClaudeAgentOptions, QueryPrompt, SubprocessTransport, query_stream_with_transport};
+/// use futures::stream::StreamExt;
+/// use std::sync::Arc;
+///
+/// #[tokio::main]
+/// async fn main() -> anyhow::Result<()> {
+///     let transport =
+///         SubprocessTransport::new_async(QueryPrompt::Streaming, ClaudeAgentOptions::default()).await?;
+///     let mut stream = query_stream_with_transport("What is 2 + 2?", Arc::new(transport)).await?;
+///
+///     while let Some(result) = stream.next().await {
+///         println!("{:?}", result?);
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+pub async fn query_stream_with_transport(
+    prompt: impl Into<String>,
+    transport: Arc<dyn Transport>,
+) -> Result<Pin<Box<dyn Stream<Item = Result<Message>> + Send>>> {
+    let query_prompt = QueryPrompt::Text(prompt.into());
+    let client = InternalClient::with_transport(query_prompt, transport);
+    client.connect().await?;
+    Ok(create_message_stream(client.into_transport()))
+}
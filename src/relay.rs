@@ -0,0 +1,517 @@
+//! Relay server bridging a Claude Code CLI to a TCP or Unix socket
+//!
+//! The relay runs next to the CLI (e.g. on a build worker), spawns it through
+//! [`SubprocessTransport`] and forwards its stdio to a single remote client using
+//! [`SocketTransport`](crate::SocketTransport). The relay does not interpret CLI
+//! messages: control requests for hooks, `can_use_tool` and SDK MCP servers travel
+//! to the remote client and are answered there.
+//!
+//! Connections are accepted once the CLI is running, with a welcome frame that
+//! reports the CLI's version so the client can check capabilities as it would
+//! for a local CLI.
+//!
+//! If the client disconnects, CLI output is buffered until it reconnects or
+//! [`RelayConfig::reconnect_grace`] elapses, at which point the CLI is shut down.
+//! Once [`RelayConfig::backlog_limit`] bytes are buffered the relay stops reading
+//! CLI output, so the CLI blocks on its stdout until a client is back.
+
+use futures::StreamExt;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+use typed_builder::TypedBuilder;
+
+use crate::errors::{ClaudeError, Result};
+use crate::internal::transport::line_reader::{BoundedLine, DEFAULT_MAX_BUFFER_SIZE};
+use crate::internal::transport::socket::{
+    RELAY_PROTOCOL_VERSION, RelayReader, RelayWriter, frame, split_boxed, write_line,
+};
+use crate::internal::transport::{QueryPrompt, RelayEndpoint, SubprocessTransport, Transport};
+use crate::types::config::ClaudeAgentOptions;
+
+/// Configuration for [`RelayServer`]
+#[derive(Debug, Clone, TypedBuilder)]
+#[builder(doc)]
+pub struct RelayConfig {
+    /// Shared secret clients must present in the handshake
+    #[builder(default, setter(into, strip_option))]
+    pub auth_token: Option<String>,
+    /// Time allowed for a client to complete the handshake
+    #[builder(default = Duration::from_secs(10))]
+    pub handshake_timeout: Duration,
+    /// How long to keep the CLI running while no client is connected
+    #[builder(default = Duration::from_secs(60))]
+    pub reconnect_grace: Duration,
+    /// Largest client message forwarded to the CLI, in bytes; longer ones are dropped
+    #[builder(default = DEFAULT_MAX_BUFFER_SIZE)]
+    pub max_buffer_size: usize,
+    /// Bytes of CLI output buffered for a disconnected client before the relay
+    /// stops reading from the CLI
+    #[builder(default = 64 * 1024 * 1024)]
+    pub backlog_limit: usize,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// Where the relayed CLI comes from
+enum Backend {
+    /// Spawn the CLI on the first authenticated connection
+    Spawn(Box<ClaudeAgentOptions>),
+    /// Relay an already constructed transport
+    Transport(Arc<dyn Transport>),
+}
+
+/// Relay server for a single CLI session
+///
+/// # Example
+///
+/// ```no_run
+/// use claude_agent_sdk_rs::ClaudeAgentOptions;
+/// use claude_agent_sdk_rs::relay::{RelayConfig, RelayServer};
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = RelayConfig::builder().auth_token("secret").build();
+/// let server = RelayServer::new(ClaudeAgentOptions::default(), config);
+/// server.serve(&"tcp://0.0.0.0:7878".parse()?).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct RelayServer {
+    backend: Backend,
+    config: RelayConfig,
+}
+
+/// CLI output lines the pump may hold while the session loop isn't taking them
+const PUMP_CAPACITY: usize = 64;
+
+/// Event observed by the session loop
+enum SessionEvent {
+    Connection(Option<(RelayReader, RelayWriter)>),
+    ClientLine(std::io::Result<BoundedLine>),
+    CliOutput(Option<String>),
+    GraceExpired,
+}
+
+impl RelayServer {
+    /// Create a relay that spawns the CLI with the given options
+    ///
+    /// The CLI is started in streaming mode when the first client authenticates.
+    pub fn new(options: ClaudeAgentOptions, config: RelayConfig) -> Self {
+        Self {
+            backend: Backend::Spawn(Box::new(options)),
+            config,
+        }
+    }
+
+    /// Create a relay for an arbitrary transport
+    pub fn with_transport(transport: Arc<dyn Transport>, config: RelayConfig) -> Self {
+        Self {
+            backend: Backend::Transport(transport),
+            config,
+        }
+    }
+
+    /// Listen on the endpoint and relay one CLI session
+    ///
+    /// Returns once the CLI output ends, the client sends a close request, or no
+    /// client reconnects within the grace period.
+    pub async fn serve(self, endpoint: &RelayEndpoint) -> Result<()> {
+        let (conn_tx, conn_rx) = flume::unbounded();
+        let acceptor = self.spawn_acceptor(endpoint, conn_tx).await?;
+        info!("Relay listening on {}", endpoint);
+
+        let result = self.run_session(conn_rx).await;
+
+        acceptor.abort();
+        #[cfg(unix)]
+        if let RelayEndpoint::Unix(path) = endpoint {
+            let _ = std::fs::remove_file(path);
+        }
+        result
+    }
+
+    /// Bind the listener and spawn the accept loop
+    ///
+    /// Every connection is authenticated in its own task; authenticated
+    /// connections are sent to the session loop, which welcomes them.
+    async fn spawn_acceptor(
+        &self,
+        endpoint: &RelayEndpoint,
+        conn_tx: flume::Sender<(RelayReader, RelayWriter)>,
+    ) -> Result<JoinHandle<()>> {
+        let config = self.config.clone();
+        let bind_error = |e: std::io::Error| {
+            ClaudeError::Transport(format!("Failed to bind relay on {}: {}", endpoint, e))
+        };
+
+        match endpoint {
+            RelayEndpoint::Tcp(addr) => {
+                let listener = tokio::net::TcpListener::bind(addr)
+                    .await
+                    .map_err(bind_error)?;
+                Ok(tokio::spawn(async move {
+                    while let Ok((stream, peer)) = listener.accept().await {
+                        debug!("Relay connection from {}", peer);
+                        let _ = stream.set_nodelay(true);
+                        let (reader, writer) = stream.into_split();
+                        let (reader, writer) = split_boxed(reader, writer);
+                        tokio::spawn(authenticate(
+                            reader,
+                            writer,
+                            config.clone(),
+                            conn_tx.clone(),
+                        ));
+                    }
+                }))
+            }
+            #[cfg(unix)]
+            RelayEndpoint::Unix(path) => {
+                // Replace a stale socket left behind by a previous relay
+                if path.exists() {
+                    std::fs::remove_file(path).map_err(bind_error)?;
+                }
+                let listener = tokio::net::UnixListener::bind(path).map_err(bind_error)?;
+                Ok(tokio::spawn(async move {
+                    while let Ok((stream, _)) = listener.accept().await {
+                        let (reader, writer) = stream.into_split();
+                        let (reader, writer) = split_boxed(reader, writer);
+                        tokio::spawn(authenticate(
+                            reader,
+                            writer,
+                            config.clone(),
+                            conn_tx.clone(),
+                        ));
+                    }
+                }))
+            }
+        }
+    }
+
+    /// Build and connect the relayed transport
+    async fn start_transport(&self) -> Result<Arc<dyn Transport>> {
+        let transport: Arc<dyn Transport> = match &self.backend {
+            Backend::Spawn(options) => Arc::new(
+                SubprocessTransport::new_async(QueryPrompt::Streaming, (**options).clone()).await?,
+            ),
+            Backend::Transport(transport) => Arc::clone(transport),
+        };
+        transport.connect().await?;
+        Ok(transport)
+    }
+
+    async fn run_session(
+        &self,
+        conn_rx: flume::Receiver<(RelayReader, RelayWriter)>,
+    ) -> Result<()> {
+        let first = conn_rx
+            .recv_async()
+            .await
+            .map_err(|_| ClaudeError::Transport("Relay listener stopped".to_string()))?;
+
+        let transport = match self.start_transport().await {
+            Ok(transport) => transport,
+            Err(e) => {
+                let (_, mut writer) = first;
+                let _ = send_error(&mut writer, &format!("Failed to start CLI: {}", e)).await;
+                return Err(e);
+            }
+        };
+        let cli_version = transport.cli_version();
+
+        // Pump CLI output into a channel so the session loop can select on it; the
+        // channel is bounded so a full backlog holds the pump, and the CLI, back
+        let (out_tx, out_rx) = flume::bounded::<String>(PUMP_CAPACITY);
+        let pump_transport = Arc::clone(&transport);
+        let pump = tokio::spawn(async move {
+            let mut stream = pump_transport.read_messages();
+            while let Some(result) = stream.next().await {
+                match result {
+                    Ok(json) => {
+                        if out_tx.send_async(json.to_string()).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => warn!("Relay dropped unreadable CLI output: {}", e),
+                }
+            }
+        });
+
+        let mut conn = welcome(first, cli_version.as_deref()).await;
+        let mut backlog = Backlog::default();
+        let mut disconnected_at = conn.is_none().then(Instant::now);
+        let mut cli_done = false;
+
+        loop {
+            let grace_deadline = disconnected_at.map(|at| at + self.config.reconnect_grace);
+            let event = tokio::select! {
+                c = conn_rx.recv_async() => SessionEvent::Connection(c.ok()),
+                line = next_client_line(&mut conn) => SessionEvent::ClientLine(line),
+                out = out_rx.recv_async(), if !cli_done && backlog.bytes < self.config.backlog_limit => {
+                    SessionEvent::CliOutput(out.ok())
+                }
+                _ = sleep_until(grace_deadline) => SessionEvent::GraceExpired,
+            };
+
+            match event {
+                SessionEvent::Connection(Some(new_conn)) => {
+                    let Some(new_conn) = welcome(new_conn, cli_version.as_deref()).await else {
+                        continue;
+                    };
+                    if conn.is_some() {
+                        info!("Relay client superseded by a new connection");
+                    }
+                    conn = Some(new_conn);
+                    disconnected_at = None;
+                    flush_backlog(&mut conn, &mut backlog).await;
+                    if cli_done && send_exit(&mut conn).await {
+                        break;
+                    }
+                }
+                SessionEvent::Connection(None) => {
+                    warn!("Relay listener stopped");
+                    break;
+                }
+                SessionEvent::ClientLine(Ok(BoundedLine::Line(line))) => {
+                    let frame_type = serde_json::from_str::<serde_json::Value>(&line)
+                        .ok()
+                        .and_then(|v| v.get("type").and_then(|t| t.as_str()).map(String::from));
+                    let result = match frame_type.as_deref() {
+                        Some(frame::END_INPUT) => transport.end_input().await,
+                        Some(frame::CLOSE) => break,
+                        _ => transport.write(&line).await,
+                    };
+                    if let Err(e) = result {
+                        warn!("Relay failed to forward client input to CLI: {}", e);
+                    }
+                }
+                SessionEvent::ClientLine(Ok(BoundedLine::Oversized(size))) => {
+                    warn!(
+                        "Relay dropped a {} byte client message over the {} byte limit",
+                        size, self.config.max_buffer_size
+                    );
+                }
+                SessionEvent::ClientLine(Ok(BoundedLine::Eof) | Err(_)) => {
+                    info!("Relay client disconnected");
+                    conn = None;
+                    disconnected_at = Some(Instant::now());
+                }
+                SessionEvent::CliOutput(Some(line)) => {
+                    backlog.push(line);
+                    flush_backlog(&mut conn, &mut backlog).await;
+                    if conn.is_none() && disconnected_at.is_none() {
+                        disconnected_at = Some(Instant::now());
+                    }
+                }
+                SessionEvent::CliOutput(None) => {
+                    cli_done = true;
+                    if send_exit(&mut conn).await {
+                        break;
+                    }
+                }
+                SessionEvent::GraceExpired => {
+                    warn!("No relay client reconnected within the grace period");
+                    break;
+                }
+            }
+        }
+
+        pump.abort();
+        if let Some((_, mut writer)) = conn {
+            let _ = tokio::io::AsyncWriteExt::shutdown(&mut writer).await;
+        }
+        transport.close().await
+    }
+}
+
+/// Run the relay handshake on a new connection
+async fn authenticate(
+    mut reader: RelayReader,
+    mut writer: RelayWriter,
+    config: RelayConfig,
+    conn_tx: flume::Sender<(RelayReader, RelayWriter)>,
+) {
+    let hello = match tokio::time::timeout(config.handshake_timeout, reader.next_line()).await {
+        Ok(Ok(BoundedLine::Line(line))) => line,
+        Ok(Ok(BoundedLine::Oversized(_))) => {
+            let _ = send_error(&mut writer, "handshake too large").await;
+            return;
+        }
+        Ok(_) => return,
+        Err(_) => {
+            let _ = send_error(&mut writer, "handshake timed out").await;
+            return;
+        }
+    };
+
+    if let Err(reason) = check_hello(&hello, config.auth_token.as_deref()) {
+        warn!("Relay rejected connection: {}", reason);
+        let _ = send_error(&mut writer, reason).await;
+        return;
+    }
+
+    reader.set_limit(config.max_buffer_size);
+    let _ = conn_tx.send((reader, writer));
+}
+
+/// Accept an authenticated connection, reporting the relayed CLI's version
+///
+/// Returns None if the client is already gone.
+async fn welcome(
+    conn: (RelayReader, RelayWriter),
+    cli_version: Option<&str>,
+) -> Option<(RelayReader, RelayWriter)> {
+    let (reader, mut writer) = conn;
+    let welcome = serde_json::json!({
+        "type": frame::WELCOME,
+        "version": RELAY_PROTOCOL_VERSION,
+        "cli_version": cli_version,
+    });
+    match write_line(&mut writer, &welcome.to_string()).await {
+        Ok(()) => Some((reader, writer)),
+        Err(e) => {
+            warn!("Relay failed to welcome client: {}", e);
+            None
+        }
+    }
+}
+
+/// Validate a client greeting against the expected token
+fn check_hello(line: &str, expected_token: Option<&str>) -> std::result::Result<(), &'static str> {
+    let hello: serde_json::Value = serde_json::from_str(line).map_err(|_| "malformed handshake")?;
+    if hello.get("type").and_then(|v| v.as_str()) != Some(frame::HELLO) {
+        return Err("expected relay_hello");
+    }
+    if hello.get("version").and_then(|v| v.as_u64()) != Some(RELAY_PROTOCOL_VERSION) {
+        return Err("unsupported relay protocol version");
+    }
+    if let Some(expected) = expected_token {
+        let presented = hello.get("token").and_then(|v| v.as_str()).unwrap_or("");
+        if !constant_time_eq(presented.as_bytes(), expected.as_bytes()) {
+            return Err("invalid auth token");
+        }
+    }
+    Ok(())
+}
+
+/// Compare two byte strings without short-circuiting on the first mismatch
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn send_error(writer: &mut RelayWriter, message: &str) -> std::io::Result<()> {
+    let error = serde_json::json!({ "type": frame::ERROR, "message": message });
+    write_line(writer, &error.to_string()).await
+}
+
+/// Read the next line from the client, or wait forever if none is connected
+///
+/// Cancel-safe: a line cut short by another event is resumed on the next call.
+async fn next_client_line(
+    conn: &mut Option<(RelayReader, RelayWriter)>,
+) -> std::io::Result<BoundedLine> {
+    match conn {
+        Some((reader, _)) => reader.next_line().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// CLI output not yet delivered to the client
+#[derive(Default)]
+struct Backlog {
+    lines: VecDeque<String>,
+    bytes: usize,
+}
+
+impl Backlog {
+    fn push(&mut self, line: String) {
+        self.bytes += line.len();
+        self.lines.push_back(line);
+    }
+}
+
+/// Write buffered CLI output to the client, dropping the connection on failure
+async fn flush_backlog(conn: &mut Option<(RelayReader, RelayWriter)>, backlog: &mut Backlog) {
+    let Some((_, writer)) = conn else {
+        return;
+    };
+    while let Some(line) = backlog.lines.front() {
+        if let Err(e) = write_line(writer, line).await {
+            warn!("Relay failed to write to client, buffering output: {}", e);
+            *conn = None;
+            return;
+        }
+        backlog.bytes -= line.len();
+        backlog.lines.pop_front();
+    }
+}
+
+/// Notify the client that the CLI has exited; returns whether it was delivered
+async fn send_exit(conn: &mut Option<(RelayReader, RelayWriter)>) -> bool {
+    let Some((_, writer)) = conn else {
+        return false;
+    };
+    let exit = serde_json::json!({ "type": frame::EXIT });
+    write_line(writer, &exit.to_string()).await.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hello(token: Option<&str>) -> String {
+        serde_json::json!({ "type": frame::HELLO, "version": RELAY_PROTOCOL_VERSION, "token": token })
+            .to_string()
+    }
+
+    #[test]
+    fn test_check_hello_accepts_matching_token() {
+        assert!(check_hello(&hello(Some("secret")), Some("secret")).is_ok());
+        assert!(check_hello(&hello(None), None).is_ok());
+    }
+
+    #[test]
+    fn test_check_hello_rejects_bad_token() {
+        assert_eq!(
+            check_hello(&hello(Some("wrong")), Some("secret")),
+            Err("invalid auth token")
+        );
+        assert_eq!(
+            check_hello(&hello(None), Some("secret")),
+            Err("invalid auth token")
+        );
+    }
+
+    #[test]
+    fn test_check_hello_rejects_version_mismatch() {
+        let line = serde_json::json!({ "type": frame::HELLO, "version": 99 }).to_string();
+        assert_eq!(
+            check_hello(&line, None),
+            Err("unsupported relay protocol version")
+        );
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"abcd"));
+    }
+}
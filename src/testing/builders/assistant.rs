@@ -4,6 +4,7 @@ use crate::types::messages::{
     AssistantMessage, AssistantMessageInner, ContentBlock, Message, TextBlock, ThinkingBlock,
     ToolUseBlock,
 };
+use crate::types::usage::Usage;
 
 /// Builder for AssistantMessage
 pub struct AssistantMessageBuilder {
@@ -11,6 +12,7 @@ pub struct AssistantMessageBuilder {
     model: Option<String>,
     stop_reason: Option<String>,
     session_id: Option<String>,
+    usage: Option<Usage>,
 }
 
 impl AssistantMessageBuilder {
@@ -21,6 +23,7 @@ impl AssistantMessageBuilder {
             model: None,
             stop_reason: None,
             session_id: None,
+            usage: None,
         }
     }
 
@@ -83,6 +86,12 @@ impl AssistantMessageBuilder {
         self
     }
 
+    /// Set the token usage
+    pub fn usage(mut self, usage: Usage) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
     /// Build the message
     pub fn build(self) -> Message {
         Message::Assistant(AssistantMessage {
@@ -91,7 +100,7 @@ impl AssistantMessageBuilder {
                 model: self.model,
                 id: Some(format!("msg_{}", uuid::Uuid::new_v4())),
                 stop_reason: self.stop_reason,
-                usage: None,
+                usage: self.usage,
                 error: None,
             },
             parent_tool_use_id: None,
@@ -1,6 +1,7 @@
 //! Builder for ResultMessage
 
 use crate::types::messages::{Message, ResultMessage};
+use crate::types::usage::Usage;
 
 /// Builder for ResultMessage (query completion)
 pub struct ResultMessageBuilder {
@@ -12,6 +13,7 @@ pub struct ResultMessageBuilder {
     is_error: bool,
     session_id: String,
     result: Option<String>,
+    usage: Option<Usage>,
 }
 
 impl ResultMessageBuilder {
@@ -26,6 +28,7 @@ impl ResultMessageBuilder {
             is_error: false,
             session_id: format!("test-session-{}", uuid::Uuid::new_v4()),
             result: None,
+            usage: None,
         }
     }
 
@@ -78,6 +81,12 @@ impl ResultMessageBuilder {
         self
     }
 
+    /// Set the token usage
+    pub fn usage(mut self, usage: Usage) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
     /// Build the message
     pub fn build(self) -> Message {
         Message::Result(ResultMessage {
@@ -88,7 +97,8 @@ impl ResultMessageBuilder {
             num_turns: self.turns,
             is_error: self.is_error,
             session_id: self.session_id,
-            usage: None,
+            usage: self.usage,
+            model_usage: Default::default(),
             result: self.result,
             structured_output: None,
         })
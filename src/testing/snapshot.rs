@@ -14,6 +14,7 @@ use super::scenario::Scenario;
 use super::transport::{MessageTiming, ScheduledMessage};
 use crate::errors::Result;
 use crate::internal::transport::Transport;
+use crate::types::env::Redactor;
 
 /// Message direction in a recorded session
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
@@ -57,6 +58,7 @@ pub struct SessionSnapshot {
 pub struct SnapshotRecorder {
     messages: Arc<Mutex<Vec<RecordedMessage>>>,
     start_time: std::time::Instant,
+    redactor: Redactor,
 }
 
 impl SnapshotRecorder {
@@ -65,11 +67,22 @@ impl SnapshotRecorder {
         Self {
             messages: Arc::new(Mutex::new(Vec::new())),
             start_time: std::time::Instant::now(),
+            redactor: Redactor::default(),
         }
     }
 
+    /// Mask secrets in every recorded message
+    ///
+    /// Use [`EnvPolicy::redactor`](crate::EnvPolicy::redactor) to mask the same
+    /// values the transport masks.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
     /// Record a received message
-    pub async fn record_received(&self, msg: serde_json::Value) {
+    pub async fn record_received(&self, mut msg: serde_json::Value) {
+        self.redactor.redact_json(&mut msg);
         self.messages.lock().await.push(RecordedMessage {
             offset_ms: self.start_time.elapsed().as_millis() as u64,
             direction: MessageDirection::Received,
@@ -78,7 +91,8 @@ impl SnapshotRecorder {
     }
 
     /// Record a sent message
-    pub async fn record_sent(&self, msg: serde_json::Value) {
+    pub async fn record_sent(&self, mut msg: serde_json::Value) {
+        self.redactor.redact_json(&mut msg);
         self.messages.lock().await.push(RecordedMessage {
             offset_ms: self.start_time.elapsed().as_millis() as u64,
             direction: MessageDirection::Sent,
@@ -296,6 +310,18 @@ mod tests {
         assert_eq!(messages[1].direction, MessageDirection::Received);
     }
 
+    #[tokio::test]
+    async fn test_snapshot_recorder_redacts_secrets() {
+        let recorder = SnapshotRecorder::new().with_redactor(Redactor::new(["sk-secret"]));
+
+        recorder
+            .record_sent(serde_json::json!({"type": "user", "text": "key sk-secret"}))
+            .await;
+
+        let messages = recorder.messages().await;
+        assert_eq!(messages[0].content["text"], "key [REDACTED]");
+    }
+
     #[test]
     fn test_snapshot_player_from_json() {
         let json = r#"{
@@ -0,0 +1,218 @@
+//! What the connected Claude Code CLI can do
+
+use tracing::warn;
+
+use crate::errors::{Result, UnsupportedCapabilityError};
+use crate::types::config::ClaudeAgentOptions;
+use crate::types::server_info::ServerInfo;
+use crate::version::{parse_version, version_at_least};
+
+/// A CLI feature that not every supported CLI version has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// File checkpointing and the `rewind_files` control request
+    FileCheckpointing,
+    /// Loading plugins with `--plugin-dir`
+    Plugins,
+    /// Structured output with `--json-schema`
+    OutputFormat,
+}
+
+impl Capability {
+    /// Every capability the SDK checks for
+    pub const ALL: [Capability; 3] = [
+        Capability::FileCheckpointing,
+        Capability::Plugins,
+        Capability::OutputFormat,
+    ];
+
+    /// First CLI version that supports this capability
+    pub fn min_cli_version(self) -> &'static str {
+        // Versions are the CLI changelog entries that introduced each feature
+        match self {
+            // 2.0.12: "Plugin System Released"
+            Capability::Plugins => "2.0.12",
+            // 2.0.45: structured output with `--json-schema`
+            Capability::OutputFormat => "2.0.45",
+            // 2.0.64: SDK file checkpointing and the `rewind_files` control request
+            Capability::FileCheckpointing => "2.0.64",
+        }
+    }
+}
+
+impl std::fmt::Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Capability::FileCheckpointing => "file checkpointing (rewind_files)",
+            Capability::Plugins => "plugins",
+            Capability::OutputFormat => "output_format",
+        })
+    }
+}
+
+/// Capabilities of the connected CLI, from its version and `initialize` response
+///
+/// Returned by [`ClaudeClient::capabilities`](crate::ClaudeClient::capabilities).
+/// The version comes from the transport, or from the `initialize` response if
+/// the transport doesn't know it. A `plugin` slash command in the response also
+/// shows plugin support.
+///
+/// When nothing settles a capability (the version check was skipped, or a custom
+/// transport doesn't report a version), it is assumed to be present and the CLI
+/// is left to reject what it doesn't understand; `connect()` logs a warning for
+/// each such capability the options use.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    cli_version: Option<String>,
+    lists_plugin_command: bool,
+}
+
+impl Capabilities {
+    /// Work out capabilities from the CLI version and, once connected, its `initialize` response
+    ///
+    /// A version that can't be parsed counts as unknown.
+    pub(crate) fn detect(cli_version: Option<&str>, server_info: Option<&ServerInfo>) -> Self {
+        let reported = server_info.and_then(ServerInfo::cli_version);
+        Self {
+            cli_version: [cli_version, reported]
+                .into_iter()
+                .flatten()
+                .find(|version| parse_version(version).is_some())
+                .map(str::to_string),
+            lists_plugin_command: server_info.is_some_and(|info| info.supports_command("plugin")),
+        }
+    }
+
+    /// Version of the connected CLI, if known
+    pub fn cli_version(&self) -> Option<&str> {
+        self.cli_version.as_deref()
+    }
+
+    /// Whether the CLI supports `capability`
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.known_support(capability).unwrap_or(true)
+    }
+
+    /// Whether the CLI supports `capability`, or None if nothing shows either way
+    fn known_support(&self, capability: Capability) -> Option<bool> {
+        if capability == Capability::Plugins && self.lists_plugin_command {
+            return Some(true);
+        }
+        let version = self.cli_version.as_deref()?;
+        Some(version_at_least(version, capability.min_cli_version()))
+    }
+
+    /// Capabilities the CLI is missing
+    pub fn unsupported(&self) -> Vec<Capability> {
+        Capability::ALL
+            .into_iter()
+            .filter(|capability| !self.supports(*capability))
+            .collect()
+    }
+
+    /// Fail with [`UnsupportedCapabilityError`] unless the CLI supports `capability`
+    pub(crate) fn require(&self, capability: Capability) -> Result<()> {
+        if self.supports(capability) {
+            return Ok(());
+        }
+        Err(UnsupportedCapabilityError::new(
+            capability,
+            self.cli_version.clone().unwrap_or_default(),
+        )
+        .into())
+    }
+
+    /// Fail if `options` turn on a feature the CLI doesn't support
+    ///
+    /// Features whose support is unknown are let through with a warning.
+    pub(crate) fn check_options(&self, options: &ClaudeAgentOptions) -> Result<()> {
+        let needed = [
+            (
+                options.enable_file_checkpointing,
+                Capability::FileCheckpointing,
+            ),
+            (!options.plugins.is_empty(), Capability::Plugins),
+            (options.output_format.is_some(), Capability::OutputFormat),
+        ];
+        for (used, capability) in needed {
+            if !used {
+                continue;
+            }
+            if self.known_support(capability).is_none() {
+                warn!(
+                    "Can't tell whether the CLI supports {} (needs {} or newer): its version is unknown",
+                    capability,
+                    capability.min_cli_version()
+                );
+            }
+            self.require(capability)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ClaudeError;
+
+    #[test]
+    fn test_capabilities_follow_cli_version() {
+        let old = Capabilities::detect(Some("2.0.20"), None);
+        assert!(old.supports(Capability::Plugins));
+        assert!(!old.supports(Capability::OutputFormat));
+        assert_eq!(
+            old.unsupported(),
+            vec![Capability::FileCheckpointing, Capability::OutputFormat]
+        );
+
+        let unknown = Capabilities::detect(None, None);
+        assert!(unknown.unsupported().is_empty());
+        assert_eq!(Capabilities::detect(Some("dev"), None), unknown);
+    }
+
+    #[test]
+    fn test_capabilities_use_initialize_response() {
+        let info = |response: serde_json::Value| ServerInfo::from_response(response);
+
+        // The response's version stands in for one the transport doesn't know
+        let reported =
+            Capabilities::detect(None, Some(&info(serde_json::json!({"version": "2.0.20"}))));
+        assert_eq!(reported.cli_version(), Some("2.0.20"));
+        assert!(!reported.supports(Capability::OutputFormat));
+        let transport_first = Capabilities::detect(
+            Some("2.1.0"),
+            Some(&info(serde_json::json!({"version": "2.0.20"}))),
+        );
+        assert_eq!(transport_first.cli_version(), Some("2.1.0"));
+
+        // A plugin command shows plugin support even where the version looks too old
+        let plugins = Capabilities::detect(
+            Some("2.0.10"),
+            Some(&info(serde_json::json!({"commands": [{"name": "plugin"}]}))),
+        );
+        assert!(plugins.supports(Capability::Plugins));
+        assert!(!plugins.supports(Capability::OutputFormat));
+    }
+
+    #[test]
+    fn test_check_options_names_required_version() {
+        let options = ClaudeAgentOptions::builder()
+            .output_format(serde_json::json!({"type": "json_schema", "schema": {}}))
+            .build();
+        let capabilities = Capabilities::detect(Some("2.0.30"), None);
+
+        let err = capabilities.check_options(&options).unwrap_err();
+        assert!(matches!(
+            &err,
+            ClaudeError::UnsupportedCapability(e)
+                if e.capability == Capability::OutputFormat && e.required_version == "2.0.45"
+        ));
+        assert!(err.to_string().contains("2.0.45"));
+        assert!(
+            Capabilities::detect(Some("2.1.0"), None)
+                .check_options(&options)
+                .is_ok()
+        );
+    }
+}
@@ -4,13 +4,18 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use typed_builder::TypedBuilder;
 
 use super::efficiency::EfficiencyConfig;
+use super::env::EnvPolicy;
 use super::hooks::{HookEvent, HookMatcher};
+use super::launcher::ProcessLauncher;
 use super::mcp::McpServers;
 use super::permissions::CanUseToolCallback;
 use super::plugin::SdkPluginConfig;
+use super::tap::ProtocolTap;
+use crate::locator::CliDiscovery;
 
 /// Main configuration options for Claude Agent
 #[derive(Clone, TypedBuilder)]
@@ -68,6 +73,12 @@ pub struct ClaudeAgentOptions {
     /// Path to Claude CLI
     #[builder(default, setter(into, strip_option))]
     pub cli_path: Option<PathBuf>,
+    /// How to find the CLI when `cli_path` is not set, and which versions to accept
+    ///
+    /// Defaults to [`CliDiscovery::default`]. A version requirement here also applies
+    /// to an explicit `cli_path`.
+    #[builder(default, setter(strip_option))]
+    pub cli_discovery: Option<CliDiscovery>,
     /// Settings file path
     #[builder(default, setter(into, strip_option))]
     pub settings: Option<String>,
@@ -77,12 +88,27 @@ pub struct ClaudeAgentOptions {
     /// Environment variables
     #[builder(default)]
     pub env: HashMap<String, String>,
+    /// Which of the SDK process's environment variables the CLI inherits, and which
+    /// values to redact
+    ///
+    /// When `None`, the CLI inherits the whole environment and nothing is redacted.
+    #[builder(default, setter(strip_option))]
+    pub env_policy: Option<EnvPolicy>,
     /// Extra CLI arguments
     #[builder(default)]
     pub extra_args: HashMap<String, Option<String>>,
-    /// Maximum buffer size for subprocess output
+    /// Maximum size of a single message from the CLI, in bytes (default 10MB)
+    ///
+    /// Larger messages are rejected without being fully buffered, or written to
+    /// [`spill_dir`](Self::spill_dir) if set.
     #[builder(default, setter(strip_option))]
     pub max_buffer_size: Option<usize>,
+    /// Directory for messages that exceed `max_buffer_size`
+    ///
+    /// Oversized messages are delivered as [`Message::Spilled`](crate::Message::Spilled)
+    /// instead of failing. Control protocol messages are never spilled.
+    #[builder(default, setter(into, strip_option))]
+    pub spill_dir: Option<PathBuf>,
     /// Callback for stderr output
     #[builder(default, setter(strip_option))]
     pub stderr_callback: Option<Arc<dyn Fn(String) + Send + Sync>>,
@@ -92,6 +118,13 @@ pub struct ClaudeAgentOptions {
     /// Hook callbacks
     #[builder(default, setter(strip_option))]
     pub hooks: Option<HashMap<HookEvent, Vec<HookMatcher>>>,
+    /// Allow hooks to be added and removed after connect
+    ///
+    /// Enables [`ClaudeClient::add_hook`](crate::ClaudeClient::add_hook). The CLI
+    /// then calls back into the SDK on every hook event, even while no runtime
+    /// hook is registered.
+    #[builder(default = false)]
+    pub runtime_hooks: bool,
     /// User identifier
     #[builder(default, setter(into, strip_option))]
     pub user: Option<String>,
@@ -146,11 +179,84 @@ pub struct ClaudeAgentOptions {
     /// Filesystem sandbox configuration applied via Landlock (Linux only).
     ///
     /// When set, the spawned Claude CLI subprocess is restricted at the OS level
-    /// so it can only write to the specified directories. Reads are unrestricted.
-    /// On non-Linux platforms this option is accepted but ignored.
+    /// so it can only write to the specified directories, and optionally only read
+    /// from and connect to what the configuration allows. On non-Linux platforms
+    /// this option is accepted but ignored.
     #[builder(default, setter(strip_option))]
     pub landlock_sandbox: Option<LandlockSandboxConfig>,
 
+    /// Resource limits (rlimits, niceness, I/O priority) for the CLI process.
+    ///
+    /// Applied before the CLI starts, together with the Landlock sandbox.
+    /// On non-Linux platforms this option is accepted but ignored.
+    #[builder(default, setter(strip_option))]
+    pub resource_limits: Option<ResourceLimits>,
+
+    /// Seccomp filter blocking syscalls the CLI should never need.
+    ///
+    /// Installed before the CLI starts, after the Landlock sandbox.
+    /// On non-Linux platforms this option is accepted but ignored.
+    #[builder(default, setter(strip_option))]
+    pub seccomp_profile: Option<SeccompProfile>,
+
+    /// Run the CLI in its own user and network namespace with only loopback.
+    ///
+    /// Outbound traffic goes through a proxy run by the SDK, which only connects to
+    /// the allowed destinations. Unlike [`sandbox`](Self::sandbox), which configures
+    /// the CLI's own bash sandbox, this isolates the CLI process itself.
+    /// Connecting fails on platforms other than Linux.
+    #[builder(default, setter(strip_option))]
+    pub network_isolation: Option<NetworkIsolation>,
+
+    /// Wrapper program to launch the CLI through (e.g. `bwrap`, `nsjail`).
+    ///
+    /// When set, the CLI is executed by the wrapper instead of directly, and host
+    /// paths (`cwd`, `add_dirs`, plugin paths) are translated with the launcher's
+    /// path mappings. See [`ProcessLauncher`].
+    #[builder(default, setter(strip_option))]
+    pub launcher: Option<ProcessLauncher>,
+
+    /// Restart policy for supervised mode in [`ClaudeClient`](crate::ClaudeClient).
+    ///
+    /// When set, a CLI process that exits unexpectedly is respawned with `resume` set to
+    /// the last seen session ID and re-initialized with the same hooks and MCP servers,
+    /// including those added at runtime.
+    /// Each restart emits a [`Message::Reconnect`](crate::Message::Reconnect) event.
+    /// Only applies to the default subprocess transport.
+    #[builder(default, setter(strip_option))]
+    pub restart_policy: Option<RestartPolicy>,
+
+    /// How the CLI subprocess is stopped on disconnect.
+    ///
+    /// The CLI runs in its own process group (Unix), so tool subprocesses and stdio
+    /// MCP servers it started are stopped along with it.
+    #[builder(default)]
+    pub shutdown_policy: ShutdownPolicy,
+
+    /// How long to wait for the CLI to answer a control request (default 60s).
+    ///
+    /// Applies to `initialize`, `interrupt`, `set_model` and the other requests the
+    /// SDK sends; the `_with_timeout` methods on [`ClaudeClient`](crate::ClaudeClient)
+    /// override it per call.
+    #[builder(default = Duration::from_secs(60))]
+    pub control_request_timeout: Duration,
+
+    /// Capacity of the message queue between the CLI reader and `ClaudeClient`'s
+    /// message streams. Unbounded when `None`.
+    #[builder(default, setter(strip_option))]
+    pub message_queue_capacity: Option<usize>,
+
+    /// What happens when the message queue is full
+    #[builder(default)]
+    pub overflow_policy: OverflowPolicy,
+
+    /// Record every frame exchanged with the CLI, for debugging production incidents.
+    ///
+    /// Covers user messages, control requests and responses in both directions, and
+    /// stream output, including after supervised restarts. See [`ProtocolTap`].
+    #[builder(default, setter(strip_option))]
+    pub protocol_tap: Option<ProtocolTap>,
+
     /// Efficiency configuration for built-in efficiency hooks.
     ///
     /// When configured, the SDK automatically injects hooks to:
@@ -490,22 +596,343 @@ pub struct SandboxSettings {
     pub enable_weaker_nested_sandbox: Option<bool>,
 }
 
-/// Filesystem sandbox configuration applied via Landlock (Linux only).
+/// Filesystem and network sandbox applied via Landlock (Linux only).
 ///
 /// When the subprocess is spawned, a Landlock ruleset restricts filesystem
-/// writes to only the listed directories (plus `/tmp` and `~/.claude`).
-/// Reads and execution are allowed everywhere.
-/// If `writable_roots` is empty, no sandbox is applied.
-#[derive(Debug, Clone, Default)]
+/// writes to `writable_roots` and `extra_writable_paths`, and reads to
+/// `readable_roots` minus `denied_paths`. TCP bind and connect can be limited
+/// to specific ports on kernels with Landlock ABI v4 (Linux 6.7+). A config that
+/// restricts nothing (see [`is_empty`](Self::is_empty)) applies no sandbox.
+///
+/// Landlock can only grant access, so a denied path is carved out by granting
+/// access to its siblings, and to those of each parent up to the root, as they
+/// exist when the CLI is spawned. Anything created later directly inside one of
+/// those parents (e.g. a new file in `~` when `~/.ssh` is denied) is inaccessible
+/// to the CLI until it is restarted.
+#[derive(Debug, Clone, TypedBuilder)]
+#[builder(doc)]
+#[non_exhaustive]
 pub struct LandlockSandboxConfig {
     /// Directories the subprocess is allowed to write to.
+    ///
+    /// When empty, writes are allowed everywhere except beneath `denied_paths`.
+    #[builder(default)]
     pub writable_roots: Vec<PathBuf>,
+    /// Further writable paths for the CLI's own state; paths that don't exist are skipped.
+    ///
+    /// Defaults to `/tmp` and `~/.claude`. Only used with `writable_roots`.
+    #[builder(default = default_extra_writable_paths())]
+    pub extra_writable_paths: Vec<PathBuf>,
+    /// Paths the subprocess may read and execute from; `None` allows the whole filesystem.
+    ///
+    /// Include the system directories the CLI needs (e.g. `/usr`, `/lib`, `/etc`)
+    /// and the Node.js installation. Writable paths are always readable.
+    #[builder(default, setter(strip_option))]
+    pub readable_roots: Option<Vec<PathBuf>>,
+    /// Paths that are not accessible even beneath a readable or writable root (e.g. `~/.ssh`).
+    #[builder(default)]
+    pub denied_paths: Vec<PathBuf>,
+    /// TCP ports the subprocess may bind to; `None` leaves binding unrestricted.
+    #[builder(default, setter(strip_option))]
+    pub tcp_bind_ports: Option<Vec<u16>>,
+    /// TCP ports the subprocess may connect to; `None` leaves connecting unrestricted.
+    #[builder(default, setter(strip_option))]
+    pub tcp_connect_ports: Option<Vec<u16>>,
+    /// How to handle kernels that can't enforce the whole policy.
+    #[builder(default)]
+    pub mode: LandlockMode,
+}
+
+impl Default for LandlockSandboxConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl LandlockSandboxConfig {
+    /// Whether the config restricts nothing: no writable or readable roots,
+    /// denied paths or port lists
+    pub fn is_empty(&self) -> bool {
+        self.writable_roots.is_empty()
+            && self.readable_roots.is_none()
+            && self.denied_paths.is_empty()
+            && self.tcp_bind_ports.is_none()
+            && self.tcp_connect_ports.is_none()
+    }
+}
+
+fn default_extra_writable_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from("/tmp")];
+    if let Some(home) = std::env::var_os("HOME") {
+        paths.push(PathBuf::from(home).join(".claude"));
+    }
+    paths
+}
+
+/// How strictly a [`LandlockSandboxConfig`] is enforced
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LandlockMode {
+    /// Enforce what the kernel supports and silently skip the rest
+    #[default]
+    BestEffort,
+    /// Fail to spawn the CLI unless the kernel fully enforces the policy
+    Strict,
+}
+
+/// Network namespace isolation for the CLI process (Linux only)
+///
+/// The CLI runs in a fresh user and network namespace that only has loopback.
+/// `HTTPS_PROXY` and `HTTP_PROXY` point it at a proxy on `127.0.0.1:proxy_port`,
+/// which the SDK serves from the host's network namespace. The proxy only tunnels
+/// `CONNECT` requests to `allowed_destinations`. Requires unprivileged user
+/// namespaces.
+#[derive(Debug, Clone, TypedBuilder)]
+#[builder(doc)]
+pub struct NetworkIsolation {
+    /// Destinations reachable through the proxy, as `host` (port 443) or `host:port`
+    ///
+    /// A `*.` prefix also matches subdomains (`*.example.com`). Defaults to the
+    /// Anthropic API.
+    #[builder(default = vec!["api.anthropic.com:443".to_string()])]
+    pub allowed_destinations: Vec<String>,
+    /// Loopback port the proxy listens on inside the namespace
+    #[builder(default = 3128)]
+    pub proxy_port: u16,
+}
+
+impl Default for NetworkIsolation {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// Seccomp syscall filter applied to the CLI process (Linux only)
+///
+/// The filter blocks the `preset`'s syscalls plus `deny`, except those listed in
+/// `allow`. Syscalls are named as in `man 2 syscalls` (e.g. `"ptrace"`), plus
+/// `"raw_sockets"` for raw and packet sockets and `"inet_sockets"` for IPv4 and IPv6
+/// sockets. The filter is inherited by every process the CLI starts and is supported
+/// on x86_64 and aarch64.
+#[derive(Debug, Clone, Default, TypedBuilder)]
+#[builder(doc)]
+pub struct SeccompProfile {
+    /// Base set of blocked syscalls
+    #[builder(default)]
+    pub preset: SeccompPreset,
+    /// Further syscalls to block
+    #[builder(default)]
+    pub deny: Vec<String>,
+    /// Syscalls to permit even though the preset blocks them
+    #[builder(default)]
+    pub allow: Vec<String>,
+    /// What happens when a blocked syscall is made
+    #[builder(default)]
+    pub action: SeccompAction,
+}
+
+/// Base syscall block list of a [`SeccompProfile`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SeccompPreset {
+    /// Debugging, mounting, kernel modules, BPF, keyrings, clock changes, reboot
+    /// and raw or packet sockets
+    ///
+    /// Note that `mount` and `pivot_root` are also used by the CLI's own bash
+    /// sandbox (see [`SandboxSettings`]); allow them if that is enabled.
+    #[default]
+    Default,
+    /// [`Default`](Self::Default) plus namespaces, `chroot`, `io_uring` and NUMA
+    /// memory controls
+    Strict,
+    /// [`Default`](Self::Default) plus all IPv4 and IPv6 sockets
+    ///
+    /// This also blocks the CLI's own API traffic, so it is only useful with a
+    /// launcher or relay that reaches the API on the CLI's behalf.
+    NetworkOff,
+    /// Nothing; only `deny` is blocked
+    Empty,
+}
+
+/// Outcome of a syscall blocked by a [`SeccompProfile`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SeccompAction {
+    /// The syscall fails with `EPERM`
+    #[default]
+    Errno,
+    /// The whole process is killed with `SIGSYS`
+    KillProcess,
+}
+
+/// Resource limits applied to the CLI process (Linux only)
+///
+/// Each `Option` left as `None` keeps the limit inherited from the parent.
+/// Limits also apply to every tool process and MCP server the CLI spawns;
+/// `processes` counts all processes of the user, not just the CLI's.
+#[derive(Debug, Clone, Default, PartialEq, Eq, TypedBuilder)]
+#[builder(doc)]
+pub struct ResourceLimits {
+    /// CPU time in seconds (`RLIMIT_CPU`)
+    #[builder(default, setter(strip_option))]
+    pub cpu_seconds: Option<u64>,
+    /// Virtual address space in bytes (`RLIMIT_AS`)
+    #[builder(default, setter(strip_option))]
+    pub address_space: Option<u64>,
+    /// Maximum number of open file descriptors (`RLIMIT_NOFILE`)
+    #[builder(default, setter(strip_option))]
+    pub open_files: Option<u64>,
+    /// Maximum number of processes for the user (`RLIMIT_NPROC`)
+    #[builder(default, setter(strip_option))]
+    pub processes: Option<u64>,
+    /// Maximum core dump size in bytes (`RLIMIT_CORE`); `0` disables core dumps
+    #[builder(default, setter(strip_option))]
+    pub core_size: Option<u64>,
+    /// Maximum size of a written file in bytes (`RLIMIT_FSIZE`)
+    #[builder(default, setter(strip_option))]
+    pub file_size: Option<u64>,
+    /// Scheduling niceness, from -20 (highest priority) to 19 (lowest)
+    #[builder(default, setter(strip_option))]
+    pub nice: Option<i32>,
+    /// I/O scheduling class and level (`ioprio_set`)
+    #[builder(default, setter(strip_option))]
+    pub io_priority: Option<IoPriority>,
+}
+
+impl ResourceLimits {
+    /// Whether any limit or priority is set
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// I/O scheduling priority, as set by `ionice`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPriority {
+    /// Real-time class with a level from 0 (highest) to 7; usually requires root
+    RealTime(u8),
+    /// Best-effort class with a level from 0 (highest) to 7
+    BestEffort(u8),
+    /// Only gets disk time when no other process needs it
+    Idle,
+}
+
+/// The resource limit a CLI process most likely died from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceLimitKind {
+    /// CPU time ([`ResourceLimits::cpu_seconds`])
+    CpuTime,
+    /// Address space ([`ResourceLimits::address_space`])
+    AddressSpace,
+    /// Open files ([`ResourceLimits::open_files`])
+    OpenFiles,
+    /// Process count ([`ResourceLimits::processes`])
+    Processes,
+    /// File size ([`ResourceLimits::file_size`])
+    FileSize,
+}
+
+impl std::fmt::Display for ResourceLimitKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::CpuTime => "CPU time limit",
+            Self::AddressSpace => "address space limit",
+            Self::OpenFiles => "open files limit",
+            Self::Processes => "process limit",
+            Self::FileSize => "file size limit",
+        })
+    }
+}
+
+/// Limits on restarting a CLI process that exits unexpectedly
+#[derive(Debug, Clone, TypedBuilder)]
+#[builder(doc)]
+pub struct RestartPolicy {
+    /// Maximum consecutive restart attempts before giving up
+    #[builder(default = 3)]
+    pub max_restarts: u32,
+    /// Delay before the first restart attempt, doubled after each attempt
+    #[builder(default = Duration::from_millis(500))]
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between attempts
+    #[builder(default = Duration::from_secs(10))]
+    pub max_backoff: Duration,
+    /// A process that stays up this long resets the attempt counter
+    #[builder(default = Duration::from_secs(60))]
+    pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl RestartPolicy {
+    /// Delay before the given (1-based) restart attempt
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Behavior when a bounded message queue is full
+///
+/// See [`ClaudeAgentOptions::message_queue_capacity`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Hold messages back until the consumer catches up
+    ///
+    /// Control requests and responses are still handled while the queue is full,
+    /// so `interrupt()` and permission callbacks keep working, until as many
+    /// messages again are staged behind it. Then the SDK stops reading the CLI's
+    /// output, and the CLI blocks writing it until the consumer catches up.
+    #[default]
+    Block,
+    /// Drop incoming `stream_event` messages; other messages block as under `Block`
+    DropStreamEvents,
+    /// End the message stream with [`ClaudeError::QueueOverflow`](crate::errors::ClaudeError::QueueOverflow)
+    Fail,
+}
+
+/// Escalation timings for stopping the CLI subprocess
+///
+/// Stdin is closed first. If the CLI is still running after `grace_period`, its
+/// process group receives SIGTERM, and after a further `term_timeout`, SIGKILL.
+/// On Windows there is no SIGTERM stage; the process is killed after `grace_period`.
+#[derive(Debug, Clone, TypedBuilder)]
+#[builder(doc)]
+pub struct ShutdownPolicy {
+    /// Time to wait for a clean exit after closing stdin
+    #[builder(default = Duration::from_secs(5))]
+    pub grace_period: Duration,
+    /// Time to wait after SIGTERM before sending SIGKILL
+    #[builder(default = Duration::from_secs(5))]
+    pub term_timeout: Duration,
+}
+
+impl Default for ShutdownPolicy {
+    fn default() -> Self {
+        Self::builder().build()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_restart_policy_backoff() {
+        let policy = RestartPolicy::builder()
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(300))
+            .build();
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(300));
+        assert_eq!(policy.backoff(40), Duration::from_millis(300));
+    }
+
     #[test]
     fn test_tools_from_str_array() {
         let tools: Tools = ["Write", "Read", "Bash"].into();
@@ -0,0 +1,311 @@
+//! Environment policy and secret redaction for the CLI subprocess
+//!
+//! By default the CLI inherits the SDK process's whole environment. An
+//! [`EnvPolicy`] narrows what it inherits, and names the variables whose values
+//! must never show up in output the SDK surfaces; a [`Redactor`] masks them.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use typed_builder::TypedBuilder;
+
+/// Replacement text for redacted values
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Shortest value a [`Redactor`] masks
+///
+/// Masking a value like `1` or `true` everywhere it appears would garble
+/// unrelated output without protecting anything.
+pub const MIN_SECRET_LEN: usize = 6;
+
+/// Which of the SDK process's environment variables the CLI inherits
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum EnvInheritance {
+    /// Inherit every variable
+    #[default]
+    All,
+    /// Inherit only variables whose names match one of these patterns
+    ///
+    /// Patterns may use `*` (any run of characters) and `?` (one character),
+    /// e.g. `LC_*`.
+    Allowlist(Vec<String>),
+    /// Inherit nothing; the CLI only sees `env` and the SDK's own variables
+    Clean,
+}
+
+/// Controls the environment the CLI is spawned with
+///
+/// Variables set in [`ClaudeAgentOptions::env`](super::config::ClaudeAgentOptions::env)
+/// are always passed, whatever the policy.
+///
+/// # Example
+///
+/// ```
+/// use claude_agent_sdk_rs::{ClaudeAgentOptions, EnvInheritance, EnvPolicy};
+///
+/// let options = ClaudeAgentOptions::builder()
+///     .env_policy(
+///         EnvPolicy::builder()
+///             .inherit(EnvInheritance::Allowlist(vec![
+///                 "PATH".to_string(),
+///                 "HOME".to_string(),
+///                 "LC_*".to_string(),
+///             ]))
+///             .redact(vec!["ANTHROPIC_API_KEY".to_string(), "*_TOKEN".to_string()])
+///             .build(),
+///     )
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default, TypedBuilder)]
+#[builder(doc)]
+pub struct EnvPolicy {
+    /// Which inherited variables to keep
+    #[builder(default)]
+    pub inherit: EnvInheritance,
+    /// Inherited variables to drop even if `inherit` keeps them (patterns as in `Allowlist`)
+    #[builder(default, setter(into))]
+    pub remove: Vec<String>,
+    /// Variables whose values are masked in SDK logs, stderr output and recorded snapshots
+    ///
+    /// Matches inherited variables and `env` alike (patterns as in `Allowlist`).
+    /// Values shorter than [`MIN_SECRET_LEN`] are not masked.
+    #[builder(default, setter(into))]
+    pub redact: Vec<String>,
+}
+
+impl EnvPolicy {
+    /// Filter `parent` down to the variables the CLI inherits
+    pub(crate) fn inherited(
+        &self,
+        parent: impl IntoIterator<Item = (OsString, OsString)>,
+    ) -> Vec<(OsString, OsString)> {
+        parent
+            .into_iter()
+            .filter(|(key, _)| {
+                let key = key.to_string_lossy();
+                let kept = match &self.inherit {
+                    EnvInheritance::All => true,
+                    EnvInheritance::Allowlist(patterns) => matches_any(patterns, &key),
+                    EnvInheritance::Clean => false,
+                };
+                kept && !matches_any(&self.remove, &key)
+            })
+            .collect()
+    }
+
+    /// Build a redactor for the values of the `redact` variables
+    ///
+    /// Values are looked up in `env` first, then in the SDK process's environment.
+    pub fn redactor(&self, env: &HashMap<String, String>) -> Redactor {
+        if self.redact.is_empty() {
+            return Redactor::default();
+        }
+        let inherited = std::env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)));
+        Redactor::new(
+            env.iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .chain(inherited)
+                .filter(|(key, _)| matches_any(&self.redact, key))
+                .map(|(_, value)| value),
+        )
+    }
+}
+
+/// Masks secret values in text and JSON
+///
+/// `Debug` output never includes the secrets themselves.
+#[derive(Clone, Default)]
+pub struct Redactor {
+    /// Longest first, so a secret containing another is masked whole
+    secrets: Vec<String>,
+}
+
+impl Redactor {
+    /// Create a redactor for the given secret values
+    ///
+    /// Values shorter than [`MIN_SECRET_LEN`] bytes are ignored.
+    pub fn new(secrets: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let mut secrets: Vec<String> = secrets
+            .into_iter()
+            .map(Into::into)
+            .filter(|secret| secret.len() >= MIN_SECRET_LEN)
+            .collect();
+        secrets.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        secrets.dedup();
+        Self { secrets }
+    }
+
+    /// Whether there is nothing to redact
+    pub fn is_empty(&self) -> bool {
+        self.secrets.is_empty()
+    }
+
+    /// Replace every occurrence of a secret in `text` with [`REDACTED`]
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for secret in &self.secrets {
+            if text.contains(secret.as_str()) {
+                text = Cow::Owned(text.replace(secret.as_str(), REDACTED));
+            }
+        }
+        text
+    }
+
+    /// Redact every string in a JSON value, including object keys
+    pub fn redact_json(&self, value: &mut serde_json::Value) {
+        if self.is_empty() {
+            return;
+        }
+        match value {
+            serde_json::Value::String(text) => {
+                if let Cow::Owned(redacted) = self.redact(text) {
+                    *text = redacted;
+                }
+            }
+            serde_json::Value::Array(items) => {
+                items.iter_mut().for_each(|item| self.redact_json(item));
+            }
+            serde_json::Value::Object(map) => {
+                let entries = std::mem::take(map);
+                for (key, mut item) in entries {
+                    self.redact_json(&mut item);
+                    map.insert(self.redact(&key).into_owned(), item);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Debug for Redactor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Redactor")
+            .field("secrets", &self.secrets.len())
+            .finish()
+    }
+}
+
+fn matches_any(patterns: &[String], name: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| glob_matches(pattern.as_bytes(), name.as_bytes()))
+}
+
+/// Match `name` against a pattern with `*` and `?` wildcards
+pub(crate) fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*`: pattern index past it, and name index it matched up to
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    backtrack = Some((star_p, star_n + 1));
+                    p = star_p;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(names: &[&str]) -> Vec<(OsString, OsString)> {
+        names
+            .iter()
+            .map(|name| (OsString::from(name), OsString::from("value")))
+            .collect()
+    }
+
+    fn names(vars: Vec<(OsString, OsString)>) -> Vec<String> {
+        vars.into_iter()
+            .map(|(key, _)| key.into_string().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches(b"PATH", b"PATH"));
+        assert!(!glob_matches(b"PATH", b"PATHS"));
+        assert!(glob_matches(b"LC_*", b"LC_ALL"));
+        assert!(glob_matches(b"*_TOKEN", b"GITHUB_TOKEN"));
+        assert!(glob_matches(b"*SECRET*", b"AWS_SECRET_ACCESS_KEY"));
+        assert!(glob_matches(b"A?C", b"ABC"));
+        assert!(!glob_matches(b"A?C", b"AC"));
+        assert!(glob_matches(b"*", b""));
+    }
+
+    #[test]
+    fn test_inherited_applies_mode_and_removals() {
+        let parent = vars(&["PATH", "HOME", "LC_ALL", "AWS_SECRET_ACCESS_KEY"]);
+
+        let policy = EnvPolicy::builder()
+            .remove(vec!["AWS_*".to_string()])
+            .build();
+        assert_eq!(
+            names(policy.inherited(parent.clone())),
+            ["PATH", "HOME", "LC_ALL"]
+        );
+
+        let policy = EnvPolicy::builder()
+            .inherit(EnvInheritance::Allowlist(vec![
+                "PATH".to_string(),
+                "LC_*".to_string(),
+            ]))
+            .build();
+        assert_eq!(names(policy.inherited(parent.clone())), ["PATH", "LC_ALL"]);
+
+        let policy = EnvPolicy::builder().inherit(EnvInheritance::Clean).build();
+        assert!(policy.inherited(parent).is_empty());
+    }
+
+    #[test]
+    fn test_redactor_masks_text_and_json() {
+        let policy = EnvPolicy::builder()
+            .redact(vec!["*_TOKEN".to_string()])
+            .build();
+        let env = HashMap::from([
+            ("API_TOKEN".to_string(), "tok-123".to_string()),
+            ("PLAIN".to_string(), "visible".to_string()),
+        ]);
+        let redactor = policy.redactor(&env);
+
+        assert_eq!(
+            redactor.redact("auth tok-123 visible"),
+            "auth [REDACTED] visible"
+        );
+        assert!(matches!(redactor.redact("nothing here"), Cow::Borrowed(_)));
+
+        let mut value = serde_json::json!({"a": ["tok-123"], "tok-123": 1, "n": 5});
+        redactor.redact_json(&mut value);
+        assert_eq!(
+            value,
+            serde_json::json!({"a": ["[REDACTED]"], "[REDACTED]": 1, "n": 5})
+        );
+        assert!(!format!("{:?}", redactor).contains("tok-123"));
+    }
+
+    #[test]
+    fn test_redactor_ignores_short_values() {
+        let redactor = Redactor::new(["1", "true", "", "secret"]);
+        assert_eq!(
+            redactor.redact("debug=1 verbose=true secret"),
+            "debug=1 verbose=true [REDACTED]"
+        );
+    }
+}
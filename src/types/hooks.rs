@@ -4,6 +4,7 @@ use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use typed_builder::TypedBuilder;
 
 /// Hook events that can be intercepted
@@ -38,6 +39,23 @@ pub struct HookMatcher {
     pub timeout: Option<f64>,
 }
 
+/// Handle to a hook added with [`ClaudeClient::add_hook`](crate::ClaudeClient::add_hook)
+///
+/// Pass it to [`ClaudeClient::remove_hook`](crate::ClaudeClient::remove_hook) to
+/// unregister the hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HookHandle {
+    pub(crate) id: u64,
+    pub(crate) event: HookEvent,
+}
+
+impl HookHandle {
+    /// Event the hook was added for
+    pub fn event(&self) -> HookEvent {
+        self.event
+    }
+}
+
 /// Hook callback type
 pub type HookCallback = Arc<
     dyn Fn(HookInput, Option<String>, HookContext) -> BoxFuture<'static, HookJsonOutput>
@@ -174,8 +192,52 @@ pub struct PreCompactHookInput {
 /// Hook context passed to callbacks
 #[derive(Debug, Clone, Default)]
 pub struct HookContext {
-    /// Abort signal (future feature)
-    pub signal: Option<()>,
+    /// Fires if the CLI cancels the hook; always set when the SDK invokes the hook
+    pub signal: Option<AbortSignal>,
+}
+
+/// Cancellation signal for a hook or permission callback
+///
+/// The SDK aborts it when the CLI sends a `control_cancel_request` for the call,
+/// then stops awaiting the callback's future. Work the callback spawned elsewhere
+/// should watch [`aborted`](Self::aborted) to stop as well.
+#[derive(Debug, Clone, Default)]
+pub struct AbortSignal(Arc<AbortState>);
+
+#[derive(Debug, Default)]
+struct AbortState {
+    aborted: AtomicBool,
+    notify: tokio::sync::Notify,
+}
+
+impl AbortSignal {
+    /// Create a signal that has not fired
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the signal has fired
+    pub fn is_aborted(&self) -> bool {
+        self.0.aborted.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the signal fires
+    pub async fn aborted(&self) {
+        loop {
+            // Register before checking, so an abort in between isn't missed
+            let notified = self.0.notify.notified();
+            if self.is_aborted() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Fire the signal, waking every waiter
+    pub fn abort(&self) {
+        self.0.aborted.store(true, Ordering::SeqCst);
+        self.0.notify.notify_waiters();
+    }
 }
 
 /// Hook output (can be async or sync)
@@ -326,6 +388,26 @@ mod tests {
     use super::*;
     use serde_json::json;
 
+    #[tokio::test]
+    async fn test_abort_signal_wakes_waiters() {
+        let signal = AbortSignal::new();
+        let waiter = tokio::spawn({
+            let signal = signal.clone();
+            async move { signal.aborted().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!signal.is_aborted());
+
+        signal.abort();
+        tokio::time::timeout(std::time::Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(signal.is_aborted());
+        // Already fired: resolves immediately
+        signal.aborted().await;
+    }
+
     #[test]
     fn test_hook_event_serialization() {
         // HookEvent serializes to PascalCase to match Python SDK
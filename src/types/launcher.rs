@@ -0,0 +1,340 @@
+//! Launcher wrapper configuration for spawning the Claude Code CLI
+//!
+//! A [`ProcessLauncher`] runs the CLI through an isolation tool such as
+//! `bwrap`, `nsjail` or `firejail` instead of executing it directly. Host paths
+//! from [`ClaudeAgentOptions`](super::config::ClaudeAgentOptions) are rewritten
+//! through [`PathMapping`]s so they resolve inside the wrapper.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use typed_builder::TypedBuilder;
+
+/// Maps a host path prefix to the path it is mounted at inside the wrapper
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathMapping {
+    /// Path prefix on the host
+    pub host: PathBuf,
+    /// Corresponding path inside the wrapper
+    pub sandbox: PathBuf,
+}
+
+impl PathMapping {
+    /// Create a new path mapping
+    pub fn new(host: impl Into<PathBuf>, sandbox: impl Into<PathBuf>) -> Self {
+        Self {
+            host: host.into(),
+            sandbox: sandbox.into(),
+        }
+    }
+}
+
+/// How the CLI's environment variables reach the process inside the wrapper
+///
+/// # Security
+///
+/// [`Flag`](Self::Flag) and [`FlagPair`](Self::FlagPair) put every value on the
+/// wrapper's command line, including `ANTHROPIC_API_KEY` and anything else in
+/// [`ClaudeAgentOptions::env`](super::config::ClaudeAgentOptions::env). Other local
+/// users can read command lines through `ps` and `/proc/<pid>/cmdline`. Use
+/// [`Process`](Self::Process), the default, whenever the environment holds secrets,
+/// and configure the wrapper to pass the variables through (bwrap and firejail do
+/// unless told to clear the environment; nsjail needs `--keep_env` or `--env KEY`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum EnvForwarding {
+    /// Set the variables on the wrapper process and rely on it to pass them through
+    ///
+    /// Recommended: values stay out of the command line.
+    #[default]
+    Process,
+    /// Pass each variable as `FLAG KEY=VALUE` (e.g. nsjail's `--env`)
+    ///
+    /// Values are visible to other users through `ps`; don't use this for secrets.
+    Flag(String),
+    /// Pass each variable as `FLAG KEY VALUE` (e.g. bwrap's `--setenv`)
+    ///
+    /// Values are visible to other users through `ps`; don't use this for secrets.
+    FlagPair(String),
+}
+
+/// Wrapper program used to launch the Claude Code CLI
+///
+/// The CLI is spawned as `program [args...] [cwd_flag CWD] [env flags...] CLI_PATH [CLI_ARGS...]`.
+/// The working directory, `add_dirs`, plugin paths, the MCP config path and the CLI
+/// path itself are translated with [`path_mappings`](Self::path_mappings); paths
+/// outside every mapping are passed through unchanged.
+///
+/// # Example
+///
+/// ```
+/// use claude_agent_sdk_rs::{ClaudeAgentOptions, PathMapping, ProcessLauncher};
+///
+/// let launcher = ProcessLauncher::builder()
+///     .program("bwrap")
+///     .args(["--ro-bind", "/usr", "/usr", "--bind", "/home/me/project", "/workspace"].map(String::from))
+///     .path_mappings(vec![PathMapping::new("/home/me/project", "/workspace")])
+///     .cwd_flag("--chdir")
+///     .build();
+///
+/// let options = ClaudeAgentOptions::builder()
+///     .cwd("/home/me/project")
+///     .launcher(launcher)
+///     .build();
+/// ```
+#[derive(Debug, Clone, TypedBuilder)]
+#[builder(doc)]
+pub struct ProcessLauncher {
+    /// Wrapper program to execute (e.g. `bwrap`)
+    #[builder(setter(into))]
+    pub program: PathBuf,
+    /// Arguments passed to the wrapper before the CLI command
+    #[builder(default, setter(into))]
+    pub args: Vec<String>,
+    /// Host-to-wrapper path translations, longest matching prefix wins
+    #[builder(default, setter(into))]
+    pub path_mappings: Vec<PathMapping>,
+    /// Wrapper flag that sets the CLI's working directory (e.g. `--chdir`)
+    ///
+    /// When unset, the wrapper inherits the host working directory and is
+    /// expected to keep it.
+    #[builder(default, setter(into, strip_option))]
+    pub cwd_flag: Option<String>,
+    /// Host working directory for the wrapper process (defaults to the CLI's `cwd`)
+    #[builder(default, setter(into, strip_option))]
+    pub cwd: Option<PathBuf>,
+    /// Extra environment variables for the CLI, overriding the SDK's own
+    #[builder(default)]
+    pub env: HashMap<String, String>,
+    /// Environment variables removed before launching
+    #[builder(default, setter(into))]
+    pub env_remove: Vec<String>,
+    /// How environment variables are handed to the CLI
+    ///
+    /// Defaults to [`EnvForwarding::Process`]. The flag variants expose values on the
+    /// command line; see [`EnvForwarding`] before using them with secrets.
+    #[builder(default)]
+    pub env_forwarding: EnvForwarding,
+}
+
+/// Fully resolved command for spawning the wrapper
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LaunchCommand {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    /// Variables to set on the spawned process
+    pub env: HashMap<String, String>,
+    /// Variables to remove from the inherited environment
+    pub env_remove: Vec<String>,
+    pub cwd: Option<PathBuf>,
+}
+
+impl ProcessLauncher {
+    /// Translate a host path to the path seen inside the wrapper
+    pub fn map_path(&self, host_path: &Path) -> PathBuf {
+        self.path_mappings
+            .iter()
+            .filter_map(|mapping| {
+                host_path.strip_prefix(&mapping.host).ok().map(|rest| {
+                    let mapped = if rest.as_os_str().is_empty() {
+                        mapping.sandbox.clone()
+                    } else {
+                        mapping.sandbox.join(rest)
+                    };
+                    (mapping.host.components().count(), mapped)
+                })
+            })
+            .max_by_key(|(depth, _)| *depth)
+            .map(|(_, path)| path)
+            .unwrap_or_else(|| host_path.to_path_buf())
+    }
+
+    /// Wrap a CLI invocation in the launcher command
+    ///
+    /// `cli_path` and `cwd` are host paths; `cli_args` must already use wrapper paths.
+    pub(crate) fn wrap(
+        &self,
+        cli_path: &Path,
+        cli_args: Vec<String>,
+        mut env: HashMap<String, String>,
+        cwd: Option<&Path>,
+    ) -> LaunchCommand {
+        env.extend(self.env.clone());
+        for key in &self.env_remove {
+            env.remove(key);
+        }
+
+        let mut args = self.args.clone();
+        if let (Some(flag), Some(cwd)) = (&self.cwd_flag, cwd) {
+            args.push(flag.clone());
+            args.push(self.map_path(cwd).display().to_string());
+        }
+
+        let process_env = match &self.env_forwarding {
+            EnvForwarding::Process => env,
+            EnvForwarding::Flag(flag) => {
+                for (key, value) in sorted(&env) {
+                    args.push(flag.clone());
+                    args.push(format!("{}={}", key, value));
+                }
+                HashMap::new()
+            }
+            EnvForwarding::FlagPair(flag) => {
+                for (key, value) in sorted(&env) {
+                    args.push(flag.clone());
+                    args.push(key.clone());
+                    args.push(value.clone());
+                }
+                HashMap::new()
+            }
+        };
+
+        args.push(self.map_path(cli_path).display().to_string());
+        args.extend(cli_args);
+
+        LaunchCommand {
+            program: self.program.clone(),
+            args,
+            env: process_env,
+            env_remove: self.env_remove.clone(),
+            cwd: self.cwd.clone().or_else(|| cwd.map(Path::to_path_buf)),
+        }
+    }
+}
+
+/// Environment entries in a stable order, so wrapper arguments are deterministic
+fn sorted(env: &HashMap<String, String>) -> Vec<(&String, &String)> {
+    let mut entries: Vec<_> = env.iter().collect();
+    entries.sort();
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mappings() -> Vec<PathMapping> {
+        vec![
+            PathMapping::new("/home/me", "/host-home"),
+            PathMapping::new("/home/me/project", "/workspace"),
+        ]
+    }
+
+    #[test]
+    fn test_map_path_uses_longest_prefix() {
+        let launcher = ProcessLauncher::builder()
+            .program("bwrap")
+            .path_mappings(mappings())
+            .build();
+        assert_eq!(
+            launcher.map_path(Path::new("/home/me/project/src")),
+            PathBuf::from("/workspace/src")
+        );
+        assert_eq!(
+            launcher.map_path(Path::new("/home/me/notes")),
+            PathBuf::from("/host-home/notes")
+        );
+        assert_eq!(
+            launcher.map_path(Path::new("/home/me/project")),
+            PathBuf::from("/workspace")
+        );
+    }
+
+    #[test]
+    fn test_map_path_passes_through_unmapped() {
+        let launcher = ProcessLauncher::builder()
+            .program("bwrap")
+            .path_mappings(mappings())
+            .build();
+        assert_eq!(
+            launcher.map_path(Path::new("/opt/tools")),
+            PathBuf::from("/opt/tools")
+        );
+        // Prefix matching is per component, not per character
+        assert_eq!(
+            launcher.map_path(Path::new("/home/meow")),
+            PathBuf::from("/home/meow")
+        );
+    }
+
+    #[test]
+    fn test_wrap_orders_arguments() {
+        let launcher = ProcessLauncher::builder()
+            .program("bwrap")
+            .path_mappings(mappings())
+            .args(vec!["--unshare-all".to_string()])
+            .cwd_flag("--chdir")
+            .build();
+        let command = launcher.wrap(
+            Path::new("/home/me/.local/bin/claude"),
+            vec!["--verbose".to_string()],
+            HashMap::new(),
+            Some(Path::new("/home/me/project")),
+        );
+
+        assert_eq!(command.program, PathBuf::from("bwrap"));
+        assert_eq!(
+            command.args,
+            vec![
+                "--unshare-all",
+                "--chdir",
+                "/workspace",
+                "/host-home/.local/bin/claude",
+                "--verbose"
+            ]
+        );
+        assert_eq!(command.cwd, Some(PathBuf::from("/home/me/project")));
+    }
+
+    #[test]
+    fn test_wrap_forwards_env_as_flags() {
+        let launcher = ProcessLauncher::builder()
+            .program("nsjail")
+            .env(HashMap::from([("EXTRA".to_string(), "1".to_string())]))
+            .env_remove(vec!["SECRET".to_string()])
+            .env_forwarding(EnvForwarding::Flag("--env".to_string()))
+            .build();
+        let env = HashMap::from([
+            ("CLAUDE_CODE_ENTRYPOINT".to_string(), "sdk-rs".to_string()),
+            ("SECRET".to_string(), "hunter2".to_string()),
+        ]);
+        let command = launcher.wrap(Path::new("claude"), vec![], env, None);
+
+        assert_eq!(
+            command.args,
+            vec![
+                "--env",
+                "CLAUDE_CODE_ENTRYPOINT=sdk-rs",
+                "--env",
+                "EXTRA=1",
+                "claude"
+            ]
+        );
+        assert!(command.env.is_empty());
+        assert_eq!(command.env_remove, vec!["SECRET"]);
+        assert_eq!(command.cwd, None);
+    }
+
+    #[test]
+    fn test_wrap_forwards_env_as_flag_pairs() {
+        let launcher = ProcessLauncher::builder()
+            .program("bwrap")
+            .env_forwarding(EnvForwarding::FlagPair("--setenv".to_string()))
+            .build();
+        let env = HashMap::from([("A".to_string(), "b".to_string())]);
+        let command = launcher.wrap(Path::new("claude"), vec![], env, None);
+
+        assert_eq!(command.args, vec!["--setenv", "A", "b", "claude"]);
+    }
+
+    #[test]
+    fn test_wrap_process_env_overrides() {
+        let launcher = ProcessLauncher::builder()
+            .program("firejail")
+            .env(HashMap::from([("A".to_string(), "override".to_string())]))
+            .build();
+        let env = HashMap::from([("A".to_string(), "sdk".to_string())]);
+        let command = launcher.wrap(Path::new("claude"), vec![], env, None);
+
+        assert_eq!(command.env["A"], "override");
+        assert_eq!(command.args, vec!["claude"]);
+    }
+}
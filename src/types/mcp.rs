@@ -34,6 +34,25 @@ pub enum McpServerConfig {
     Sdk(McpSdkServerConfig),
 }
 
+impl McpServerConfig {
+    /// Configuration as the CLI expects it; SDK servers only pass their name
+    pub(crate) fn to_cli_config(&self) -> serde_json::Value {
+        let (server_type, config) = match self {
+            McpServerConfig::Sdk(sdk_config) => {
+                return serde_json::json!({"type": "sdk", "name": sdk_config.name});
+            }
+            McpServerConfig::Stdio(config) => ("stdio", serde_json::to_value(config)),
+            McpServerConfig::Sse(config) => ("sse", serde_json::to_value(config)),
+            McpServerConfig::Http(config) => ("http", serde_json::to_value(config)),
+        };
+        let mut value = config.unwrap_or_else(|_| serde_json::json!({}));
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert("type".to_string(), serde_json::json!(server_type));
+        }
+        value
+    }
+}
+
 /// Stdio MCP server configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpStdioServerConfig {
@@ -83,6 +102,58 @@ pub trait SdkMcpServer: Send + Sync {
     async fn handle_message(&self, message: serde_json::Value) -> Result<serde_json::Value>;
 }
 
+/// Connection state of an MCP server, from
+/// [`ClaudeClient::mcp_status`](crate::ClaudeClient::mcp_status)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct McpServerStatus {
+    /// Server name
+    pub name: String,
+    /// Whether the CLI is connected to the server
+    pub status: McpConnectionStatus,
+    /// Name and version the server reported, once connected
+    #[serde(
+        default,
+        rename = "serverInfo",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub server_info: Option<McpServerInfo>,
+    /// Why the connection failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Any other fields the CLI returned
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Connection state reported by the CLI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum McpConnectionStatus {
+    /// Connected and serving tools
+    Connected,
+    /// Still connecting
+    Pending,
+    /// Connection failed
+    Failed,
+    /// Waiting for the user to authenticate
+    NeedsAuth,
+    /// Disabled by the user
+    Disabled,
+    /// A state this SDK version doesn't know
+    #[serde(other)]
+    Unknown,
+}
+
+/// Name and version an MCP server reported
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct McpServerInfo {
+    /// Server name
+    pub name: String,
+    /// Server version
+    #[serde(default)]
+    pub version: String,
+}
+
 /// Tool handler trait
 pub trait ToolHandler: Send + Sync {
     /// Handle a tool invocation
@@ -275,3 +346,36 @@ macro_rules! tool {
         }
     }};
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_to_cli_config_tags_the_server_type() {
+        let stdio = McpServerConfig::Stdio(McpStdioServerConfig {
+            command: "npx".to_string(),
+            args: Some(vec!["server".to_string()]),
+            env: None,
+        });
+        assert_eq!(
+            stdio.to_cli_config(),
+            json!({"type": "stdio", "command": "npx", "args": ["server"]})
+        );
+        let sdk = McpServerConfig::Sdk(create_sdk_mcp_server("calc", "1.0.0", vec![]));
+        assert_eq!(sdk.to_cli_config(), json!({"type": "sdk", "name": "calc"}));
+    }
+
+    #[test]
+    fn test_server_status_parses_known_and_unknown_states() {
+        let statuses: Vec<McpServerStatus> = serde_json::from_value(json!([
+            {"name": "github", "status": "needs-auth", "scope": "user"},
+            {"name": "db", "status": "reconnecting"}
+        ]))
+        .unwrap();
+        assert_eq!(statuses[0].status, McpConnectionStatus::NeedsAuth);
+        assert_eq!(statuses[0].extra["scope"], "user");
+        assert_eq!(statuses[1].status, McpConnectionStatus::Unknown);
+    }
+}
@@ -1,6 +1,9 @@
 //! Message types for Claude Agent SDK
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::types::usage::{ModelUsage, Usage};
 
 /// Supported image MIME types for Claude API
 const SUPPORTED_IMAGE_MIME_TYPES: &[&str] = &["image/jpeg", "image/png", "image/gif", "image/webp"];
@@ -45,12 +48,95 @@ pub enum Message {
     /// User message (rarely used in stream output)
     #[serde(rename = "user")]
     User(UserMessage),
-    /// Control cancel request (ignore this - it's internal control protocol)
+    /// Control cancel request; the SDK consumes these, so clients don't receive them
     #[serde(rename = "control_cancel_request")]
     ControlCancelRequest(serde_json::Value),
     /// Rate limit event from the API
     #[serde(rename = "rate_limit_event")]
     RateLimitEvent(serde_json::Value),
+    /// CLI restart notice from the SDK (supervised mode only, never sent by the CLI)
+    #[serde(rename = "sdk_reconnect")]
+    Reconnect(ReconnectEvent),
+    /// Message larger than `max_buffer_size`, written to disk by the SDK
+    /// (only with [`spill_dir`](crate::ClaudeAgentOptions::spill_dir) set)
+    #[serde(rename = "sdk_spilled")]
+    Spilled(SpilledMessage),
+    /// The CLI process exited unexpectedly (from the SDK, never sent by the CLI)
+    #[serde(rename = "sdk_process_exited")]
+    ProcessExited(ProcessExited),
+}
+
+/// Exit report for a CLI process that ended before the SDK closed it
+///
+/// Also emitted when a one-shot query's CLI fails before producing a result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessExited {
+    /// Exit code, if the process exited normally
+    pub code: Option<i32>,
+    /// Signal that terminated the process (Unix only)
+    pub signal: Option<i32>,
+    /// Most recent stderr lines, oldest first
+    pub stderr_tail: Vec<String>,
+    /// Configured resource limit the process died from, when stderr or the exit
+    /// signal shows it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<crate::types::config::ResourceLimitKind>,
+}
+
+/// A message from the CLI that was written to a file instead of being buffered
+///
+/// The SDK does not delete the file; call [`remove`](Self::remove) once done with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpilledMessage {
+    /// File holding the raw JSON line
+    pub path: std::path::PathBuf,
+    /// Size of the message in bytes
+    pub size: u64,
+    /// The message's `type` field (e.g. `"user"` for tool results), if found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_type: Option<String>,
+}
+
+impl SpilledMessage {
+    /// Read and parse the message from disk
+    pub fn load(&self) -> crate::errors::Result<Message> {
+        let file = std::fs::File::open(&self.path)?;
+        serde_json::from_reader(std::io::BufReader::new(file)).map_err(|e| {
+            crate::errors::MessageParseError::new(
+                format!("Failed to parse spilled message: {}", e),
+                None,
+            )
+            .into()
+        })
+    }
+
+    /// Delete the backing file
+    pub fn remove(&self) -> std::io::Result<()> {
+        std::fs::remove_file(&self.path)
+    }
+}
+
+/// Outcome of restarting a CLI process that exited unexpectedly
+///
+/// Emitted on the message stream when [`RestartPolicy`](crate::RestartPolicy) is set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ReconnectEvent {
+    /// A new CLI process was started and initialized
+    Reconnected {
+        /// Restart attempt that succeeded (1-based)
+        attempt: u32,
+        /// Session resumed by the new process, if one had been seen
+        #[serde(skip_serializing_if = "Option::is_none")]
+        session_id: Option<String>,
+    },
+    /// The restart policy was exhausted; the message stream ends after this event
+    Failed {
+        /// Number of restart attempts made
+        attempts: u32,
+        /// Error from the last attempt
+        error: String,
+    },
 }
 
 /// User message
@@ -134,9 +220,9 @@ pub struct AssistantMessageInner {
     /// Stop reason
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stop_reason: Option<String>,
-    /// Usage statistics
+    /// Token usage for the API response this message is part of
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub usage: Option<serde_json::Value>,
+    pub usage: Option<Usage>,
     /// Error type (if any)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<AssistantMessageError>,
@@ -191,9 +277,17 @@ pub struct ResultMessage {
     /// Total cost in USD
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_cost_usd: Option<f64>,
-    /// Usage statistics
+    /// Token usage for the turn
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub usage: Option<serde_json::Value>,
+    pub usage: Option<Usage>,
+    /// Token usage and cost for the turn by model
+    #[serde(
+        default,
+        rename = "modelUsage",
+        deserialize_with = "crate::types::usage::null_as_empty",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub model_usage: HashMap<String, ModelUsage>,
     /// Result text (if any)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<String>,
@@ -439,6 +533,50 @@ mod tests {
     use super::*;
     use serde_json::json;
 
+    #[test]
+    fn test_message_reconnect_roundtrip() {
+        let json = json!({
+            "type": "sdk_reconnect",
+            "status": "reconnected",
+            "attempt": 2,
+            "session_id": "abc"
+        });
+
+        let msg: Message = serde_json::from_value(json.clone()).unwrap();
+        match &msg {
+            Message::Reconnect(ReconnectEvent::Reconnected {
+                attempt,
+                session_id,
+            }) => {
+                assert_eq!(*attempt, 2);
+                assert_eq!(session_id.as_deref(), Some("abc"));
+            }
+            other => panic!("Expected reconnect event, got {:?}", other),
+        }
+        assert_eq!(serde_json::to_value(&msg).unwrap(), json);
+    }
+
+    #[test]
+    fn test_message_process_exited_roundtrip() {
+        let json = json!({
+            "type": "sdk_process_exited",
+            "code": null,
+            "signal": 9,
+            "stderr_tail": ["killed"]
+        });
+
+        let msg: Message = serde_json::from_value(json.clone()).unwrap();
+        match &msg {
+            Message::ProcessExited(exit) => {
+                assert_eq!(exit.code, None);
+                assert_eq!(exit.signal, Some(9));
+                assert_eq!(exit.stderr_tail, vec!["killed"]);
+            }
+            other => panic!("Expected process exit report, got {:?}", other),
+        }
+        assert_eq!(serde_json::to_value(&msg).unwrap(), json);
+    }
+
     #[test]
     fn test_content_block_text_serialization() {
         let block = ContentBlock::Text(TextBlock {
@@ -1,9 +1,16 @@
 //! Type definitions for the Claude Agent SDK
 
+pub mod capabilities;
 pub mod config;
 pub mod efficiency;
+pub mod env;
 pub mod hooks;
+pub mod launcher;
 pub mod mcp;
 pub mod messages;
 pub mod permissions;
 pub mod plugin;
+pub mod sandbox;
+pub mod server_info;
+pub mod tap;
+pub mod usage;
@@ -4,6 +4,8 @@ use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
+use super::hooks::AbortSignal;
+
 /// Callback for tool usage permission
 pub type CanUseToolCallback = Arc<
     dyn Fn(String, serde_json::Value, ToolPermissionContext) -> BoxFuture<'static, PermissionResult>
@@ -14,8 +16,10 @@ pub type CanUseToolCallback = Arc<
 /// Context provided to permission callbacks
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ToolPermissionContext {
-    /// Abort signal (future feature)
-    pub signal: Option<()>,
+    /// Fires if the CLI cancels the permission request; always set when the SDK
+    /// invokes the callback
+    #[serde(skip)]
+    pub signal: Option<AbortSignal>,
     /// Permission suggestions from Claude
     pub suggestions: Vec<PermissionUpdate>,
 }
@@ -34,14 +38,21 @@ pub enum PermissionResult {
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PermissionResultAllow {
     /// Updated tool input (if modified)
-    #[serde(rename = "updatedInput", serialize_with = "serialize_updated_input", default)]
+    #[serde(
+        rename = "updatedInput",
+        serialize_with = "serialize_updated_input",
+        default
+    )]
     pub updated_input: Option<serde_json::Value>,
     /// Permission updates to apply
     #[serde(skip_serializing_if = "Option::is_none", rename = "updatedPermissions")]
     pub updated_permissions: Option<Vec<PermissionUpdate>>,
 }
 
-fn serialize_updated_input<S>(value: &Option<serde_json::Value>, serializer: S) -> Result<S::Ok, S::Error>
+fn serialize_updated_input<S>(
+    value: &Option<serde_json::Value>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
 {
@@ -0,0 +1,42 @@
+//! Sandbox enforcement status reported for a spawned CLI process
+
+use serde::{Deserialize, Serialize};
+
+/// OS-level sandboxing actually in effect for the CLI process
+///
+/// Returned by [`ClaudeClient::sandbox_status`](crate::ClaudeClient::sandbox_status).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SandboxStatus {
+    /// Landlock status; `None` if no Landlock sandbox was requested
+    pub landlock: Option<LandlockStatus>,
+    /// Whether the CLI runs in its own network namespace behind the SDK's proxy
+    pub network_isolated: bool,
+}
+
+/// Landlock enforcement reached when the CLI was spawned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LandlockStatus {
+    /// How much of the requested policy the kernel enforces
+    pub enforcement: LandlockEnforcement,
+    /// Landlock ABI version of the kernel; `None` if Landlock is unavailable
+    pub abi: Option<u32>,
+}
+
+impl LandlockStatus {
+    /// Whether the whole policy is enforced
+    pub fn is_fully_enforced(&self) -> bool {
+        self.enforcement == LandlockEnforcement::FullyEnforced
+    }
+}
+
+/// How much of a Landlock policy the kernel enforces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LandlockEnforcement {
+    /// Every requested restriction is enforced
+    FullyEnforced,
+    /// Some restrictions are not supported by the kernel's ABI and were skipped
+    PartiallyEnforced,
+    /// Landlock is unavailable; no restriction is enforced
+    NotEnforced,
+}
@@ -0,0 +1,200 @@
+//! Server information returned by the CLI's `initialize` response
+
+use serde::{Deserialize, Serialize};
+
+/// What the connected CLI offers, from its `initialize` response
+///
+/// Returned by [`ClaudeClient::get_server_info`](crate::ClaudeClient::get_server_info).
+/// Fields the SDK doesn't model are kept in [`extra`](Self::extra).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    /// Available slash commands
+    #[serde(default)]
+    pub commands: Vec<SlashCommand>,
+    /// Current output style
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_style: Option<String>,
+    /// Output styles that can be selected
+    #[serde(default)]
+    pub available_output_styles: Vec<String>,
+    /// Models that can be selected with `set_model`
+    #[serde(default)]
+    pub models: Vec<ModelInfo>,
+    /// Account the CLI is authenticated as
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<AccountInfo>,
+    /// Any other fields the CLI returned
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ServerInfo {
+    /// Parse an `initialize` response, keeping everything in `extra` if it doesn't fit the model
+    pub(crate) fn from_response(response: serde_json::Value) -> Self {
+        match serde_json::from_value(response.clone()) {
+            Ok(info) => info,
+            Err(e) => {
+                tracing::warn!("Unexpected initialize response from the CLI: {}", e);
+                Self {
+                    extra: match response {
+                        serde_json::Value::Object(map) => map,
+                        _ => Default::default(),
+                    },
+                    ..Default::default()
+                }
+            }
+        }
+    }
+
+    /// Look up a slash command by name, with or without the leading `/`
+    pub fn command(&self, name: &str) -> Option<&SlashCommand> {
+        let name = name.strip_prefix('/').unwrap_or(name);
+        self.commands.iter().find(|command| command.name == name)
+    }
+
+    /// Whether a slash command is available, e.g. `supports_command("compact")`
+    pub fn supports_command(&self, name: &str) -> bool {
+        self.command(name).is_some()
+    }
+
+    /// CLI version, if the response reports one in a `version` field
+    pub fn cli_version(&self) -> Option<&str> {
+        self.extra.get("version").and_then(|v| v.as_str())
+    }
+
+    /// Whether a model can be selected, by its `value`
+    pub fn supports_model(&self, value: &str) -> bool {
+        self.models.iter().any(|model| model.value == value)
+    }
+
+    /// Whether an output style can be selected
+    pub fn supports_output_style(&self, name: &str) -> bool {
+        self.available_output_styles
+            .iter()
+            .any(|style| style == name)
+    }
+}
+
+/// A slash command offered by the CLI
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlashCommand {
+    /// Command name, without the leading `/`
+    pub name: String,
+    /// What the command does
+    #[serde(default)]
+    pub description: String,
+    /// Hint for the command's arguments, e.g. `<file>`
+    #[serde(
+        default,
+        rename = "argumentHint",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub argument_hint: Option<String>,
+    /// Any other fields the CLI returned
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A model the CLI can switch to
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    /// Identifier to pass to `set_model`
+    pub value: String,
+    /// Human-readable name
+    #[serde(
+        default,
+        rename = "displayName",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub display_name: Option<String>,
+    /// Description of the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Any other fields the CLI returned
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Account the CLI is authenticated as
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountInfo {
+    /// Account email
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Organization name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    /// Subscription type, e.g. `max`
+    #[serde(
+        default,
+        rename = "subscriptionType",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub subscription_type: Option<String>,
+    /// Where the auth token came from
+    #[serde(
+        default,
+        rename = "tokenSource",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub token_source: Option<String>,
+    /// Where the API key came from
+    #[serde(
+        default,
+        rename = "apiKeySource",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub api_key_source: Option<String>,
+    /// Any other fields the CLI returned
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_server_info_from_response() {
+        let info = ServerInfo::from_response(json!({
+            "commands": [
+                {"name": "compact", "description": "Compact the conversation", "argumentHint": "<instructions>"},
+                {"name": "review", "description": "Review a PR", "aliases": ["r"]}
+            ],
+            "output_style": "default",
+            "available_output_styles": ["default", "Explanatory"],
+            "models": [{"value": "sonnet", "displayName": "Sonnet", "description": "Fast"}],
+            "account": {"email": "dev@example.com", "subscriptionType": "max"},
+            "pid": 1234
+        }));
+
+        assert!(info.supports_command("compact"));
+        assert!(info.supports_command("/review"));
+        assert!(!info.supports_command("deploy"));
+        assert_eq!(
+            info.command("compact").unwrap().argument_hint.as_deref(),
+            Some("<instructions>")
+        );
+        assert_eq!(
+            info.command("review").unwrap().extra["aliases"],
+            json!(["r"])
+        );
+        assert_eq!(info.output_style.as_deref(), Some("default"));
+        assert!(info.supports_output_style("Explanatory"));
+        assert!(info.supports_model("sonnet"));
+        assert_eq!(info.models[0].display_name.as_deref(), Some("Sonnet"));
+        let account = info.account.as_ref().unwrap();
+        assert_eq!(account.email.as_deref(), Some("dev@example.com"));
+        assert_eq!(account.subscription_type.as_deref(), Some("max"));
+        assert_eq!(info.extra["pid"], 1234);
+    }
+
+    #[test]
+    fn test_server_info_keeps_unexpected_shapes() {
+        let info = ServerInfo::from_response(json!({"commands": "not a list", "pid": 1}));
+        assert!(info.commands.is_empty());
+        assert_eq!(info.extra["commands"], "not a list");
+        assert_eq!(info.extra["pid"], 1);
+    }
+}
@@ -0,0 +1,531 @@
+//! Wire-level protocol tap for production debugging
+//!
+//! A [`ProtocolTap`] sees every frame exchanged with the CLI: user messages,
+//! control requests and responses in both directions, and stream output. Frames
+//! are stamped with a sequence number and a monotonic timestamp, masked, and
+//! written to a rotating JSONL file or handed to a callback. File writes happen
+//! on a dedicated thread, off the async runtime.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use typed_builder::TypedBuilder;
+
+use super::env::REDACTED;
+
+/// Which way a frame travelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TapDirection {
+    /// Written by the SDK to the CLI
+    Sent,
+    /// Read by the SDK from the CLI
+    Received,
+}
+
+/// One frame seen by a [`ProtocolTap`]; each JSONL line holds one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TapFrame {
+    /// Position in the tap's frame sequence, starting at 0
+    pub seq: u64,
+    /// Monotonic time since the tap was created, in microseconds
+    pub elapsed_us: u64,
+    /// Wall-clock time, in milliseconds since the Unix epoch, for correlating with other logs
+    pub unix_ms: u64,
+    /// Which way the frame travelled
+    pub direction: TapDirection,
+    /// The frame, after masking
+    pub frame: serde_json::Value,
+}
+
+/// Rotating JSONL file a [`ProtocolTap`] writes to
+#[derive(Debug, Clone, TypedBuilder)]
+#[builder(doc)]
+pub struct TapFile {
+    /// File to append frames to; rotated files get `.1`, `.2`, ... appended
+    #[builder(setter(into))]
+    pub path: PathBuf,
+    /// Size at which the file is rotated (default 64 MiB)
+    #[builder(default = 64 * 1024 * 1024)]
+    pub max_bytes: u64,
+    /// Files kept, including the current one (default 5)
+    #[builder(default = 5)]
+    pub max_files: usize,
+}
+
+/// Custom redaction applied to every frame after the built-in masks
+pub type TapRedaction = Arc<dyn Fn(TapDirection, &mut serde_json::Value) + Send + Sync>;
+
+/// Callback receiving every frame
+pub type TapCallback = Arc<dyn Fn(&TapFrame) + Send + Sync>;
+
+enum TapSink {
+    File(FileWriter),
+    Callback(TapCallback),
+}
+
+/// Hands serialized frames to a dedicated writer thread
+struct FileWriter {
+    path: PathBuf,
+    tx: flume::Sender<WriterCommand>,
+}
+
+enum WriterCommand {
+    Line(Vec<u8>),
+    Flush(flume::Sender<()>),
+}
+
+impl FileWriter {
+    /// Start the writer thread; it exits once every clone of the tap is dropped
+    fn spawn(config: TapFile) -> Self {
+        let (tx, rx) = flume::unbounded();
+        let path = config.path.clone();
+        let spawned = std::thread::Builder::new()
+            .name("claude-protocol-tap".to_string())
+            .spawn(move || {
+                let mut file = RotatingFile::new(config);
+                for command in rx.iter() {
+                    match command {
+                        WriterCommand::Line(line) => {
+                            if let Err(e) = file.write_line(&line) {
+                                warn!(
+                                    "Failed to write protocol tap frame to {}: {}",
+                                    file.config.path.display(),
+                                    e
+                                );
+                            }
+                        }
+                        WriterCommand::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            warn!("Failed to start the protocol tap writer: {}", e);
+        }
+        Self { path, tx }
+    }
+}
+
+/// Records every frame exchanged with the CLI
+///
+/// Set it as [`ClaudeAgentOptions::protocol_tap`](crate::ClaudeAgentOptions::protocol_tap).
+/// Clones share the same sequence, clock and file, so a supervised restart keeps
+/// writing to the same trace. Values matched by the
+/// [`env_policy`](crate::ClaudeAgentOptions::env_policy) redaction patterns are
+/// always masked.
+///
+/// # Example
+///
+/// ```no_run
+/// use claude_agent_sdk_rs::{ClaudeAgentOptions, ProtocolTap, TapFile};
+///
+/// let tap = ProtocolTap::to_file(
+///     TapFile::builder()
+///         .path("/var/log/my-app/claude-trace.jsonl")
+///         .max_bytes(16 * 1024 * 1024)
+///         .build(),
+/// )
+/// .with_masked_prompts()
+/// .with_masked_tool_inputs();
+///
+/// let options = ClaudeAgentOptions::builder().protocol_tap(tap).build();
+/// ```
+#[derive(Clone)]
+pub struct ProtocolTap {
+    inner: Arc<TapState>,
+    mask_prompts: bool,
+    mask_tool_inputs: bool,
+    redaction: Option<TapRedaction>,
+}
+
+struct TapState {
+    sink: TapSink,
+    started: Instant,
+    /// Next sequence number; held while a frame is handed on, so frames reach the
+    /// sink in sequence order
+    next_seq: Mutex<u64>,
+}
+
+impl ProtocolTap {
+    /// Write frames as JSONL to a rotating file
+    ///
+    /// Frames are written by a dedicated thread. The file is opened, and appended
+    /// to, when the first frame arrives; new files are readable only by their owner.
+    pub fn to_file(file: TapFile) -> Self {
+        Self::new(TapSink::File(FileWriter::spawn(file)))
+    }
+
+    /// Hand every frame to `callback`
+    ///
+    /// The callback runs on the SDK's I/O path, one frame at a time and in sequence
+    /// order, so it should return quickly.
+    pub fn to_callback(callback: impl Fn(&TapFrame) + Send + Sync + 'static) -> Self {
+        Self::new(TapSink::Callback(Arc::new(callback)))
+    }
+
+    fn new(sink: TapSink) -> Self {
+        Self {
+            inner: Arc::new(TapState {
+                sink,
+                started: Instant::now(),
+                next_seq: Mutex::new(0),
+            }),
+            mask_prompts: false,
+            mask_tool_inputs: false,
+            redaction: None,
+        }
+    }
+
+    /// Mask the text of user prompts
+    pub fn with_masked_prompts(mut self) -> Self {
+        self.mask_prompts = true;
+        self
+    }
+
+    /// Mask tool inputs in tool calls, permission requests and hook inputs
+    pub fn with_masked_tool_inputs(mut self) -> Self {
+        self.mask_tool_inputs = true;
+        self
+    }
+
+    /// Apply `redaction` to every frame after the built-in masks
+    pub fn with_redaction(
+        mut self,
+        redaction: impl Fn(TapDirection, &mut serde_json::Value) + Send + Sync + 'static,
+    ) -> Self {
+        self.redaction = Some(Arc::new(redaction));
+        self
+    }
+
+    /// Mask and record a frame
+    ///
+    /// `frame` has already had environment secrets redacted.
+    pub(crate) fn record(&self, direction: TapDirection, mut frame: serde_json::Value) {
+        if self.mask_prompts {
+            mask_prompts(&mut frame);
+        }
+        if self.mask_tool_inputs {
+            mask_tool_inputs(&mut frame);
+        }
+        if let Some(redaction) = &self.redaction {
+            redaction(direction, &mut frame);
+        }
+
+        let mut next_seq = self.inner.next_seq.lock().unwrap();
+        let frame = TapFrame {
+            seq: *next_seq,
+            elapsed_us: self.inner.started.elapsed().as_micros() as u64,
+            unix_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+            direction,
+            frame,
+        };
+        *next_seq += 1;
+        match &self.inner.sink {
+            TapSink::Callback(callback) => callback(&frame),
+            TapSink::File(writer) => {
+                let mut line = match serde_json::to_vec(&frame) {
+                    Ok(line) => line,
+                    Err(e) => {
+                        warn!("Failed to serialize a protocol tap frame: {}", e);
+                        return;
+                    }
+                };
+                line.push(b'\n');
+                let _ = writer.tx.send(WriterCommand::Line(line));
+            }
+        }
+    }
+
+    /// Block until every frame recorded so far has been written
+    ///
+    /// Useful before exiting, so the end of the trace isn't lost. Does nothing for
+    /// a callback tap.
+    pub fn flush(&self) {
+        if let TapSink::File(writer) = &self.inner.sink {
+            let (done_tx, done_rx) = flume::bounded(1);
+            if writer.tx.send(WriterCommand::Flush(done_tx)).is_ok() {
+                let _ = done_rx.recv();
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ProtocolTap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sink = match &self.inner.sink {
+            TapSink::File(writer) => format!("{:?}", writer.path),
+            TapSink::Callback(_) => "callback".to_string(),
+        };
+        f.debug_struct("ProtocolTap")
+            .field("sink", &sink)
+            .field("mask_prompts", &self.mask_prompts)
+            .field("mask_tool_inputs", &self.mask_tool_inputs)
+            .field("redaction", &self.redaction.is_some())
+            .finish()
+    }
+}
+
+/// Replace `value` with [`REDACTED`] if present
+fn mask(value: Option<&mut serde_json::Value>) {
+    if let Some(value) = value {
+        *value = serde_json::Value::String(REDACTED.to_string());
+    }
+}
+
+fn mask_prompts(frame: &mut serde_json::Value) {
+    match frame["type"].as_str() {
+        Some("user") => match frame.pointer_mut("/message/content") {
+            Some(content @ serde_json::Value::String(_)) => mask(Some(content)),
+            Some(serde_json::Value::Array(blocks)) => {
+                for block in blocks.iter_mut().filter(|block| block["type"] == "text") {
+                    mask(block.get_mut("text"));
+                }
+            }
+            _ => {}
+        },
+        Some("control_request") if frame["request"]["subtype"] == "hook_callback" => {
+            mask(frame.pointer_mut("/request/input/prompt"));
+        }
+        _ => {}
+    }
+}
+
+fn mask_tool_inputs(frame: &mut serde_json::Value) {
+    match frame["type"].as_str() {
+        Some("assistant") => {
+            if let Some(serde_json::Value::Array(blocks)) = frame.pointer_mut("/message/content") {
+                for block in blocks
+                    .iter_mut()
+                    .filter(|block| block["type"] == "tool_use")
+                {
+                    mask(block.get_mut("input"));
+                }
+            }
+        }
+        Some("stream_event") => {
+            mask(frame.pointer_mut("/event/content_block/input"));
+            mask(frame.pointer_mut("/event/delta/partial_json"));
+        }
+        Some("control_request") => match frame["request"]["subtype"].as_str() {
+            Some("can_use_tool") => mask(frame.pointer_mut("/request/input")),
+            Some("hook_callback") => mask(frame.pointer_mut("/request/input/tool_input")),
+            Some("mcp_message") => mask(frame.pointer_mut("/request/message/params/arguments")),
+            _ => {}
+        },
+        Some("control_response") => {
+            mask(frame.pointer_mut("/response/response/updatedInput"));
+            mask(frame.pointer_mut("/response/response/hookSpecificOutput/updatedInput"));
+        }
+        _ => {}
+    }
+}
+
+/// JSONL file that is rotated once it reaches its size limit
+struct RotatingFile {
+    config: TapFile,
+    file: Option<File>,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn new(config: TapFile) -> Self {
+        Self {
+            config,
+            file: None,
+            size: 0,
+        }
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        if self.file.is_none() {
+            let file = open_trace(&self.config.path)?;
+            self.size = file.metadata()?.len();
+            self.file = Some(file);
+        }
+        if self.size > 0 && self.size + line.len() as u64 > self.config.max_bytes {
+            self.rotate()?;
+        }
+        let file = self.file.as_mut().expect("opened above");
+        file.write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shift `path.N` to `path.N+1`, dropping the oldest, and start a new file
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file = None;
+        let path = &self.config.path;
+        let kept = self.config.max_files.saturating_sub(1);
+        if kept == 0 {
+            remove_if_exists(path)?;
+        } else {
+            remove_if_exists(&rotated(path, kept))?;
+            for n in (1..kept).rev() {
+                rename_if_exists(&rotated(path, n), &rotated(path, n + 1))?;
+            }
+            rename_if_exists(path, &rotated(path, 1))?;
+        }
+        self.file = Some(open_trace(path)?);
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Open a trace file for appending; traces hold prompts and tool I/O, so a new
+/// file is readable only by its owner
+fn open_trace(path: &Path) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn rename_if_exists(from: &Path, to: &Path) -> std::io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_masks_prompts_and_tool_inputs() {
+        let mut frame = json!({
+            "type": "user",
+            "message": {"role": "user", "content": [
+                {"type": "text", "text": "deploy to prod"},
+                {"type": "image", "source": {}}
+            ]}
+        });
+        mask_prompts(&mut frame);
+        assert_eq!(frame["message"]["content"][0]["text"], REDACTED);
+        assert_eq!(frame["message"]["content"][1]["type"], "image");
+
+        let mut frame = json!({
+            "type": "assistant",
+            "message": {"content": [
+                {"type": "text", "text": "Running it"},
+                {"type": "tool_use", "id": "t1", "name": "Bash", "input": {"command": "ls"}}
+            ]}
+        });
+        mask_tool_inputs(&mut frame);
+        assert_eq!(frame["message"]["content"][0]["text"], "Running it");
+        assert_eq!(frame["message"]["content"][1]["input"], REDACTED);
+        assert_eq!(frame["message"]["content"][1]["name"], "Bash");
+
+        let mut frame = json!({
+            "type": "control_request",
+            "request_id": "r1",
+            "request": {"subtype": "can_use_tool", "tool_name": "Bash", "input": {"command": "ls"}}
+        });
+        mask_tool_inputs(&mut frame);
+        assert_eq!(frame["request"]["input"], REDACTED);
+        assert_eq!(frame["request"]["tool_name"], "Bash");
+    }
+
+    #[test]
+    fn test_concurrent_frames_are_written_in_seq_order() {
+        let dir = std::env::temp_dir().join(format!("claude-tap-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("trace.jsonl");
+        let tap = ProtocolTap::to_file(TapFile::builder().path(&path).build());
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for n in 0..50 {
+                        tap.record(TapDirection::Sent, json!({"type": "user", "n": n}));
+                    }
+                });
+            }
+        });
+        tap.flush();
+
+        let seqs: Vec<u64> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<TapFrame>(line).unwrap().seq)
+            .collect();
+        assert_eq!(seqs, (0..200).collect::<Vec<_>>());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_rotates_and_keeps_max_files() {
+        let dir = std::env::temp_dir().join(format!("claude-tap-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("trace.jsonl");
+        let tap = ProtocolTap::to_file(
+            TapFile::builder()
+                .path(&path)
+                .max_bytes(200)
+                .max_files(3)
+                .build(),
+        );
+        for n in 0..20 {
+            tap.record(TapDirection::Received, json!({"type": "system", "n": n}));
+        }
+        tap.flush();
+
+        assert!(path.exists());
+        assert!(rotated(&path, 1).exists());
+        assert!(rotated(&path, 2).exists());
+        assert!(!rotated(&path, 3).exists());
+
+        let frames: Vec<TapFrame> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let last = frames.last().unwrap();
+        assert_eq!(last.seq, 19);
+        assert_eq!(last.frame["n"], 19);
+        assert!(
+            frames
+                .windows(2)
+                .all(|w| w[0].elapsed_us <= w[1].elapsed_us)
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for file in [path.clone(), rotated(&path, 1)] {
+                let mode = std::fs::metadata(file).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600);
+            }
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
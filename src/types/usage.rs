@@ -0,0 +1,504 @@
+//! Token usage reported on assistant and result messages
+
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
+
+use crate::types::messages::Message;
+
+/// Token usage for one API response, a turn, or a whole session
+///
+/// Counts the CLI leaves out, or sends as `null`, are zero. A count that isn't a
+/// `u64` (a float, a negative number, a string) is also zero, and the raw value is
+/// kept in [`extra`](Self::extra) under its own key. Fields the SDK doesn't model
+/// are kept in `extra` too; when usage is added up, only the modelled counts are
+/// summed and `extra` keeps the first value of each field.
+///
+/// # Example
+///
+/// ```
+/// use claude_agent_sdk_rs::Usage;
+///
+/// let first: Usage = serde_json::from_str(r#"{"input_tokens": 3, "output_tokens": 17}"#).unwrap();
+/// let second: Usage = serde_json::from_str(r#"{"input_tokens": 5, "cache_read_input_tokens": 900}"#).unwrap();
+///
+/// let total: Usage = [first, second].into_iter().sum();
+/// assert_eq!(total.input_tokens, 8);
+/// assert_eq!(total.total_tokens(), 925);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(remote = "Self")]
+pub struct Usage {
+    /// Input tokens not read from or written to the prompt cache
+    #[serde(default)]
+    pub input_tokens: u64,
+    /// Output tokens
+    #[serde(default)]
+    pub output_tokens: u64,
+    /// Input tokens written to the prompt cache
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    /// Input tokens read from the prompt cache
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+    /// Cache writes split by cache lifetime
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation: Option<CacheCreation>,
+    /// Requests made by server-side tools
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_tool_use: Option<ServerToolUse>,
+    /// Service tier the request ran on, e.g. `standard`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
+    /// Any other fields the CLI returned
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl<'de> Deserialize<'de> for Usage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields: &[Field] = &[
+            ("input_tokens", Value::is_u64),
+            ("output_tokens", Value::is_u64),
+            ("cache_creation_input_tokens", Value::is_u64),
+            ("cache_read_input_tokens", Value::is_u64),
+        ];
+        deserialize_lenient(deserializer, fields, Usage::deserialize, |usage| {
+            &mut usage.extra
+        })
+    }
+}
+
+impl Serialize for Usage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_merged(
+            Usage::serialize(self, serde_json::value::Serializer),
+            serializer,
+        )
+    }
+}
+
+impl Usage {
+    /// All input tokens, cached or not
+    pub fn total_input_tokens(&self) -> u64 {
+        self.input_tokens + self.cache_creation_input_tokens + self.cache_read_input_tokens
+    }
+
+    /// All input and output tokens
+    pub fn total_tokens(&self) -> u64 {
+        self.total_input_tokens() + self.output_tokens
+    }
+
+    /// Sum the usage of the assistant messages in a turn
+    ///
+    /// The CLI sends each content block of a response as its own message, all
+    /// carrying that response's usage, so messages sharing an ID count once.
+    pub fn for_turn<'a>(messages: impl IntoIterator<Item = &'a Message>) -> Self {
+        let mut seen = HashSet::new();
+        messages
+            .into_iter()
+            .filter_map(|message| match message {
+                Message::Assistant(assistant) => Some(&assistant.message),
+                _ => None,
+            })
+            .filter(|inner| inner.id.as_ref().is_none_or(|id| seen.insert(id)))
+            .filter_map(|inner| inner.usage.as_ref())
+            .sum()
+    }
+
+    /// Sum the usage of the result messages in a session, one per turn
+    pub fn for_session<'a>(messages: impl IntoIterator<Item = &'a Message>) -> Self {
+        messages
+            .into_iter()
+            .filter_map(|message| match message {
+                Message::Result(result) => result.usage.as_ref(),
+                _ => None,
+            })
+            .sum()
+    }
+}
+
+impl AddAssign<&Usage> for Usage {
+    fn add_assign(&mut self, other: &Usage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+        add_optional(&mut self.cache_creation, &other.cache_creation);
+        add_optional(&mut self.server_tool_use, &other.server_tool_use);
+        if self.service_tier.is_none() {
+            self.service_tier.clone_from(&other.service_tier);
+        }
+        add_extra(&mut self.extra, &other.extra);
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        *self += &other;
+    }
+}
+
+impl Add for Usage {
+    type Output = Usage;
+
+    fn add(mut self, other: Usage) -> Usage {
+        self += &other;
+        self
+    }
+}
+
+impl Sum for Usage {
+    fn sum<I: Iterator<Item = Usage>>(iter: I) -> Self {
+        iter.fold(Usage::default(), Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Usage> for Usage {
+    fn sum<I: Iterator<Item = &'a Usage>>(iter: I) -> Self {
+        iter.fold(Usage::default(), |mut total, usage| {
+            total += usage;
+            total
+        })
+    }
+}
+
+/// Prompt cache writes by cache lifetime
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(remote = "Self")]
+pub struct CacheCreation {
+    /// Tokens written with the 5 minute lifetime
+    #[serde(default)]
+    pub ephemeral_5m_input_tokens: u64,
+    /// Tokens written with the 1 hour lifetime
+    #[serde(default)]
+    pub ephemeral_1h_input_tokens: u64,
+    /// Any other fields the CLI returned
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl<'de> Deserialize<'de> for CacheCreation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields: &[Field] = &[
+            (
+                "ephemeral_5m_input_tokens",
+                Value::is_u64 as fn(&Value) -> bool,
+            ),
+            ("ephemeral_1h_input_tokens", Value::is_u64),
+        ];
+        deserialize_lenient(deserializer, fields, CacheCreation::deserialize, |cache| {
+            &mut cache.extra
+        })
+    }
+}
+
+impl Serialize for CacheCreation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_merged(
+            CacheCreation::serialize(self, serde_json::value::Serializer),
+            serializer,
+        )
+    }
+}
+
+impl AddAssign<&CacheCreation> for CacheCreation {
+    fn add_assign(&mut self, other: &CacheCreation) {
+        self.ephemeral_5m_input_tokens += other.ephemeral_5m_input_tokens;
+        self.ephemeral_1h_input_tokens += other.ephemeral_1h_input_tokens;
+        add_extra(&mut self.extra, &other.extra);
+    }
+}
+
+/// Requests made by server-side tools such as web search
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(remote = "Self")]
+pub struct ServerToolUse {
+    /// Web searches run
+    #[serde(default)]
+    pub web_search_requests: u64,
+    /// Any other fields the CLI returned, e.g. other tools' request counts
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl<'de> Deserialize<'de> for ServerToolUse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields: &[Field] = &[("web_search_requests", Value::is_u64 as fn(&Value) -> bool)];
+        deserialize_lenient(deserializer, fields, ServerToolUse::deserialize, |tools| {
+            &mut tools.extra
+        })
+    }
+}
+
+impl Serialize for ServerToolUse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_merged(
+            ServerToolUse::serialize(self, serde_json::value::Serializer),
+            serializer,
+        )
+    }
+}
+
+impl AddAssign<&ServerToolUse> for ServerToolUse {
+    fn add_assign(&mut self, other: &ServerToolUse) {
+        self.web_search_requests += other.web_search_requests;
+        add_extra(&mut self.extra, &other.extra);
+    }
+}
+
+/// Usage and cost for one model, from [`ResultMessage::model_usage`](crate::ResultMessage::model_usage)
+///
+/// Subagents and background tasks may run on a different model than the main
+/// conversation, so a turn can report several of these. Invalid values are
+/// handled as on [`Usage`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(remote = "Self", rename_all = "camelCase")]
+pub struct ModelUsage {
+    /// Input tokens not read from or written to the prompt cache
+    #[serde(default)]
+    pub input_tokens: u64,
+    /// Output tokens
+    #[serde(default)]
+    pub output_tokens: u64,
+    /// Input tokens written to the prompt cache
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    /// Input tokens read from the prompt cache
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+    /// Web searches run
+    #[serde(default)]
+    pub web_search_requests: u64,
+    /// Cost in USD
+    #[serde(default, rename = "costUSD")]
+    pub cost_usd: f64,
+    /// Context window of the model, in tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u64>,
+    /// Any other fields the CLI returned
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl<'de> Deserialize<'de> for ModelUsage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields: &[Field] = &[
+            ("inputTokens", Value::is_u64),
+            ("outputTokens", Value::is_u64),
+            ("cacheCreationInputTokens", Value::is_u64),
+            ("cacheReadInputTokens", Value::is_u64),
+            ("webSearchRequests", Value::is_u64),
+            ("costUSD", Value::is_number),
+            ("contextWindow", Value::is_u64),
+        ];
+        deserialize_lenient(deserializer, fields, ModelUsage::deserialize, |usage| {
+            &mut usage.extra
+        })
+    }
+}
+
+impl Serialize for ModelUsage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_merged(
+            ModelUsage::serialize(self, serde_json::value::Serializer),
+            serializer,
+        )
+    }
+}
+
+impl ModelUsage {
+    /// All input and output tokens
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens
+            + self.cache_creation_input_tokens
+            + self.cache_read_input_tokens
+            + self.output_tokens
+    }
+}
+
+impl AddAssign<&ModelUsage> for ModelUsage {
+    fn add_assign(&mut self, other: &ModelUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+        self.web_search_requests += other.web_search_requests;
+        self.cost_usd += other.cost_usd;
+        self.context_window = self.context_window.or(other.context_window);
+        add_extra(&mut self.extra, &other.extra);
+    }
+}
+
+/// A modelled field's wire name and a check that its value is usable
+type Field = (&'static str, fn(&Value) -> bool);
+
+/// Deserialize a JSON object into `T`, dropping modelled fields whose value fails `valid`
+///
+/// Dropped fields take their default. Their raw value, unless `null`, is put back
+/// into `T`'s `extra` map under the same key.
+fn deserialize_lenient<'de, D: Deserializer<'de>, T>(
+    deserializer: D,
+    fields: &[Field],
+    from_value: fn(Value) -> serde_json::Result<T>,
+    extra: fn(&mut T) -> &mut Map<String, Value>,
+) -> Result<T, D::Error> {
+    let mut object = Map::deserialize(deserializer)?;
+    let mut invalid = Vec::new();
+    for (key, valid) in fields {
+        if object.get(*key).is_some_and(|value| !valid(value))
+            && let Some(value) = object.remove(*key)
+            && !value.is_null()
+        {
+            invalid.push((key.to_string(), value));
+        }
+    }
+    let mut parsed = from_value(Value::Object(object)).map_err(D::Error::custom)?;
+    extra(&mut parsed).extend(invalid);
+    Ok(parsed)
+}
+
+/// Serialize through a JSON object, so a raw value kept in `extra` replaces the
+/// zero its modelled field holds rather than duplicating the key
+fn serialize_merged<S: Serializer>(
+    value: serde_json::Result<Value>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    value.map_err(S::Error::custom)?.serialize(serializer)
+}
+
+/// Treat a `null` per-model usage map as empty
+pub(crate) fn null_as_empty<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, ModelUsage>, D::Error> {
+    Ok(Option::deserialize(deserializer)?.unwrap_or_default())
+}
+
+fn add_optional<T: Default + for<'a> AddAssign<&'a T>>(total: &mut Option<T>, other: &Option<T>) {
+    if let Some(other) = other {
+        *total.get_or_insert_with(T::default) += other;
+    }
+}
+
+/// Keep the first value of each unmodelled field
+///
+/// What an unknown field means isn't known, so it isn't summed.
+fn add_extra(total: &mut Map<String, Value>, other: &Map<String, Value>) {
+    for (key, value) in other {
+        if !total.contains_key(key) {
+            total.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::message_parser::MessageParser;
+    use serde_json::json;
+
+    #[test]
+    fn test_usage_sums_known_fields_and_keeps_first_extra() {
+        let usage: Usage = serde_json::from_value(json!({
+            "input_tokens": 3,
+            "output_tokens": null,
+            "cache_read_input_tokens": 100,
+            "cache_creation": {"ephemeral_5m_input_tokens": 10},
+            "server_tool_use": {"web_search_requests": 1, "web_fetch_requests": 2},
+            "service_tier": "standard",
+            "thinking_tokens": 7
+        }))
+        .unwrap();
+        assert_eq!(usage.output_tokens, 0);
+        assert_eq!(usage.extra["thinking_tokens"], 7);
+
+        let total = usage.clone() + usage;
+        assert_eq!(total.input_tokens, 6);
+        assert_eq!(total.total_tokens(), 206);
+        assert_eq!(total.cache_creation.unwrap().ephemeral_5m_input_tokens, 20);
+        let server_tools = total.server_tool_use.unwrap();
+        assert_eq!(server_tools.web_search_requests, 2);
+        assert_eq!(server_tools.extra["web_fetch_requests"], 2);
+        assert_eq!(total.service_tier.as_deref(), Some("standard"));
+        assert_eq!(total.extra["thinking_tokens"], 7);
+    }
+
+    #[test]
+    fn test_invalid_counts_are_zero_and_kept_in_extra() {
+        let usage: Usage = serde_json::from_str(
+            r#"{
+                "input_tokens": 1.5,
+                "output_tokens": -3,
+                "cache_read_input_tokens": 18446744073709551616,
+                "cache_creation_input_tokens": null
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(usage.total_tokens(), 0);
+        assert_eq!(usage.extra["input_tokens"], 1.5);
+        assert_eq!(usage.extra["output_tokens"], -3);
+        assert!(usage.extra["cache_read_input_tokens"].is_f64());
+        assert!(!usage.extra.contains_key("cache_creation_input_tokens"));
+
+        // The raw value replaces the zero when written back out
+        let value = serde_json::to_value(&usage).unwrap();
+        assert_eq!(value["input_tokens"], 1.5);
+        assert_eq!(value["cache_creation_input_tokens"], 0);
+        assert_eq!(serde_json::from_value::<Usage>(value).unwrap(), usage);
+    }
+
+    #[test]
+    fn test_result_with_null_cost_still_parses() {
+        let message = MessageParser::parse(json!({
+            "type": "result",
+            "subtype": "success",
+            "duration_ms": 10,
+            "duration_api_ms": 8,
+            "is_error": false,
+            "num_turns": 1,
+            "session_id": "session",
+            "usage": {"input_tokens": 3, "output_tokens": "n/a"},
+            "modelUsage": {
+                "claude-sonnet-4-5": {"inputTokens": 3, "costUSD": null, "contextWindow": -1}
+            }
+        }))
+        .unwrap();
+        let Message::Result(result) = message else {
+            panic!("expected a result message");
+        };
+        assert_eq!(result.usage.unwrap().input_tokens, 3);
+        let model = &result.model_usage["claude-sonnet-4-5"];
+        assert_eq!(model.input_tokens, 3);
+        assert_eq!(model.cost_usd, 0.0);
+        assert_eq!(model.context_window, None);
+        assert_eq!(model.extra["contextWindow"], -1);
+    }
+
+    #[test]
+    fn test_turn_usage_counts_each_response_once() {
+        let assistant = |id: &str, output_tokens: u64| {
+            serde_json::from_value::<Message>(json!({
+                "type": "assistant",
+                "message": {
+                    "id": id,
+                    "content": [{"type": "text", "text": "hi"}],
+                    "usage": {"input_tokens": 1, "output_tokens": output_tokens}
+                }
+            }))
+            .unwrap()
+        };
+        let messages = [
+            assistant("msg_1", 5),
+            assistant("msg_1", 5),
+            assistant("msg_2", 3),
+        ];
+
+        let usage = Usage::for_turn(&messages);
+        assert_eq!(usage.input_tokens, 2);
+        assert_eq!(usage.output_tokens, 8);
+    }
+}
@@ -32,14 +32,7 @@ pub fn get_claude_code_version() -> Option<&'static str> {
                 .output()
                 .ok()
                 .filter(|output| output.status.success())
-                .and_then(|output| {
-                    let version_output = String::from_utf8_lossy(&output.stdout);
-                    version_output
-                        .lines()
-                        .next()
-                        .and_then(|line| line.split_whitespace().next())
-                        .map(|v| v.trim().to_string())
-                })
+                .and_then(|output| parse_version_output(&String::from_utf8_lossy(&output.stdout)))
         })
         .as_deref()
 }
@@ -67,33 +60,131 @@ pub fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
     Some((major, minor, patch))
 }
 
+/// Extract the version from `claude --version` output (e.g. `2.0.30 (Claude Code)`)
+pub(crate) fn parse_version_output(output: &str) -> Option<String> {
+    output
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().next())
+        .map(|v| v.trim().to_string())
+}
+
 /// Check if the CLI version meets the minimum requirement
 pub fn check_version(cli_version: &str) -> bool {
-    let Some((cli_maj, cli_min, cli_patch)) = parse_version(cli_version) else {
-        return false;
-    };
-
-    let Some((req_maj, req_min, req_patch)) = parse_version(MIN_CLI_VERSION) else {
-        return false;
-    };
+    version_at_least(cli_version, MIN_CLI_VERSION)
+}
 
-    if cli_maj > req_maj {
-        return true;
-    }
-    if cli_maj < req_maj {
-        return false;
+/// Whether `version` is at least `minimum`; unparseable versions never are
+pub(crate) fn version_at_least(version: &str, minimum: &str) -> bool {
+    match (parse_version(version), parse_version(minimum)) {
+        (Some(version), Some(minimum)) => version >= minimum,
+        _ => false,
     }
+}
+
+/// A semver-style version requirement such as `>=2.0.30, <3`
+///
+/// Comma-separated comparators must all match. Supported operators are `=`, `>`,
+/// `>=`, `<`, `<=`, `~` (same minor) and `^` (same major, the default for a bare
+/// version). Missing minor and patch components default to zero.
+///
+/// # Example
+///
+/// ```
+/// use claude_agent_sdk_rs::version::VersionRequirement;
+///
+/// let requirement: VersionRequirement = ">=2.0.30, <3".parse().unwrap();
+/// assert!(requirement.matches("2.1.4"));
+/// assert!(!requirement.matches("3.0.0"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionRequirement {
+    source: String,
+    comparators: Vec<(Comparison, (u32, u32, u32))>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Tilde,
+    Caret,
+}
 
-    // Major versions are equal
-    if cli_min > req_min {
-        return true;
+impl VersionRequirement {
+    /// Whether `version` (e.g. `2.0.30`) satisfies every comparator
+    ///
+    /// Versions that cannot be parsed never match.
+    pub fn matches(&self, version: &str) -> bool {
+        let Some(version) = parse_version(version) else {
+            return false;
+        };
+        self.comparators.iter().all(|(op, bound)| match op {
+            Comparison::Exact => version == *bound,
+            Comparison::Greater => version > *bound,
+            Comparison::GreaterEq => version >= *bound,
+            Comparison::Less => version < *bound,
+            Comparison::LessEq => version <= *bound,
+            Comparison::Tilde => version >= *bound && version < (bound.0, bound.1 + 1, 0),
+            Comparison::Caret => version >= *bound && version < (bound.0 + 1, 0, 0),
+        })
     }
-    if cli_min < req_min {
-        return false;
+}
+
+impl std::str::FromStr for VersionRequirement {
+    type Err = crate::errors::ClaudeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            crate::errors::ClaudeError::InvalidConfig(format!(
+                "Invalid version requirement: {:?}",
+                s
+            ))
+        };
+
+        let mut comparators = Vec::new();
+        for part in s.split(',') {
+            let part = part.trim();
+            let (op, rest) = [
+                (">=", Comparison::GreaterEq),
+                ("<=", Comparison::LessEq),
+                (">", Comparison::Greater),
+                ("<", Comparison::Less),
+                ("=", Comparison::Exact),
+                ("~", Comparison::Tilde),
+                ("^", Comparison::Caret),
+            ]
+            .iter()
+            .find_map(|(prefix, op)| part.strip_prefix(prefix).map(|rest| (*op, rest)))
+            .unwrap_or((Comparison::Caret, part));
+
+            let mut numbers = rest.trim().trim_start_matches('v').split('.');
+            let mut next = |required: bool| match numbers.next() {
+                Some(n) => n.parse::<u32>().map_err(|_| invalid()),
+                None if required => Err(invalid()),
+                None => Ok(0),
+            };
+            let bound = (next(true)?, next(false)?, next(false)?);
+            if numbers.next().is_some() {
+                return Err(invalid());
+            }
+            comparators.push((op, bound));
+        }
+
+        Ok(Self {
+            source: s.trim().to_string(),
+            comparators,
+        })
     }
+}
 
-    // Major and minor are equal
-    cli_patch >= req_patch
+impl std::fmt::Display for VersionRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
 }
 
 #[cfg(test)]
@@ -118,4 +209,34 @@ mod tests {
         assert!(!check_version("1.9.9"));
         assert!(!check_version("1.99.99"));
     }
+
+    #[test]
+    fn test_version_requirement_range() {
+        let requirement: VersionRequirement = ">=2.0.30, <3".parse().unwrap();
+        assert!(requirement.matches("2.0.30"));
+        assert!(requirement.matches("2.9.0"));
+        assert!(!requirement.matches("2.0.29"));
+        assert!(!requirement.matches("3.0.0"));
+        assert!(!requirement.matches("not-a-version"));
+        assert_eq!(requirement.to_string(), ">=2.0.30, <3");
+    }
+
+    #[test]
+    fn test_version_requirement_caret_and_tilde() {
+        let caret: VersionRequirement = "2.1".parse().unwrap();
+        assert!(caret.matches("2.5.0"));
+        assert!(!caret.matches("2.0.9"));
+        assert!(!caret.matches("3.0.0"));
+
+        let tilde: VersionRequirement = "~2.1.3".parse().unwrap();
+        assert!(tilde.matches("2.1.9"));
+        assert!(!tilde.matches("2.2.0"));
+    }
+
+    #[test]
+    fn test_version_requirement_rejects_garbage() {
+        assert!("".parse::<VersionRequirement>().is_err());
+        assert!(">=two".parse::<VersionRequirement>().is_err());
+        assert!("1.2.3.4".parse::<VersionRequirement>().is_err());
+    }
 }
@@ -3,11 +3,14 @@
 //! These tests validate the ClaudeClient behavior without requiring the Claude Code CLI.
 //! They use the mock framework to simulate CLI communication.
 
+use claude_agent_sdk_rs::errors::QueueOverflowError;
 use claude_agent_sdk_rs::testing::{
     AssistantMessageBuilder, MockClient, MockTransport, ResultMessageBuilder, ScenarioBuilder,
     SystemMessageBuilder, Transport, timing_profiles,
 };
-use claude_agent_sdk_rs::{ClaudeAgentOptions, Message, PermissionMode};
+use claude_agent_sdk_rs::{
+    ClaudeAgentOptions, ClaudeError, Message, OverflowPolicy, PermissionMode,
+};
 use futures::StreamExt;
 use std::time::Duration;
 
@@ -702,3 +705,97 @@ async fn test_scenario_with_trigger_pattern() {
 
     client.disconnect().await.unwrap();
 }
+
+// =============================================================================
+// Message Queue Tests
+// =============================================================================
+
+fn stream_event(index: usize) -> serde_json::Value {
+    serde_json::json!({
+        "type": "stream_event",
+        "uuid": format!("event-{}", index),
+        "session_id": "test-session",
+        "event": {"type": "content_block_delta"}
+    })
+}
+
+#[tokio::test]
+async fn test_client_queue_drops_stream_events_when_full() {
+    let mut builder = MockTransport::builder();
+    for index in 0..5 {
+        builder = builder.message(stream_event(index));
+    }
+    let transport = builder
+        .message(serde_json::to_value(ResultMessageBuilder::default().build()).unwrap())
+        .build();
+    let options = ClaudeAgentOptions::builder()
+        .message_queue_capacity(2)
+        .overflow_policy(OverflowPolicy::DropStreamEvents)
+        .build();
+
+    let mut client = MockClient::from_transport(transport, options);
+    client.connect_with_transport().await.unwrap();
+
+    // The result message waits for room instead of being dropped
+    tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            let metrics = client.queue_metrics().unwrap();
+            if metrics.blocked_sends > 0 && metrics.dropped_stream_events == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("reader should block on the result message");
+
+    // Two stream events in the queue and the result waiting behind them
+    let metrics = client.queue_metrics().unwrap();
+    assert_eq!(metrics.capacity, Some(2));
+    assert_eq!(metrics.depth, 3);
+
+    let messages: Vec<_> = tokio::time::timeout(
+        Duration::from_secs(2),
+        client.receive_response().collect::<Vec<_>>(),
+    )
+    .await
+    .expect("Should receive response");
+    assert_eq!(messages.len(), 3);
+    assert!(matches!(messages[2], Ok(Message::Result(_))));
+
+    client.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_client_queue_overflow_fails_stream() {
+    let mut builder = MockTransport::builder();
+    for index in 0..3 {
+        builder = builder.message(stream_event(index));
+    }
+    let options = ClaudeAgentOptions::builder()
+        .message_queue_capacity(1)
+        .overflow_policy(OverflowPolicy::Fail)
+        .build();
+
+    let mut client = MockClient::from_transport(builder.build(), options);
+    client.connect_with_transport().await.unwrap();
+
+    let messages: Vec<_> = tokio::time::timeout(
+        Duration::from_secs(2),
+        client.client().receive_messages().collect::<Vec<_>>(),
+    )
+    .await
+    .expect("stream should end after the overflow error");
+
+    assert_eq!(messages.len(), 2);
+    assert!(matches!(messages[0], Ok(Message::StreamEvent(_))));
+    assert!(matches!(
+        messages[1],
+        Err(ClaudeError::QueueOverflow(QueueOverflowError {
+            capacity: 1
+        }))
+    ));
+    assert!(client.queue_metrics().unwrap().overflowed);
+
+    client.disconnect().await.unwrap();
+}
@@ -0,0 +1,115 @@
+//! Fake CLI scripts shared by the integration tests
+//!
+//! Each test binary uses a different subset of these helpers.
+
+#![cfg(unix)]
+#![allow(dead_code)]
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Create an empty directory under the system temp dir
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("claude-{}-{}", name, uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Write `script` to an executable `claude` in `dir` and return its path
+pub fn write_cli(dir: &Path, script: &str) -> PathBuf {
+    let path = dir.join("claude");
+    std::fs::write(&path, script).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+/// Write a [`FakeCli`] running `on_user` to a new temp dir; returns the dir and the script
+pub fn fake_cli(name: &str, on_user: &str) -> (PathBuf, PathBuf) {
+    FakeCli::new(on_user).write(name)
+}
+
+/// A shell script standing in for the CLI
+///
+/// It answers `initialize`, runs `on_user` for each user message and ignores
+/// any other input.
+pub struct FakeCli {
+    setup: String,
+    initialize_response: String,
+    on_line: String,
+    on_user: String,
+    after: String,
+}
+
+impl FakeCli {
+    pub fn new(on_user: &str) -> Self {
+        Self {
+            setup: String::new(),
+            initialize_response: "{}".to_string(),
+            on_line: String::new(),
+            on_user: on_user.to_string(),
+            after: String::new(),
+        }
+    }
+
+    /// Shell run before reading stdin
+    pub fn setup(mut self, setup: &str) -> Self {
+        self.setup = setup.to_string();
+        self
+    }
+
+    /// JSON sent back for `initialize`, spliced into a single-quoted `printf` format
+    pub fn initialize_response(mut self, response: &str) -> Self {
+        self.initialize_response = response.to_string();
+        self
+    }
+
+    /// Shell run for every line read, as `$line`, before it is handled
+    pub fn on_line(mut self, on_line: &str) -> Self {
+        self.on_line = on_line.to_string();
+        self
+    }
+
+    /// Shell run once stdin is closed
+    pub fn after(mut self, after: &str) -> Self {
+        self.after = after.to_string();
+        self
+    }
+
+    pub fn script(&self) -> String {
+        format!(
+            r#"#!/bin/sh
+{setup}
+while IFS= read -r line; do
+{on_line}
+  case "$line" in
+    *'"subtype":"initialize"'*)
+      id=$(printf '%s' "$line" | sed 's/.*"request_id":"\([^"]*\)".*/\1/')
+      printf '{{"type":"control_response","response":{{"subtype":"success","request_id":"%s","response":{response}}}}}\n' "$id"
+      ;;
+    *'"type":"user"'*)
+{on_user}
+      ;;
+  esac
+done
+{after}
+"#,
+            setup = self.setup,
+            on_line = self.on_line,
+            response = self.initialize_response,
+            on_user = self.on_user,
+            after = self.after,
+        )
+    }
+
+    /// Write the script to an executable `claude` in `dir` and return its path
+    pub fn write_to(&self, dir: &Path) -> PathBuf {
+        write_cli(dir, &self.script())
+    }
+
+    /// Write the script to a new temp dir; returns the dir and the script's path
+    pub fn write(&self, name: &str) -> (PathBuf, PathBuf) {
+        let dir = temp_dir(name);
+        let path = self.write_to(&dir);
+        (dir, path)
+    }
+}
@@ -333,8 +333,8 @@ async fn test_get_server_info() -> anyhow::Result<()> {
     if let Some(server_info) = info {
         // Should have some expected fields
         assert!(
-            server_info.is_object(),
-            "Server info should be a JSON object"
+            !server_info.commands.is_empty(),
+            "Server info should list slash commands"
         );
     }
 
@@ -380,17 +380,35 @@ fn test_usage_statistics_structure() {
     let msg = load_fixture("result_001.json");
     match msg {
         Message::Result(result) => {
-            assert!(result.usage.is_some());
             let usage = result.usage.as_ref().unwrap();
 
             // Should have token counts
-            assert!(usage["input_tokens"].is_number());
-            assert!(usage["output_tokens"].is_number());
+            assert_eq!(usage.input_tokens, 3);
+            assert_eq!(usage.output_tokens, 17);
 
-            // May have cache stats
-            if usage["cache_read_input_tokens"].is_number() {
-                assert!(usage["cache_read_input_tokens"].as_u64().is_some());
-            }
+            // Cache stats and server tool use
+            assert_eq!(usage.cache_read_input_tokens, 14422);
+            assert_eq!(usage.cache_creation_input_tokens, 333);
+            assert_eq!(
+                usage
+                    .cache_creation
+                    .as_ref()
+                    .unwrap()
+                    .ephemeral_5m_input_tokens,
+                333
+            );
+            assert_eq!(
+                usage.server_tool_use.as_ref().unwrap().web_search_requests,
+                0
+            );
+            assert_eq!(usage.service_tier.as_deref(), Some("standard"));
+
+            // Per-model usage covers the main model and any helper models
+            let sonnet = &result.model_usage["claude-sonnet-4-5-20250929"];
+            assert_eq!(sonnet.output_tokens, 17);
+            assert_eq!(sonnet.context_window, Some(200000));
+            let cost: f64 = result.model_usage.values().map(|m| m.cost_usd).sum();
+            assert!((cost - result.total_cost_usd.unwrap()).abs() < 1e-9);
         }
         _ => panic!("Expected Result message"),
     }
@@ -0,0 +1,427 @@
+//! Tests for driving a CLI through the socket relay
+//!
+//! The relay forwards a scripted fake CLI over a Unix socket, and the client
+//! connects with `SocketTransport`, so control requests make the full round trip.
+
+#![cfg(unix)]
+
+use async_trait::async_trait;
+use claude_agent_sdk_rs::relay::{RelayConfig, RelayServer};
+use claude_agent_sdk_rs::{
+    ClaudeAgentOptions, ClaudeClient, ContentBlock, Message, PermissionResult,
+    PermissionResultDeny, ReconnectPolicy, RelayEndpoint, Result, SocketTransport,
+    SocketTransportConfig, Transport,
+};
+use futures::StreamExt;
+use futures::stream::Stream;
+use serde_json::json;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A fake CLI that asks for tool permission before answering a user message
+struct ScriptedCli {
+    tx: Mutex<Option<flume::Sender<serde_json::Value>>>,
+    rx: flume::Receiver<serde_json::Value>,
+    ready: AtomicBool,
+    /// Version reported to the relay, if any
+    cli_version: Option<String>,
+    /// Every line the relay wrote to the CLI
+    written: Arc<Mutex<Vec<String>>>,
+}
+
+impl ScriptedCli {
+    fn new() -> Self {
+        let (tx, rx) = flume::unbounded();
+        Self {
+            tx: Mutex::new(Some(tx)),
+            rx,
+            ready: AtomicBool::new(false),
+            cli_version: None,
+            written: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    fn emit(&self, value: serde_json::Value) {
+        if let Some(tx) = self.tx.lock().unwrap().as_ref() {
+            let _ = tx.send(value);
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for ScriptedCli {
+    async fn connect(&self) -> Result<()> {
+        self.ready.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn write(&self, data: &str) -> Result<()> {
+        self.written.lock().unwrap().push(data.to_string());
+        let value: serde_json::Value = serde_json::from_str(data).unwrap();
+        match value["type"].as_str() {
+            Some("control_request") => self.emit(json!({
+                "type": "control_response",
+                "response": {
+                    "subtype": "success",
+                    "request_id": value["request_id"],
+                    "response": {"commands": []}
+                }
+            })),
+            Some("user") => self.emit(json!({
+                "type": "control_request",
+                "request_id": "perm_1",
+                "request": {
+                    "subtype": "can_use_tool",
+                    "tool_name": "Bash",
+                    "input": {"command": "rm -rf /"}
+                }
+            })),
+            Some("control_response") => {
+                let response = &value["response"];
+                assert_eq!(response["request_id"], "perm_1");
+                let behavior = response["response"]["behavior"]
+                    .as_str()
+                    .unwrap()
+                    .to_string();
+                self.emit(json!({
+                    "type": "assistant",
+                    "message": {"content": [{"type": "text", "text": behavior}], "model": "test"},
+                    "session_id": "relay"
+                }));
+                self.emit(json!({
+                    "type": "result",
+                    "subtype": "success",
+                    "duration_ms": 1,
+                    "duration_api_ms": 1,
+                    "is_error": false,
+                    "num_turns": 1,
+                    "session_id": "relay"
+                }));
+                // The CLI exits after its only turn
+                self.tx.lock().unwrap().take();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn read_messages(&self) -> Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send + '_>> {
+        Box::pin(self.rx.stream().map(Ok))
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.ready.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+
+    fn cli_version(&self) -> Option<String> {
+        self.cli_version.clone()
+    }
+
+    async fn end_input(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Start a relay for a fresh fake CLI and wait until it accepts connections
+async fn start_relay(token: &str) -> RelayEndpoint {
+    start_relay_for(ScriptedCli::new(), token).await
+}
+
+async fn start_relay_for(cli: ScriptedCli, token: &str) -> RelayEndpoint {
+    start_relay_with(cli, RelayConfig::builder().auth_token(token).build()).await
+}
+
+async fn start_relay_with(cli: ScriptedCli, config: RelayConfig) -> RelayEndpoint {
+    let path = std::env::temp_dir().join(format!("claude-relay-{}.sock", uuid::Uuid::new_v4()));
+    let endpoint = RelayEndpoint::Unix(path.clone());
+
+    let server = RelayServer::with_transport(Arc::new(cli), config);
+    let serve_endpoint = endpoint.clone();
+    tokio::spawn(async move { server.serve(&serve_endpoint).await });
+
+    wait_for_socket(&path).await;
+    endpoint
+}
+
+async fn wait_for_socket(path: &Path) {
+    for _ in 0..100 {
+        if path.exists() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("relay did not start listening on {}", path.display());
+}
+
+#[tokio::test]
+async fn test_relay_forwards_can_use_tool_round_trip() {
+    let endpoint = start_relay("secret").await;
+    let transport = SocketTransport::new(
+        endpoint,
+        SocketTransportConfig::builder()
+            .auth_token("secret")
+            .build(),
+    );
+
+    let options = ClaudeAgentOptions::builder()
+        .can_use_tool(Arc::new(|_tool, _input, _context| {
+            Box::pin(async {
+                PermissionResult::Deny(PermissionResultDeny {
+                    message: "not over the relay".to_string(),
+                    interrupt: false,
+                })
+            }) as futures::future::BoxFuture<'static, PermissionResult>
+        }) as claude_agent_sdk_rs::CanUseToolCallback)
+        .build();
+    let mut client = ClaudeClient::with_transport(Arc::new(transport), options);
+
+    tokio::time::timeout(Duration::from_secs(5), client.connect())
+        .await
+        .expect("initialize should complete across the relay")
+        .unwrap();
+
+    client.query("clean up").await.unwrap();
+    let messages: Vec<_> = tokio::time::timeout(
+        Duration::from_secs(5),
+        client.receive_response().collect::<Vec<_>>(),
+    )
+    .await
+    .expect("should receive response across the relay");
+
+    assert_eq!(messages.len(), 2);
+    match &messages[0] {
+        Ok(Message::Assistant(msg)) => match &msg.message.content[0] {
+            ContentBlock::Text(text) => assert_eq!(text.text, "deny"),
+            other => panic!("unexpected content block: {:?}", other),
+        },
+        other => panic!("expected assistant message, got {:?}", other),
+    }
+    assert!(matches!(messages[1], Ok(Message::Result(_))));
+
+    client.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_relay_reports_cli_version_for_capability_checks() {
+    use claude_agent_sdk_rs::{Capability, ClaudeError, SdkPluginConfig};
+
+    let cli = ScriptedCli {
+        cli_version: Some("2.0.10".to_string()),
+        ..ScriptedCli::new()
+    };
+    let endpoint = start_relay_for(cli, "secret").await;
+    let transport = SocketTransport::new(
+        endpoint,
+        SocketTransportConfig::builder()
+            .auth_token("secret")
+            .build(),
+    );
+
+    let options = ClaudeAgentOptions::builder()
+        .plugins(vec![SdkPluginConfig::local("./plugins/demo")])
+        .build();
+    let mut client = ClaudeClient::with_transport(Arc::new(transport), options);
+    let err = tokio::time::timeout(Duration::from_secs(5), client.connect())
+        .await
+        .expect("connect should finish across the relay")
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        ClaudeError::UnsupportedCapability(e) if e.capability == Capability::Plugins
+    ));
+}
+
+#[tokio::test]
+async fn test_relay_rejects_invalid_token() {
+    let endpoint = start_relay("secret").await;
+    let transport = SocketTransport::new(
+        endpoint,
+        SocketTransportConfig::builder()
+            .auth_token("wrong")
+            .reconnect(ReconnectPolicy::disabled())
+            .build(),
+    );
+
+    let err = transport.connect().await.unwrap_err();
+    assert!(err.to_string().contains("invalid auth token"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_socket_transport_stream_ends_on_cli_exit() {
+    let endpoint = start_relay("secret").await;
+    let transport = SocketTransport::new(
+        endpoint,
+        SocketTransportConfig::builder()
+            .auth_token("secret")
+            .build(),
+    );
+    transport.connect().await.unwrap();
+
+    transport
+        .write(&json!({"type": "user", "message": {"role": "user", "content": "hi"}}).to_string())
+        .await
+        .unwrap();
+    transport
+        .write(
+            &json!({
+                "type": "control_response",
+                "response": {
+                    "subtype": "success",
+                    "request_id": "perm_1",
+                    "response": {"behavior": "allow"}
+                }
+            })
+            .to_string(),
+        )
+        .await
+        .unwrap();
+
+    let messages: Vec<_> = tokio::time::timeout(
+        Duration::from_secs(5),
+        transport.read_messages().collect::<Vec<_>>(),
+    )
+    .await
+    .expect("stream should end when the CLI exits");
+
+    let types: Vec<_> = messages
+        .iter()
+        .map(|m| m.as_ref().unwrap()["type"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(types, ["control_request", "assistant", "result"]);
+
+    transport.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_socket_transport_reconnects_after_drop() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let path = std::env::temp_dir().join(format!("claude-relay-{}.sock", uuid::Uuid::new_v4()));
+    let listener = tokio::net::UnixListener::bind(&path).unwrap();
+
+    // A bare-bones relay that drops the first connection right after the handshake
+    tokio::spawn(async move {
+        for attempt in 0..2 {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let hello = lines.next_line().await.unwrap().unwrap();
+            assert!(hello.contains("relay_hello"));
+            writer
+                .write_all(b"{\"type\":\"relay_welcome\"}\n")
+                .await
+                .unwrap();
+            if attempt == 1 {
+                writer
+                    .write_all(
+                        b"{\"type\":\"system\",\"subtype\":\"init\"}\n{\"type\":\"relay_exit\"}\n",
+                    )
+                    .await
+                    .unwrap();
+                // Keep the connection open until the client has read everything
+                let _ = lines.next_line().await;
+            }
+        }
+    });
+
+    let transport = SocketTransport::new(
+        RelayEndpoint::Unix(path.clone()),
+        SocketTransportConfig::builder()
+            .reconnect(
+                ReconnectPolicy::builder()
+                    .initial_backoff(Duration::from_millis(10))
+                    .build(),
+            )
+            .build(),
+    );
+    transport.connect().await.unwrap();
+
+    let messages: Vec<_> = tokio::time::timeout(
+        Duration::from_secs(5),
+        transport.read_messages().collect::<Vec<_>>(),
+    )
+    .await
+    .expect("stream should resume on the new connection");
+
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].as_ref().unwrap()["subtype"], "init");
+
+    transport.close().await.unwrap();
+    let _ = std::fs::remove_file(path);
+}
+
+/// Connect to the relay as a bare socket client and complete the handshake
+async fn raw_client(endpoint: &RelayEndpoint, token: &str) -> tokio::net::UnixStream {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let RelayEndpoint::Unix(path) = endpoint else {
+        panic!("expected a Unix endpoint");
+    };
+    let mut stream = tokio::net::UnixStream::connect(path).await.unwrap();
+    let hello = json!({"type": "relay_hello", "version": 1, "token": token});
+    stream
+        .write_all(format!("{}\n", hello).as_bytes())
+        .await
+        .unwrap();
+
+    // Read the welcome a byte at a time so nothing after it is consumed
+    let mut reader = BufReader::with_capacity(1, &mut stream);
+    let mut welcome = String::new();
+    reader.read_line(&mut welcome).await.unwrap();
+    assert!(welcome.contains("relay_welcome"), "got {:?}", welcome);
+    stream
+}
+
+#[tokio::test]
+async fn test_relay_drops_oversized_and_unterminated_client_lines() {
+    use tokio::io::AsyncWriteExt;
+
+    let cli = ScriptedCli::new();
+    let written = Arc::clone(&cli.written);
+    let endpoint = start_relay_with(
+        cli,
+        RelayConfig::builder()
+            .auth_token("secret")
+            .max_buffer_size(1024)
+            .build(),
+    )
+    .await;
+
+    let mut first = raw_client(&endpoint, "secret").await;
+    let oversized = format!("{{\"type\":\"noise\",\"pad\":\"{}\"}}\n", "x".repeat(4096));
+    first.write_all(oversized.as_bytes()).await.unwrap();
+    first.write_all(b"{\"type\":\"first\"}\n").await.unwrap();
+    // A line cut off by the disconnect must not reach the CLI
+    first.write_all(b"{\"type\":\"partial\"").await.unwrap();
+    drop(first);
+
+    let mut second = raw_client(&endpoint, "secret").await;
+    second.write_all(b"{\"type\":\"second\"}\n").await.unwrap();
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while written.lock().unwrap().len() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("relay should forward the complete lines");
+
+    let written: Vec<serde_json::Value> = written
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        written,
+        vec![json!({"type": "first"}), json!({"type": "second"})]
+    );
+}
@@ -0,0 +1,385 @@
+//! Tests for the Landlock sandbox, seccomp filter and network isolation applied to the CLI process
+//!
+//! A shell script stands in for the CLI. It answers `initialize`, then probes
+//! what it can access once it receives a user message, reports the results on
+//! stderr and exits.
+
+#![cfg(target_os = "linux")]
+
+use claude_agent_sdk_rs::{
+    ClaudeAgentOptions, ClaudeClient, LandlockEnforcement, LandlockMode, LandlockSandboxConfig,
+    Message, NetworkIsolation, ProcessExited, SandboxStatus, SeccompAction, SeccompPreset,
+    SeccompProfile,
+};
+use common::{FakeCli, temp_dir};
+use futures::StreamExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+mod common;
+
+/// Write a fake CLI that runs `probe` on the first user message, then exits
+fn write_cli(dir: &Path, probe: &str) -> PathBuf {
+    FakeCli::new(&format!("{}\n      exit 3", probe)).write_to(dir)
+}
+
+/// Run the probe and return what it wrote to stderr
+async fn probe(cli_path: PathBuf, sandbox: LandlockSandboxConfig) -> Vec<String> {
+    let options = ClaudeAgentOptions::builder()
+        .cli_path(cli_path)
+        .skip_version_check(true)
+        .landlock_sandbox(sandbox)
+        .build();
+    let mut client = ClaudeClient::new(options);
+    client.connect().await.unwrap();
+    client.query("probe").await.unwrap();
+
+    let exit = {
+        let mut stream = client.receive_messages();
+        tokio::time::timeout(Duration::from_secs(10), stream.next())
+            .await
+            .expect("should receive the exit report")
+    };
+    client.disconnect().await.unwrap();
+    match exit {
+        Some(Ok(Message::ProcessExited(ProcessExited { stderr_tail, .. }))) => stderr_tail,
+        other => panic!("expected an exit report, got {:?}", other),
+    }
+}
+
+/// Landlock ABI version supported by this kernel, 0 if none
+fn landlock_abi() -> i64 {
+    // SAFETY: a null attr with LANDLOCK_CREATE_RULESET_VERSION only queries the ABI version
+    let abi = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<libc::c_void>(),
+            0usize,
+            1u32,
+        )
+    };
+    abi.max(0)
+}
+
+#[tokio::test]
+async fn test_landlock_restricts_reads_and_writes() {
+    if landlock_abi() < 3 {
+        eprintln!("Landlock ABI v3 is not supported by this kernel, skipping");
+        return;
+    }
+
+    let dir = temp_dir("landlock");
+    for sub in ["work", "outside", "secret"] {
+        std::fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    std::fs::write(dir.join("secret/key"), "hunter2").unwrap();
+    std::fs::write(dir.join("outside/readme"), "public").unwrap();
+
+    let root = dir.display();
+    let cli_path = write_cli(
+        &dir,
+        &format!(
+            r#"      for target in "{root}/work/file" "{root}/outside/file"; do
+        if echo data > "$target"; then echo "write ok $target" >&2; else echo "write denied $target" >&2; fi
+      done
+      for target in "{root}/outside/readme" "{root}/secret/key"; do
+        if cat "$target" >&2; then echo "read ok $target" >&2; else echo "read denied $target" >&2; fi
+      done"#
+        ),
+    );
+
+    let sandbox = LandlockSandboxConfig::builder()
+        .writable_roots(vec![dir.join("work")])
+        .extra_writable_paths(Vec::new())
+        .denied_paths(vec![dir.join("secret")])
+        .build();
+    let stderr = probe(cli_path, sandbox).await.join("\n");
+
+    assert!(stderr.contains(&format!("write ok {}/work/file", root)));
+    assert!(stderr.contains(&format!("write denied {}/outside/file", root)));
+    assert!(stderr.contains(&format!("read ok {}/outside/readme", root)));
+    assert!(stderr.contains(&format!("read denied {}/secret/key", root)));
+    assert!(!stderr.contains("hunter2"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_landlock_without_writable_roots_only_denies_denied_paths() {
+    if landlock_abi() < 3 {
+        eprintln!("Landlock ABI v3 is not supported by this kernel, skipping");
+        return;
+    }
+
+    let dir = temp_dir("landlock-deny");
+    for sub in ["outside", "secret"] {
+        std::fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+
+    let root = dir.display();
+    let cli_path = write_cli(
+        &dir,
+        &format!(
+            r#"      for target in "{root}/outside/file" "{root}/secret/file"; do
+        if echo data > "$target"; then echo "write ok $target" >&2; else echo "write denied $target" >&2; fi
+      done"#
+        ),
+    );
+
+    let sandbox = LandlockSandboxConfig::builder()
+        .denied_paths(vec![dir.join("secret")])
+        .build();
+    let stderr = probe(cli_path, sandbox).await.join("\n");
+
+    assert!(stderr.contains(&format!("write ok {}/outside/file", root)));
+    assert!(stderr.contains(&format!("write denied {}/secret/file", root)));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_landlock_limits_tcp_connect_ports() {
+    if landlock_abi() < 4 {
+        eprintln!("Landlock ABI v4 is not supported by this kernel, skipping");
+        return;
+    }
+
+    let allowed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let blocked = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let allowed_port = allowed.local_addr().unwrap().port();
+    let blocked_port = blocked.local_addr().unwrap().port();
+
+    let dir = temp_dir("landlock-net");
+    let cli_path = write_cli(
+        &dir,
+        &format!(
+            r#"      for port in {allowed_port} {blocked_port}; do
+        if bash -c "exec 3<>/dev/tcp/127.0.0.1/$port"; then echo "connect ok $port" >&2; else echo "connect denied $port" >&2; fi
+      done"#
+        ),
+    );
+
+    let sandbox = LandlockSandboxConfig::builder()
+        .tcp_connect_ports(vec![allowed_port])
+        .mode(LandlockMode::Strict)
+        .build();
+    let stderr = probe(cli_path, sandbox).await.join("\n");
+
+    assert!(stderr.contains(&format!("connect ok {}", allowed_port)));
+    assert!(stderr.contains(&format!("connect denied {}", blocked_port)));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_client_reports_landlock_status() {
+    let dir = temp_dir("landlock-status");
+    let cli_path = write_cli(&dir, "");
+
+    let connect = |sandbox: Option<LandlockSandboxConfig>| {
+        let cli_path = cli_path.clone();
+        async move {
+            let options = ClaudeAgentOptions::builder()
+                .cli_path(cli_path)
+                .skip_version_check(true)
+                .build();
+            let options = match sandbox {
+                Some(sandbox) => ClaudeAgentOptions {
+                    landlock_sandbox: Some(sandbox),
+                    ..options
+                },
+                None => options,
+            };
+            let mut client = ClaudeClient::new(options);
+            client.connect().await.unwrap();
+            let status = client.sandbox_status().unwrap();
+            client.disconnect().await.unwrap();
+            status
+        }
+    };
+
+    assert_eq!(connect(None).await, SandboxStatus::default());
+    // A config that restricts nothing applies no sandbox
+    assert_eq!(
+        connect(Some(LandlockSandboxConfig::default())).await,
+        SandboxStatus::default()
+    );
+
+    let status = connect(Some(
+        LandlockSandboxConfig::builder()
+            .writable_roots(vec![dir.clone()])
+            .build(),
+    ))
+    .await
+    .landlock
+    .unwrap();
+    match landlock_abi() {
+        0 => {
+            assert_eq!(status.enforcement, LandlockEnforcement::NotEnforced);
+            assert_eq!(status.abi, None);
+        }
+        abi => {
+            assert!(status.is_fully_enforced());
+            assert_eq!(status.abi, Some(abi as u32));
+        }
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Run the probe under a seccomp profile and return what it wrote to stderr
+async fn probe_seccomp(cli_path: PathBuf, profile: SeccompProfile) -> Vec<String> {
+    let options = ClaudeAgentOptions::builder()
+        .cli_path(cli_path)
+        .skip_version_check(true)
+        .seccomp_profile(profile)
+        .build();
+    let mut client = ClaudeClient::new(options);
+    client.connect().await.unwrap();
+    client.query("probe").await.unwrap();
+
+    let exit = {
+        let mut stream = client.receive_messages();
+        tokio::time::timeout(Duration::from_secs(10), stream.next())
+            .await
+            .expect("should receive the exit report")
+    };
+    client.disconnect().await.unwrap();
+    match exit {
+        Some(Ok(Message::ProcessExited(ProcessExited { stderr_tail, .. }))) => stderr_tail,
+        other => panic!("expected an exit report, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_seccomp_network_off_blocks_inet_sockets() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let dir = temp_dir("seccomp-net");
+    let cli_path = write_cli(
+        &dir,
+        &format!(
+            r#"      if bash -c "exec 3<>/dev/tcp/127.0.0.1/{port}"; then echo "connect ok" >&2; else echo "connect denied" >&2; fi"#
+        ),
+    );
+
+    let profile = SeccompProfile::builder()
+        .preset(SeccompPreset::NetworkOff)
+        .build();
+    let stderr = probe_seccomp(cli_path, profile).await.join("\n");
+    assert!(stderr.contains("connect denied"), "{}", stderr);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_seccomp_kill_action_kills_the_offending_process() {
+    let dir = temp_dir("seccomp-kill");
+    let victim = dir.join("victim");
+    std::fs::write(&victim, "").unwrap();
+    let cli_path = write_cli(
+        &dir,
+        &format!(
+            r#"      rm "{}"
+      echo "rm exit $?" >&2"#,
+            victim.display()
+        ),
+    );
+
+    let profile = SeccompProfile::builder()
+        .preset(SeccompPreset::Empty)
+        .deny(vec!["unlinkat".to_string()])
+        .action(SeccompAction::KillProcess)
+        .build();
+    let stderr = probe_seccomp(cli_path, profile).await;
+
+    // The shell reports 128 + SIGSYS for a process killed by the filter
+    assert_eq!(
+        stderr.last(),
+        Some(&format!("rm exit {}", 128 + libc::SIGSYS))
+    );
+    assert!(victim.exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Whether this host allows unprivileged user and network namespaces
+fn user_namespaces_available() -> bool {
+    std::process::Command::new("unshare")
+        .args(["--user", "--map-root-user", "--net", "true"])
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[tokio::test]
+async fn test_network_isolation_only_reaches_allowed_destinations_via_proxy() {
+    if !user_namespaces_available() {
+        eprintln!("Unprivileged user namespaces are unavailable, skipping");
+        return;
+    }
+
+    let allowed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let blocked = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let allowed_port = allowed.local_addr().unwrap().port();
+    let blocked_port = blocked.local_addr().unwrap().port();
+
+    let dir = temp_dir("netns");
+    let probe_script = dir.join("probe.sh");
+    std::fs::write(
+        &probe_script,
+        format!(
+            r#"for port in {allowed_port} {blocked_port}; do
+  exec 3<>/dev/tcp/127.0.0.1/3128
+  printf 'CONNECT 127.0.0.1:%s HTTP/1.1\r\n\r\n' "$port" >&3
+  read -r status <&3
+  echo "proxy $port: $status" >&2
+  exec 3<&-
+done
+if (exec 3<>/dev/tcp/127.0.0.1/{allowed_port}) 2>/dev/null; then echo "direct ok" >&2; else echo "direct denied" >&2; fi
+echo "proxy env: $HTTPS_PROXY" >&2
+"#
+        ),
+    )
+    .unwrap();
+    let cli_path = write_cli(&dir, &format!(r#"      bash "{}""#, probe_script.display()));
+
+    let options = ClaudeAgentOptions::builder()
+        .cli_path(cli_path)
+        .skip_version_check(true)
+        .network_isolation(
+            NetworkIsolation::builder()
+                .allowed_destinations(vec![format!("127.0.0.1:{}", allowed_port)])
+                .build(),
+        )
+        .build();
+    let mut client = ClaudeClient::new(options);
+    client.connect().await.unwrap();
+    assert!(client.sandbox_status().unwrap().network_isolated);
+    client.query("probe").await.unwrap();
+
+    let exit = {
+        let mut stream = client.receive_messages();
+        tokio::time::timeout(Duration::from_secs(10), stream.next())
+            .await
+            .expect("should receive the exit report")
+    };
+    client.disconnect().await.unwrap();
+    let stderr = match exit {
+        Some(Ok(Message::ProcessExited(ProcessExited { stderr_tail, .. }))) => {
+            stderr_tail.join("\n")
+        }
+        other => panic!("expected an exit report, got {:?}", other),
+    };
+
+    assert!(
+        stderr.contains(&format!("proxy {}: HTTP/1.1 200", allowed_port)),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains(&format!("proxy {}: HTTP/1.1 403", blocked_port)));
+    assert!(stderr.contains("direct denied"));
+    assert!(stderr.contains("proxy env: http://127.0.0.1:3128"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
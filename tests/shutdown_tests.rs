@@ -0,0 +1,118 @@
+//! Tests for the staged CLI shutdown in `ClaudeClient::disconnect`
+//!
+//! Shell scripts stand in for the CLI. Each answers `initialize` and then
+//! misbehaves in a different way once stdin is closed.
+
+#![cfg(unix)]
+
+use claude_agent_sdk_rs::{ClaudeAgentOptions, ClaudeClient, ShutdownPolicy, ShutdownStage};
+use common::FakeCli;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+mod common;
+
+fn write_cli(before: &str, after: &str) -> (PathBuf, PathBuf) {
+    FakeCli::new("")
+        .setup(before)
+        .after(after)
+        .write("shutdown")
+}
+
+fn options(cli_path: &Path) -> ClaudeAgentOptions {
+    ClaudeAgentOptions::builder()
+        .cli_path(cli_path.to_path_buf())
+        .skip_version_check(true)
+        .shutdown_policy(
+            ShutdownPolicy::builder()
+                .grace_period(Duration::from_millis(200))
+                .term_timeout(Duration::from_millis(200))
+                .build(),
+        )
+        .build()
+}
+
+async fn connect_and_disconnect(cli_path: &Path) -> ShutdownStage {
+    let mut client = ClaudeClient::new(options(cli_path));
+    client.connect().await.unwrap();
+    tokio::time::timeout(Duration::from_secs(5), client.disconnect())
+        .await
+        .expect("disconnect should not hang")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_disconnect_graceful_on_stdin_eof() {
+    let (dir, cli_path) = write_cli("", "exit 0");
+    assert_eq!(
+        connect_and_disconnect(&cli_path).await,
+        ShutdownStage::Graceful
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_disconnect_terminates_cli_ignoring_eof() {
+    let (dir, cli_path) = write_cli("", "sleep 30");
+    assert_eq!(
+        connect_and_disconnect(&cli_path).await,
+        ShutdownStage::Terminated
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_disconnect_kills_cli_ignoring_sigterm() {
+    let (dir, cli_path) = write_cli("trap '' TERM", "while :; do sleep 0.1; done");
+    assert_eq!(
+        connect_and_disconnect(&cli_path).await,
+        ShutdownStage::Killed
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_disconnect_reaps_grandchildren() {
+    let (dir, cli_path) = write_cli(
+        r#"sleep 300 >/dev/null 2>&1 &
+echo $! > "$(dirname "$0")/grandchild.pid""#,
+        "exit 0",
+    );
+    assert_eq!(
+        connect_and_disconnect(&cli_path).await,
+        ShutdownStage::Graceful
+    );
+
+    let pid = std::fs::read_to_string(dir.join("grandchild.pid")).unwrap();
+    let stat_path = format!("/proc/{}/stat", pid.trim());
+    let mut alive = true;
+    for _ in 0..50 {
+        // A zombie waiting for init to reap it counts as gone
+        alive = std::fs::read_to_string(&stat_path)
+            .map(|stat| !stat.contains(") Z "))
+            .unwrap_or(false);
+        if !alive {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(!alive, "tool process outlived the CLI");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_disconnect_terminates_leftover_processes_before_killing_them() {
+    let (dir, cli_path) = write_cli(
+        r#"sh -c 'trap "touch \"$0.term\"; exit 0" TERM; while :; do sleep 0.05; done' "$0" >/dev/null 2>&1 &"#,
+        "exit 0",
+    );
+    assert_eq!(
+        connect_and_disconnect(&cli_path).await,
+        ShutdownStage::Graceful
+    );
+
+    // The tool process got SIGTERM, and a chance to clean up, before any SIGKILL
+    assert!(dir.join("claude.term").exists());
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,139 @@
+//! Tests for supervised mode in `ClaudeClient`
+//!
+//! A shell script stands in for the CLI: it answers `initialize` and crashes on
+//! the first user message unless it was started with `--resume`.
+
+#![cfg(unix)]
+
+use claude_agent_sdk_rs::{
+    ClaudeAgentOptions, ClaudeClient, Message, ReconnectEvent, RestartPolicy,
+};
+use common::FakeCli;
+use futures::StreamExt;
+use std::path::Path;
+use std::time::Duration;
+
+mod common;
+
+/// Crashes on the first user message unless started with `--resume`
+fn resuming_cli() -> FakeCli {
+    FakeCli::new(
+        r#"      printf '{"type":"system","subtype":"init","session_id":"sess-1"}\n'
+      if [ "$resumed" = 0 ]; then
+        exit 1
+      fi
+      printf '{"type":"result","subtype":"success","duration_ms":1,"duration_api_ms":1,"is_error":false,"num_turns":1,"session_id":"sess-1"}\n'"#,
+    )
+    .setup(
+        r#"case "$*" in
+  *"--resume sess-1"*) resumed=1 ;;
+  *) resumed=0 ;;
+esac"#,
+    )
+    .initialize_response(r#"{"resumed":'"$resumed"'}"#)
+}
+
+fn supervised_options(cli_path: &Path, max_restarts: u32) -> ClaudeAgentOptions {
+    ClaudeAgentOptions::builder()
+        .cli_path(cli_path.to_path_buf())
+        .skip_version_check(true)
+        .restart_policy(
+            RestartPolicy::builder()
+                .max_restarts(max_restarts)
+                .initial_backoff(Duration::from_millis(10))
+                .build(),
+        )
+        .build()
+}
+
+#[tokio::test]
+async fn test_supervised_client_resumes_after_crash() {
+    let (dir, cli_path) = resuming_cli().write("supervisor");
+    let mut client = ClaudeClient::new(supervised_options(&cli_path, 3));
+    client.connect().await.unwrap();
+    assert_eq!(client.get_server_info().unwrap().extra["resumed"], 0);
+
+    client.query("hello").await.unwrap();
+    let mut messages = Vec::new();
+    {
+        let mut stream = client.receive_messages();
+        while let Ok(Some(message)) =
+            tokio::time::timeout(Duration::from_secs(5), stream.next()).await
+        {
+            let message = message.unwrap();
+            let done = matches!(message, Message::Reconnect(_));
+            messages.push(message);
+            if done {
+                break;
+            }
+        }
+    }
+
+    assert!(matches!(messages[0], Message::System(_)));
+    match messages.last() {
+        Some(Message::Reconnect(ReconnectEvent::Reconnected {
+            attempt,
+            session_id,
+        })) => {
+            assert_eq!(*attempt, 1);
+            assert_eq!(session_id.as_deref(), Some("sess-1"));
+        }
+        other => panic!("expected reconnect event, got {:?}", other),
+    }
+
+    // The restarted CLI was re-initialized and resumed the session
+    assert_eq!(client.get_server_info().unwrap().extra["resumed"], 1);
+    client.query("hello again").await.unwrap();
+    let turn: Vec<_> = tokio::time::timeout(
+        Duration::from_secs(5),
+        client.receive_response().collect::<Vec<_>>(),
+    )
+    .await
+    .unwrap();
+    assert!(matches!(turn.last(), Some(Ok(Message::Result(_)))));
+
+    client.disconnect().await.unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_supervised_client_reports_exhausted_restarts() {
+    let (dir, cli_path) = common::fake_cli("supervisor", "      exit 1");
+    let mut client = ClaudeClient::new(supervised_options(&cli_path, 1));
+    client.connect().await.unwrap();
+
+    client.query("hello").await.unwrap();
+    assert!(matches!(
+        next_reconnect_event(&client).await,
+        ReconnectEvent::Reconnected { attempt: 1, .. }
+    ));
+
+    // The restarted CLI crashes as well, which exhausts the policy
+    client.query("hello").await.unwrap();
+    assert!(matches!(
+        next_reconnect_event(&client).await,
+        ReconnectEvent::Failed { attempts: 1, .. }
+    ));
+    // Nothing more will arrive, so the stream ends
+    let next = tokio::time::timeout(Duration::from_secs(5), client.receive_messages().next())
+        .await
+        .expect("the message stream should end");
+    assert!(next.is_none());
+
+    client.disconnect().await.unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+async fn next_reconnect_event(client: &ClaudeClient) -> ReconnectEvent {
+    let mut stream = client.receive_messages();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(message) = stream.next().await {
+            if let Ok(Message::Reconnect(event)) = message {
+                return event;
+            }
+        }
+        panic!("message stream ended without a reconnect event");
+    })
+    .await
+    .expect("should receive a reconnect event")
+}
@@ -0,0 +1,1474 @@
+//! Tests for plugging custom transports into the SDK
+//!
+//! These tests implement `Transport` outside the crate, the way production users
+//! do, and verify that the client runs the full control protocol over it.
+
+use async_trait::async_trait;
+use claude_agent_sdk_rs::{
+    ClaudeAgentOptions, ClaudeClient, Message, OverflowPolicy, Result, Transport,
+    query_stream_with_transport, query_with_transport,
+};
+use futures::StreamExt;
+use futures::stream::Stream;
+use serde_json::json;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod common;
+
+/// A fake CLI that answers control requests and replies to each user message
+struct LoopbackTransport {
+    tx: Mutex<Option<flume::Sender<serde_json::Value>>>,
+    rx: flume::Receiver<serde_json::Value>,
+    written: Mutex<Vec<serde_json::Value>>,
+    /// Runtime MCP servers the fake CLI is connected to
+    mcp_servers: Mutex<Vec<String>>,
+    /// Version reported to the SDK, if any
+    cli_version: Option<String>,
+    ready: AtomicBool,
+}
+
+impl LoopbackTransport {
+    fn new() -> Self {
+        let (tx, rx) = flume::unbounded();
+        Self {
+            tx: Mutex::new(Some(tx)),
+            rx,
+            written: Mutex::new(Vec::new()),
+            mcp_servers: Mutex::new(Vec::new()),
+            cli_version: None,
+            ready: AtomicBool::new(false),
+        }
+    }
+
+    fn with_cli_version(version: &str) -> Self {
+        Self {
+            cli_version: Some(version.to_string()),
+            ..Self::new()
+        }
+    }
+
+    fn written(&self) -> Vec<serde_json::Value> {
+        self.written.lock().unwrap().clone()
+    }
+
+    fn emit(&self, value: serde_json::Value) {
+        if let Some(tx) = self.tx.lock().unwrap().as_ref() {
+            let _ = tx.send(value);
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for LoopbackTransport {
+    async fn connect(&self) -> Result<()> {
+        self.ready.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn write(&self, data: &str) -> Result<()> {
+        let value: serde_json::Value = serde_json::from_str(data).unwrap();
+        self.written.lock().unwrap().push(value.clone());
+
+        match value["type"].as_str() {
+            // Never answered, to exercise timeouts
+            Some("control_request") if value["request"]["subtype"] == "interrupt" => {}
+            Some("control_request")
+                if value["request"]["subtype"] == "set_permission_mode"
+                    && value["request"]["mode"] == "plan" => {}
+            Some("control_request")
+                if value["request"]["subtype"] == "set_permission_mode"
+                    && value["request"]["mode"] == "bypassPermissions" =>
+            {
+                self.emit(json!({
+                    "type": "control_response",
+                    "response": {
+                        "subtype": "error",
+                        "request_id": value["request_id"],
+                        "error": "Cannot set permission mode to bypassPermissions"
+                    }
+                }))
+            }
+            Some("control_request") if value["request"]["subtype"] == "set_model" => {
+                self.emit(json!({
+                    "type": "control_response",
+                    "response": {
+                        "subtype": "error",
+                        "request_id": value["request_id"],
+                        "error": "Unknown model: claude-nonexistent"
+                    }
+                }))
+            }
+            Some("control_request") if value["request"]["subtype"] == "mcp_set_servers" => {
+                // Servers whose command doesn't exist fail to start
+                let servers = value["request"]["servers"].as_object().unwrap();
+                let (failed, started): (Vec<_>, Vec<_>) = servers
+                    .iter()
+                    .partition(|(_, config)| config["command"] == "missing");
+                *self.mcp_servers.lock().unwrap() =
+                    started.iter().map(|(name, _)| name.to_string()).collect();
+                let errors: serde_json::Map<_, _> = failed
+                    .into_iter()
+                    .map(|(name, _)| (name.clone(), json!("command not found")))
+                    .collect();
+                self.emit(json!({
+                    "type": "control_response",
+                    "response": {
+                        "subtype": "success",
+                        "request_id": value["request_id"],
+                        "response": {"added": [], "removed": [], "errors": errors}
+                    }
+                }))
+            }
+            Some("control_request") if value["request"]["subtype"] == "mcp_status" => {
+                let servers: Vec<_> = self
+                    .mcp_servers
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|name| {
+                        json!({
+                            "name": name,
+                            "status": "connected",
+                            "serverInfo": {"name": name, "version": "1.0.0"}
+                        })
+                    })
+                    .collect();
+                self.emit(json!({
+                    "type": "control_response",
+                    "response": {
+                        "subtype": "success",
+                        "request_id": value["request_id"],
+                        "response": {"mcpServers": servers}
+                    }
+                }))
+            }
+            Some("control_request") => self.emit(json!({
+                "type": "control_response",
+                "response": {
+                    "subtype": "success",
+                    "request_id": value["request_id"],
+                    "response": {"commands": [], "output_style": "default"}
+                }
+            })),
+            Some("user") => {
+                self.emit(json!({
+                    "type": "assistant",
+                    "message": {"content": [{"type": "text", "text": "pong from loopback"}], "model": "test"},
+                    "session_id": "loopback"
+                }));
+                self.emit(json!({
+                    "type": "result",
+                    "subtype": "success",
+                    "duration_ms": 1,
+                    "duration_api_ms": 1,
+                    "is_error": false,
+                    "num_turns": 1,
+                    "session_id": "loopback"
+                }));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn read_messages(&self) -> Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send + '_>> {
+        Box::pin(self.rx.stream().map(Ok))
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.ready.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+
+    fn cli_version(&self) -> Option<String> {
+        self.cli_version.clone()
+    }
+
+    async fn end_input(&self) -> Result<()> {
+        // Closing input makes the fake CLI finish its output
+        self.tx.lock().unwrap().take();
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_client_with_transport_runs_initialize() {
+    let transport = Arc::new(LoopbackTransport::new());
+    let mut client = ClaudeClient::with_transport(
+        Arc::clone(&transport) as Arc<dyn Transport>,
+        ClaudeAgentOptions::default(),
+    );
+
+    tokio::time::timeout(Duration::from_secs(2), client.connect())
+        .await
+        .expect("initialize handshake should complete")
+        .unwrap();
+
+    let written = transport.written();
+    assert_eq!(written[0]["type"], "control_request");
+    assert_eq!(written[0]["request"]["subtype"], "initialize");
+
+    let info = client.get_server_info().expect("server info after connect");
+    assert_eq!(info.output_style.as_deref(), Some("default"));
+
+    client.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_client_with_transport_query_roundtrip() {
+    let transport = Arc::new(LoopbackTransport::new());
+    let mut client = ClaudeClient::with_transport(transport, ClaudeAgentOptions::default());
+    client.connect().await.unwrap();
+
+    client.query("ping").await.unwrap();
+    let messages: Vec<_> = tokio::time::timeout(
+        Duration::from_secs(2),
+        client.receive_response().collect::<Vec<_>>(),
+    )
+    .await
+    .expect("should receive response");
+
+    assert_eq!(messages.len(), 2);
+    assert!(matches!(messages[1], Ok(Message::Result(_))));
+
+    client.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_failed_control_requests_get_error_responses() {
+    let transport = Arc::new(LoopbackTransport::new());
+    let mut client = ClaudeClient::with_transport(
+        Arc::clone(&transport) as Arc<dyn Transport>,
+        ClaudeAgentOptions::default(),
+    );
+    client.connect().await.unwrap();
+
+    for (request_id, request) in [
+        (
+            "hook",
+            json!({"subtype": "hook_callback", "callback_id": "hook_404", "input": {}}),
+        ),
+        (
+            "mcp",
+            json!({"subtype": "mcp_message", "server_name": "missing", "message": {}}),
+        ),
+        ("unknown", json!({"subtype": "teleport"})),
+    ] {
+        transport.emit(json!({
+            "type": "control_request",
+            "request_id": request_id,
+            "request": request
+        }));
+    }
+    // No `request` at all
+    transport.emit(json!({"type": "control_request", "request_id": "malformed"}));
+
+    let errors = tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            let errors: std::collections::HashMap<String, String> = transport
+                .written()
+                .into_iter()
+                .filter(|m| m["type"] == "control_response")
+                .map(|m| {
+                    assert_eq!(m["response"]["subtype"], "error");
+                    (
+                        m["response"]["request_id"].as_str().unwrap().to_string(),
+                        m["response"]["error"].as_str().unwrap().to_string(),
+                    )
+                })
+                .collect();
+            if errors.len() == 4 {
+                break errors;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("every control request should be answered");
+
+    assert_eq!(errors["hook"], "Hook callback not found: hook_404");
+    assert_eq!(errors["mcp"], "SDK MCP server not found: missing");
+    assert_eq!(
+        errors["unknown"],
+        "Unsupported control request subtype: teleport"
+    );
+    assert!(errors["malformed"].starts_with("Malformed control request"));
+
+    client.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_control_request_errors_and_timeouts() {
+    use claude_agent_sdk_rs::ClaudeError;
+    use claude_agent_sdk_rs::errors::{ControlErrorKind, ControlProtocolError};
+
+    let transport = Arc::new(LoopbackTransport::new());
+    let options = ClaudeAgentOptions::builder()
+        .control_request_timeout(Duration::from_millis(100))
+        .build();
+    let mut client = ClaudeClient::with_transport(transport, options);
+    client.connect().await.unwrap();
+
+    // The CLI's error response carries its message
+    match client.set_model(Some("claude-nonexistent")).await {
+        Err(ClaudeError::ControlProtocol(ControlProtocolError {
+            message,
+            kind: ControlErrorKind::Cli,
+            subtype,
+        })) => {
+            assert_eq!(message, "Unknown model: claude-nonexistent");
+            assert_eq!(subtype.as_deref(), Some("set_model"));
+        }
+        other => panic!("expected a CLI error, got {:?}", other),
+    }
+
+    // An unanswered request times out after the default, or the per-call override
+    let started = std::time::Instant::now();
+    match client.interrupt().await {
+        Err(ClaudeError::ControlProtocol(error)) => {
+            assert_eq!(error.kind, ControlErrorKind::Timeout)
+        }
+        other => panic!("expected a timeout, got {:?}", other),
+    }
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    let started = std::time::Instant::now();
+    let result = client
+        .interrupt_with_timeout(Duration::from_millis(300))
+        .await;
+    assert!(matches!(
+        result,
+        Err(ClaudeError::ControlProtocol(ControlProtocolError {
+            kind: ControlErrorKind::Timeout,
+            ..
+        }))
+    ));
+    assert!(started.elapsed() >= Duration::from_millis(300));
+
+    client.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_cancel_request_aborts_running_callback() {
+    use claude_agent_sdk_rs::{PermissionResult, PermissionResultAllow};
+
+    /// Records that the callback's future was dropped
+    struct DropFlag(Arc<AtomicBool>);
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    let signalled = Arc::new(AtomicBool::new(false));
+    let dropped = Arc::new(AtomicBool::new(false));
+    let (started_tx, started_rx) = flume::bounded(1);
+    let options = ClaudeAgentOptions::builder()
+        .can_use_tool({
+            let signalled = Arc::clone(&signalled);
+            let dropped = Arc::clone(&dropped);
+            Arc::new(move |_tool, _input, context| {
+                let signal = context.signal.expect("the SDK passes a signal");
+                let signalled = Arc::clone(&signalled);
+                let flag = DropFlag(Arc::clone(&dropped));
+                let started_tx = started_tx.clone();
+                Box::pin(async move {
+                    let _flag = flag;
+                    tokio::spawn(async move {
+                        signal.aborted().await;
+                        signalled.store(true, Ordering::SeqCst);
+                    });
+                    let _ = started_tx.send(());
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                    PermissionResult::Allow(PermissionResultAllow::default())
+                })
+            })
+        })
+        .build();
+    let transport = Arc::new(LoopbackTransport::new());
+    let mut client =
+        ClaudeClient::with_transport(Arc::clone(&transport) as Arc<dyn Transport>, options);
+    client.connect().await.unwrap();
+
+    transport.emit(json!({
+        "type": "control_request",
+        "request_id": "perm_1",
+        "request": {"subtype": "can_use_tool", "tool_name": "Bash", "input": {}}
+    }));
+    tokio::time::timeout(Duration::from_secs(2), started_rx.recv_async())
+        .await
+        .unwrap()
+        .unwrap();
+    transport.emit(json!({"type": "control_cancel_request", "request_id": "perm_1"}));
+
+    tokio::time::timeout(Duration::from_secs(2), async {
+        while !(signalled.load(Ordering::SeqCst) && dropped.load(Ordering::SeqCst)) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the callback should be signalled and dropped");
+
+    // The cancel is consumed, and the cancelled request gets no response
+    client.query("ping").await.unwrap();
+    let messages: Vec<_> = tokio::time::timeout(
+        Duration::from_secs(2),
+        client.receive_response().collect::<Vec<_>>(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(messages.len(), 2);
+    assert!(
+        !transport
+            .written()
+            .iter()
+            .any(|m| m["response"]["request_id"] == "perm_1")
+    );
+
+    client.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_runtime_hooks_apply_after_connect() {
+    use claude_agent_sdk_rs::{HookEvent, HookJsonOutput, HookMatcher, SyncHookJsonOutput};
+
+    let transport = Arc::new(LoopbackTransport::new());
+    let mut client = ClaudeClient::with_transport(
+        Arc::clone(&transport) as Arc<dyn Transport>,
+        ClaudeAgentOptions::builder().runtime_hooks(true).build(),
+    );
+    client.connect().await.unwrap();
+
+    // The CLI learns about one gate per event at initialize
+    let initialize = transport
+        .written()
+        .into_iter()
+        .find(|m| m["request"]["subtype"] == "initialize")
+        .unwrap();
+    let gate_id = initialize["request"]["hooks"]["PreToolUse"][0]["hookCallbackIds"][0]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(initialize["request"]["hooks"]["Stop"].is_array());
+
+    let call_gate = |request_id: &str| {
+        transport.emit(json!({
+            "type": "control_request",
+            "request_id": request_id,
+            "request": {
+                "subtype": "hook_callback",
+                "callback_id": gate_id,
+                "input": {
+                    "hook_event_name": "PreToolUse",
+                    "session_id": "s",
+                    "transcript_path": "/tmp/t",
+                    "cwd": "/tmp",
+                    "tool_name": "Bash",
+                    "tool_input": {"command": "ls"}
+                }
+            }
+        }));
+    };
+    let response_to = |request_id: &str| {
+        let transport = Arc::clone(&transport);
+        let request_id = request_id.to_string();
+        async move {
+            loop {
+                if let Some(m) = transport
+                    .written()
+                    .into_iter()
+                    .find(|m| m["response"]["request_id"] == request_id.as_str())
+                {
+                    return m["response"].clone();
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+    };
+
+    call_gate("hook_1");
+    let response = tokio::time::timeout(Duration::from_secs(2), response_to("hook_1"))
+        .await
+        .unwrap();
+    assert_eq!(response["response"], json!({}));
+
+    let handle = client
+        .add_hook(
+            HookEvent::PreToolUse,
+            HookMatcher::builder()
+                .matcher("Bash")
+                .hooks(vec![Arc::new(|_, _, _| {
+                    Box::pin(async {
+                        HookJsonOutput::Sync(
+                            SyncHookJsonOutput::builder()
+                                .decision("block")
+                                .reason("audit phase")
+                                .build(),
+                        )
+                    })
+                })])
+                .build(),
+        )
+        .unwrap();
+    call_gate("hook_2");
+    let response = tokio::time::timeout(Duration::from_secs(2), response_to("hook_2"))
+        .await
+        .unwrap();
+    assert_eq!(response["response"]["decision"], "block");
+    assert_eq!(response["response"]["reason"], "audit phase");
+
+    assert!(client.remove_hook(handle));
+    call_gate("hook_3");
+    let response = tokio::time::timeout(Duration::from_secs(2), response_to("hook_3"))
+        .await
+        .unwrap();
+    assert_eq!(response["response"], json!({}));
+
+    client.disconnect().await.unwrap();
+
+    // Without the option there are no gates, so adding is refused
+    let client = ClaudeClient::new(ClaudeAgentOptions::default());
+    assert!(
+        client
+            .add_hook(HookEvent::Stop, HookMatcher::builder().build())
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_mcp_servers_added_and_removed_after_connect() {
+    use claude_agent_sdk_rs::{
+        McpConnectionStatus, McpServerConfig, SdkMcpTool, ToolResult, create_sdk_mcp_server, tool,
+        types::mcp::McpStdioServerConfig,
+    };
+
+    let transport = Arc::new(LoopbackTransport::new());
+    let mut client = ClaudeClient::with_transport(
+        Arc::clone(&transport) as Arc<dyn Transport>,
+        ClaudeAgentOptions::default(),
+    );
+    client.connect().await.unwrap();
+
+    let echo: SdkMcpTool = tool!(
+        "echo",
+        "Echo the input",
+        json!({"type": "object"}),
+        |_args| async {
+            Ok(ToolResult {
+                content: vec![],
+                is_error: false,
+            })
+        }
+    );
+    client
+        .add_mcp_server(
+            "tools",
+            McpServerConfig::Sdk(create_sdk_mcp_server("tools", "1.0.0", vec![echo])),
+        )
+        .await
+        .unwrap();
+    let set = transport
+        .written()
+        .into_iter()
+        .rfind(|m| m["request"]["subtype"] == "mcp_set_servers")
+        .unwrap();
+    assert_eq!(
+        set["request"]["servers"]["tools"],
+        json!({"type": "sdk", "name": "tools"})
+    );
+
+    // The CLI can reach the in-process server right away
+    let list_tools = |request_id: &str| {
+        transport.emit(json!({
+            "type": "control_request",
+            "request_id": request_id,
+            "request": {
+                "subtype": "mcp_message",
+                "server_name": "tools",
+                "message": {"jsonrpc": "2.0", "id": 1, "method": "tools/list"}
+            }
+        }));
+    };
+    let response_to = |request_id: &str| {
+        let transport = Arc::clone(&transport);
+        let request_id = request_id.to_string();
+        async move {
+            loop {
+                if let Some(m) = transport
+                    .written()
+                    .into_iter()
+                    .find(|m| m["response"]["request_id"] == request_id.as_str())
+                {
+                    return m["response"].clone();
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+    };
+    list_tools("mcp_1");
+    let response = tokio::time::timeout(Duration::from_secs(2), response_to("mcp_1"))
+        .await
+        .unwrap();
+    assert_eq!(response["subtype"], "success");
+    assert_eq!(
+        response["response"]["mcp_response"]["result"]["tools"][0]["name"],
+        "echo"
+    );
+
+    let status = client.mcp_status().await.unwrap();
+    assert_eq!(status.len(), 1);
+    assert_eq!(status[0].name, "tools");
+    assert_eq!(status[0].status, McpConnectionStatus::Connected);
+    assert_eq!(status[0].server_info.as_ref().unwrap().version, "1.0.0");
+
+    // A server the CLI can't start is reported and not kept
+    let broken = McpServerConfig::Stdio(McpStdioServerConfig {
+        command: "missing".to_string(),
+        args: None,
+        env: None,
+    });
+    let err = client.add_mcp_server("broken", broken).await.unwrap_err();
+    assert!(err.to_string().contains("command not found"));
+    assert!(client.remove_mcp_server("broken").await.is_err());
+
+    client.remove_mcp_server("tools").await.unwrap();
+    assert!(client.mcp_status().await.unwrap().is_empty());
+    list_tools("mcp_2");
+    let response = tokio::time::timeout(Duration::from_secs(2), response_to("mcp_2"))
+        .await
+        .unwrap();
+    assert_eq!(response["subtype"], "error");
+
+    client.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_permission_policy_swaps_at_runtime() {
+    use claude_agent_sdk_rs::{
+        CanUseToolCallback, PermissionMode, PermissionResult, PermissionResultAllow,
+        PermissionResultDeny,
+    };
+
+    let deny: CanUseToolCallback = Arc::new(|_tool, _input, _context| {
+        Box::pin(async {
+            PermissionResult::Deny(PermissionResultDeny {
+                message: "automated policy".to_string(),
+                interrupt: false,
+            })
+        })
+    });
+    let allow: CanUseToolCallback = Arc::new(|_tool, _input, _context| {
+        Box::pin(async { PermissionResult::Allow(PermissionResultAllow::default()) })
+    });
+
+    let transport = Arc::new(LoopbackTransport::new());
+    let mut client = ClaudeClient::with_transport(
+        Arc::clone(&transport) as Arc<dyn Transport>,
+        ClaudeAgentOptions::builder()
+            .can_use_tool(deny)
+            .control_request_timeout(Duration::from_millis(100))
+            .build(),
+    );
+    client.connect().await.unwrap();
+
+    let decide = |request_id: &'static str| {
+        let transport = Arc::clone(&transport);
+        transport.emit(json!({
+            "type": "control_request",
+            "request_id": request_id,
+            "request": {"subtype": "can_use_tool", "tool_name": "Edit", "input": {}}
+        }));
+        async move {
+            loop {
+                if let Some(m) = transport
+                    .written()
+                    .into_iter()
+                    .find(|m| m["response"]["request_id"] == request_id)
+                {
+                    return m["response"]["response"]["behavior"].clone();
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+    };
+
+    let behavior = tokio::time::timeout(Duration::from_secs(2), decide("perm_1"))
+        .await
+        .unwrap();
+    assert_eq!(behavior, "deny");
+
+    client
+        .set_permission_policy(PermissionMode::Default, Some(allow.clone()))
+        .await
+        .unwrap();
+    let mode_change = transport
+        .written()
+        .into_iter()
+        .find(|m| m["request"]["subtype"] == "set_permission_mode")
+        .unwrap();
+    assert_eq!(mode_change["request"]["mode"], "default");
+    let behavior = tokio::time::timeout(Duration::from_secs(2), decide("perm_2"))
+        .await
+        .unwrap();
+    assert_eq!(behavior, "allow");
+
+    client.set_can_use_tool(Some(Arc::new(|_tool, _input, _context| {
+        Box::pin(async { PermissionResult::Deny(PermissionResultDeny::default()) })
+    })));
+    let behavior = tokio::time::timeout(Duration::from_secs(2), decide("perm_3"))
+        .await
+        .unwrap();
+    assert_eq!(behavior, "deny");
+
+    // A rejected mode keeps the old callback
+    assert!(
+        client
+            .set_permission_policy(PermissionMode::BypassPermissions, Some(allow.clone()))
+            .await
+            .is_err()
+    );
+    let behavior = tokio::time::timeout(Duration::from_secs(2), decide("perm_4"))
+        .await
+        .unwrap();
+    assert_eq!(behavior, "deny");
+
+    // A timed-out one may have applied, so the new callback stays
+    assert!(
+        client
+            .set_permission_policy(PermissionMode::Plan, Some(allow))
+            .await
+            .is_err()
+    );
+    let behavior = tokio::time::timeout(Duration::from_secs(2), decide("perm_5"))
+        .await
+        .unwrap();
+    assert_eq!(behavior, "allow");
+
+    client.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_protocol_tap_records_masked_frames_in_both_directions() {
+    use claude_agent_sdk_rs::types::env::REDACTED;
+    use claude_agent_sdk_rs::{EnvPolicy, ProtocolTap, TapDirection, TapFrame};
+
+    let frames: Arc<Mutex<Vec<TapFrame>>> = Arc::default();
+    let tap = ProtocolTap::to_callback({
+        let frames = Arc::clone(&frames);
+        move |frame| frames.lock().unwrap().push(frame.clone())
+    })
+    .with_masked_prompts()
+    .with_redaction(|direction, frame| {
+        if direction == TapDirection::Received && frame.get("session_id").is_some() {
+            frame["session_id"] = json!("<session>");
+        }
+    });
+    // The fake CLI's reply doubles as a secret from the environment
+    let options = ClaudeAgentOptions::builder()
+        .env(std::collections::HashMap::from([(
+            "REPLY_SECRET".to_string(),
+            "pong from loopback".to_string(),
+        )]))
+        .env_policy(
+            EnvPolicy::builder()
+                .redact(vec!["REPLY_SECRET".to_string()])
+                .build(),
+        )
+        .protocol_tap(tap)
+        .build();
+    let transport = Arc::new(LoopbackTransport::new());
+    let mut client = ClaudeClient::with_transport(transport, options);
+    client.connect().await.unwrap();
+
+    client.query("deploy the hotfix").await.unwrap();
+    let messages: Vec<_> = tokio::time::timeout(
+        Duration::from_secs(2),
+        client.receive_response().collect::<Vec<_>>(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(messages.len(), 2);
+    client.disconnect().await.unwrap();
+
+    let frames = frames.lock().unwrap();
+    assert!(frames.windows(2).all(|w| w[0].seq + 1 == w[1].seq));
+    assert!(
+        frames
+            .windows(2)
+            .all(|w| w[0].elapsed_us <= w[1].elapsed_us)
+    );
+
+    let direction_of = |frame_type: &str| {
+        frames
+            .iter()
+            .find(|f| f.frame["type"] == frame_type)
+            .unwrap_or_else(|| panic!("no {} frame", frame_type))
+    };
+    let initialize = direction_of("control_request");
+    assert_eq!(initialize.direction, TapDirection::Sent);
+    assert_eq!(initialize.frame["request"]["subtype"], "initialize");
+    assert_eq!(
+        direction_of("control_response").direction,
+        TapDirection::Received
+    );
+
+    let user = direction_of("user");
+    assert_eq!(user.direction, TapDirection::Sent);
+    assert_eq!(user.frame["message"]["content"], REDACTED);
+
+    let assistant = direction_of("assistant");
+    assert_eq!(assistant.direction, TapDirection::Received);
+    assert_eq!(assistant.frame["message"]["content"][0]["text"], REDACTED);
+    assert_eq!(assistant.frame["session_id"], "<session>");
+}
+
+#[tokio::test]
+async fn test_query_with_transport_sends_user_message() {
+    let transport = Arc::new(LoopbackTransport::new());
+    let messages = query_with_transport("ping", Arc::clone(&transport) as Arc<dyn Transport>)
+        .await
+        .unwrap();
+
+    assert_eq!(messages.len(), 2);
+    let written = transport.written();
+    assert_eq!(written[0]["type"], "user");
+    assert_eq!(written[0]["message"]["content"], "ping");
+}
+
+#[tokio::test]
+async fn test_query_stream_with_transport() {
+    let transport = Arc::new(LoopbackTransport::new());
+    let stream = query_stream_with_transport("ping", transport)
+        .await
+        .unwrap();
+    let messages: Vec<_> = stream.collect().await;
+
+    assert_eq!(messages.len(), 2);
+    assert!(messages.iter().all(|m| m.is_ok()));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_subprocess_transport_runs_cli_through_launcher() {
+    use claude_agent_sdk_rs::{PathMapping, ProcessLauncher, QueryPrompt, SubprocessTransport};
+    use std::collections::HashMap;
+
+    let dir = common::temp_dir("launcher");
+    let dir_name = dir.file_name().unwrap().to_string_lossy().to_string();
+
+    // A fake CLI that reports how it was invoked
+    let cli_path = common::write_cli(
+        &dir,
+        "#!/bin/sh\ncat > /dev/null\nprintf '{\"type\":\"system\",\"subtype\":\"init\",\"argv\":\"%s\",\"pwd\":\"%s\",\"mark\":\"%s\"}\\n' \"$*\" \"$PWD\" \"$LAUNCHER_MARK\"\n",
+    );
+
+    // `sh -c 'exec "$@"'` stands in for a wrapper such as bwrap
+    let launcher = ProcessLauncher::builder()
+        .program("sh")
+        .args(vec![
+            "-c".to_string(),
+            "exec \"$@\"".to_string(),
+            "launcher".to_string(),
+        ])
+        .path_mappings(vec![PathMapping::new("/host/data", "/data")])
+        .env(HashMap::from([(
+            "LAUNCHER_MARK".to_string(),
+            "wrapped".to_string(),
+        )]))
+        .build();
+    let options = ClaudeAgentOptions::builder()
+        .cli_path(cli_path)
+        .cwd(dir.clone())
+        .add_dirs(vec![std::path::PathBuf::from("/host/data/shared")])
+        .skip_version_check(true)
+        .launcher(launcher)
+        .build();
+
+    let transport = SubprocessTransport::new(QueryPrompt::from("hi"), options).unwrap();
+    transport.connect().await.unwrap();
+    let messages: Vec<_> = transport.read_messages().collect().await;
+    transport.close().await.unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let init = messages[0].as_ref().unwrap();
+    assert!(
+        init["argv"]
+            .as_str()
+            .unwrap()
+            .contains("--add-dir /data/shared"),
+        "{}",
+        init
+    );
+    assert_eq!(init["mark"], "wrapped");
+    assert!(init["pwd"].as_str().unwrap().ends_with(dir_name.as_str()));
+}
+
+/// Fake CLI output: three 600-byte lines, one 5000-byte tool result, then one more small line
+#[cfg(unix)]
+const OVERSIZED_OUTPUT_CLI: &str = r#"#!/bin/sh
+cat > /dev/null
+small='{"type":"system","subtype":"init","pad":"'$(head -c 600 /dev/zero | tr '\0' 'x')'"}'
+for i in 1 2 3; do printf '%s\n' "$small"; done
+printf '{"type":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"%s"}]}\n' "$(head -c 5000 /dev/zero | tr '\0' 'y')"
+printf '%s\n' "$small"
+"#;
+
+#[cfg(unix)]
+async fn read_oversized_output(
+    spill_dir: Option<std::path::PathBuf>,
+) -> Vec<Result<serde_json::Value>> {
+    use claude_agent_sdk_rs::{QueryPrompt, SubprocessTransport};
+
+    let dir = common::temp_dir("oversized");
+    let cli_path = common::write_cli(&dir, OVERSIZED_OUTPUT_CLI);
+
+    let mut options = ClaudeAgentOptions::builder()
+        .cli_path(cli_path)
+        .skip_version_check(true)
+        .max_buffer_size(1024)
+        .build();
+    options.spill_dir = spill_dir;
+
+    let transport = SubprocessTransport::new(QueryPrompt::from("hi"), options).unwrap();
+    transport.connect().await.unwrap();
+    let messages: Vec<_> = transport.read_messages().collect().await;
+    transport.close().await.unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    messages
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_subprocess_transport_limits_each_message() {
+    let messages = read_oversized_output(None).await;
+
+    // 2400 bytes in total pass a 1024 byte limit; only the large line is rejected
+    assert_eq!(messages.len(), 5);
+    assert!(messages[..3].iter().all(|m| m.is_ok()));
+    match &messages[3] {
+        Err(e) => assert!(
+            e.to_string().contains("exceeds maximum of 1024 bytes"),
+            "{}",
+            e
+        ),
+        Ok(value) => panic!("expected an error, got {}", value),
+    }
+    assert!(messages[4].is_ok());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_subprocess_transport_spills_oversized_message() {
+    use claude_agent_sdk_rs::ContentBlock;
+
+    let spill_dir = std::env::temp_dir().join(format!("claude-spill-{}", uuid::Uuid::new_v4()));
+    let mut messages = read_oversized_output(Some(spill_dir.clone())).await;
+    assert_eq!(messages.len(), 5);
+    assert!(messages.iter().all(|m| m.is_ok()));
+
+    let spilled = match serde_json::from_value::<Message>(messages.remove(3).unwrap()).unwrap() {
+        Message::Spilled(spilled) => spilled,
+        other => panic!("expected a spilled message, got {:?}", other),
+    };
+    assert_eq!(spilled.message_type.as_deref(), Some("user"));
+    assert!(spilled.size > 5000);
+    assert!(spilled.path.starts_with(&spill_dir));
+
+    match spilled.load().unwrap() {
+        Message::User(user) => {
+            assert!(matches!(
+                user.content.as_deref(),
+                Some([ContentBlock::ToolResult(_)])
+            ));
+        }
+        other => panic!("expected a user message, got {:?}", other),
+    }
+    spilled.remove().unwrap();
+    std::fs::remove_dir_all(spill_dir).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_client_reports_unexpected_cli_exit() {
+    use claude_agent_sdk_rs::errors::ProcessError;
+    use claude_agent_sdk_rs::{ClaudeError, ProcessExited};
+
+    let (dir, cli_path) = common::fake_cli(
+        "exit",
+        r#"      echo "loading model" >&2
+      echo "fatal: out of tokens" >&2
+      exit 3"#,
+    );
+
+    let options = ClaudeAgentOptions::builder()
+        .cli_path(cli_path)
+        .skip_version_check(true)
+        .build();
+    let mut client = ClaudeClient::new(options);
+    client.connect().await.unwrap();
+    client.query("hello").await.unwrap();
+
+    let exit = {
+        let mut stream = client.receive_messages();
+        tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("should receive the exit report")
+    };
+    match exit {
+        Some(Ok(Message::ProcessExited(ProcessExited {
+            code,
+            signal,
+            stderr_tail,
+            limit,
+        }))) => {
+            assert_eq!(code, Some(3));
+            assert_eq!(signal, None);
+            assert_eq!(limit, None);
+            assert_eq!(stderr_tail, vec!["loading model", "fatal: out of tokens"]);
+        }
+        other => panic!("expected an exit report, got {:?}", other),
+    }
+
+    // Writing to the dead CLI reports its exit and stderr
+    match client.query("again").await {
+        Err(ClaudeError::Process(ProcessError {
+            exit_code, stderr, ..
+        })) => {
+            assert_eq!(exit_code, Some(3));
+            assert!(stderr.unwrap().contains("out of tokens"));
+        }
+        other => panic!("expected a process error, got {:?}", other),
+    }
+
+    client.disconnect().await.unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_subprocess_transport_enforces_cli_version_requirement() {
+    use claude_agent_sdk_rs::{ClaudeError, CliDiscovery, QueryPrompt, SubprocessTransport};
+
+    let dir = common::temp_dir("version");
+    let cli_path = common::write_cli(&dir, "#!/bin/sh\necho '2.0.12 (Claude Code)'\n");
+
+    let options = |requirement: &str| {
+        ClaudeAgentOptions::builder()
+            .cli_path(cli_path.clone())
+            .cli_discovery(
+                CliDiscovery::builder()
+                    .version_requirement(requirement.parse().unwrap())
+                    .build(),
+            )
+            .build()
+    };
+
+    assert!(SubprocessTransport::new(QueryPrompt::Streaming, options(">=2.0, <3")).is_ok());
+    match SubprocessTransport::new(QueryPrompt::Streaming, options(">=2.0.30")) {
+        Err(ClaudeError::CliNotFound(error)) => {
+            assert_eq!(error.candidates.len(), 1);
+            assert_eq!(error.candidates[0].path, cli_path);
+            assert_eq!(error.candidates[0].version.as_deref(), Some("2.0.12"));
+        }
+        Err(other) => panic!("expected CliNotFound, got {:?}", other),
+        Ok(_) => panic!("expected the requirement to reject the CLI"),
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_cli_discovery_runs_off_the_runtime() {
+    use claude_agent_sdk_rs::{CliDiscovery, QueryPrompt, SubprocessTransport};
+    use std::sync::atomic::AtomicUsize;
+
+    let dir = common::temp_dir("slow-version");
+    let cli_path = common::write_cli(&dir, "#!/bin/sh\nsleep 0.5\necho '2.0.12 (Claude Code)'\n");
+    let options = ClaudeAgentOptions::builder()
+        .cli_path(cli_path)
+        .cli_discovery(
+            CliDiscovery::builder()
+                .version_requirement(">=2.0".parse().unwrap())
+                .build(),
+        )
+        .build();
+
+    // The test runtime has a single thread, so the ticker only runs if
+    // discovery doesn't hold it
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let ticker = tokio::spawn({
+        let ticks = Arc::clone(&ticks);
+        async move {
+            loop {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                ticks.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    });
+    SubprocessTransport::new_async(QueryPrompt::Streaming, options)
+        .await
+        .unwrap();
+    ticker.abort();
+    assert!(ticks.load(Ordering::SeqCst) >= 5);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_resource_limits_apply_and_report_the_limit_hit() {
+    use claude_agent_sdk_rs::{ProcessExited, ResourceLimitKind, ResourceLimits};
+
+    let (dir, cli_path) = common::fake_cli(
+        "rlimit",
+        r#"      echo "nofile $(ulimit -n)" >&2
+      echo "core $(ulimit -c)" >&2
+      echo "nice $(cut -d' ' -f19 /proc/self/stat)" >&2
+      while :; do :; done"#,
+    );
+
+    let options = ClaudeAgentOptions::builder()
+        .cli_path(cli_path)
+        .skip_version_check(true)
+        .resource_limits(
+            ResourceLimits::builder()
+                .cpu_seconds(1)
+                .open_files(64)
+                .core_size(0)
+                .nice(5)
+                .build(),
+        )
+        .build();
+    let mut client = ClaudeClient::new(options);
+    client.connect().await.unwrap();
+    client.query("hello").await.unwrap();
+
+    let exit = {
+        let mut stream = client.receive_messages();
+        tokio::time::timeout(Duration::from_secs(10), stream.next())
+            .await
+            .expect("should receive the exit report")
+    };
+    match exit {
+        Some(Ok(Message::ProcessExited(ProcessExited {
+            signal,
+            stderr_tail,
+            limit,
+            ..
+        }))) => {
+            assert_eq!(signal, Some(libc::SIGXCPU));
+            assert_eq!(limit, Some(ResourceLimitKind::CpuTime));
+            assert_eq!(stderr_tail, vec!["nofile 64", "core 0", "nice 5"]);
+        }
+        other => panic!("expected an exit report, got {:?}", other),
+    }
+
+    client.disconnect().await.unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_env_policy_filters_inherited_vars_and_redacts_secrets() {
+    use claude_agent_sdk_rs::{EnvInheritance, EnvPolicy, ProcessExited};
+    use std::collections::HashMap;
+
+    let (dir, cli_path) = common::fake_cli(
+        "env",
+        r#"      echo "home=${HOME:-unset} path=${PATH:+set}" >&2
+      echo "token=$MY_TOKEN" >&2
+      exit 3"#,
+    );
+
+    let callback_lines = Arc::new(Mutex::new(Vec::new()));
+    let options = ClaudeAgentOptions::builder()
+        .cli_path(cli_path)
+        .skip_version_check(true)
+        .env(HashMap::from([(
+            "MY_TOKEN".to_string(),
+            "tok-abc123".to_string(),
+        )]))
+        .env_policy(
+            EnvPolicy::builder()
+                .inherit(EnvInheritance::Allowlist(vec!["PATH".to_string()]))
+                .redact(vec!["MY_*".to_string()])
+                .build(),
+        )
+        .stderr_callback({
+            let callback_lines = Arc::clone(&callback_lines);
+            Arc::new(move |line: String| callback_lines.lock().unwrap().push(line))
+        })
+        .build();
+    let mut client = ClaudeClient::new(options);
+    client.connect().await.unwrap();
+    client.query("hello").await.unwrap();
+
+    let exit = {
+        let mut stream = client.receive_messages();
+        tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("should receive the exit report")
+    };
+    match exit {
+        Some(Ok(Message::ProcessExited(ProcessExited { stderr_tail, .. }))) => {
+            assert_eq!(stderr_tail, vec!["home=unset path=set", "token=[REDACTED]"]);
+        }
+        other => panic!("expected an exit report, got {:?}", other),
+    }
+    assert_eq!(
+        *callback_lines.lock().unwrap(),
+        vec!["home=unset path=set\n", "token=[REDACTED]\n"]
+    );
+
+    client.disconnect().await.unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_capabilities_gate_options_and_calls_on_old_cli() {
+    use claude_agent_sdk_rs::{Capability, ClaudeError, SdkPluginConfig};
+
+    let transport = Arc::new(LoopbackTransport::with_cli_version("2.0.10"));
+    let mut client = ClaudeClient::with_transport(
+        Arc::clone(&transport) as Arc<dyn Transport>,
+        ClaudeAgentOptions::builder()
+            .plugins(vec![SdkPluginConfig::local("./plugins/demo")])
+            .build(),
+    );
+    let err = client.connect().await.unwrap_err();
+    assert!(matches!(
+        &err,
+        ClaudeError::UnsupportedCapability(e) if e.capability == Capability::Plugins
+    ));
+    assert!(err.to_string().contains("2.0.12"), "{}", err);
+    // The rejected connection is closed and its reader stopped
+    assert!(!transport.is_ready());
+    tokio::time::timeout(Duration::from_secs(2), async {
+        while Arc::strong_count(&transport) > 1 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("reader still holds the transport");
+
+    let transport = Arc::new(LoopbackTransport::with_cli_version("2.0.10"));
+    let mut client = ClaudeClient::with_transport(
+        Arc::clone(&transport) as Arc<dyn Transport>,
+        ClaudeAgentOptions::default(),
+    );
+    client.connect().await.unwrap();
+
+    let capabilities = client.capabilities().unwrap();
+    assert_eq!(capabilities.cli_version(), Some("2.0.10"));
+    assert!(!capabilities.supports(Capability::FileCheckpointing));
+
+    let err = client.rewind_files("user-1").await.unwrap_err();
+    assert!(matches!(err, ClaudeError::UnsupportedCapability(_)));
+    assert!(
+        transport
+            .written()
+            .iter()
+            .all(|m| m["request"]["subtype"] != "rewind_files")
+    );
+
+    client.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_interrupt_answered_while_message_queue_is_full() {
+    let transport = Arc::new(LoopbackTransport::new());
+    let mut client = ClaudeClient::with_transport(
+        Arc::clone(&transport) as Arc<dyn Transport>,
+        ClaudeAgentOptions::builder()
+            .message_queue_capacity(1)
+            .build(),
+    );
+    client.connect().await.unwrap();
+
+    // Nobody consumes, so the queue fills up and later messages are staged behind it
+    for i in 0..3 {
+        transport.emit(json!({
+            "type": "assistant",
+            "message": {"content": [{"type": "text", "text": format!("msg {}", i)}], "model": "test"},
+            "session_id": "loopback"
+        }));
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(client.queue_metrics().unwrap().depth, 3);
+
+    // The loopback never answers interrupts; answer this one by hand
+    let answer = {
+        let transport = Arc::clone(&transport);
+        tokio::spawn(async move {
+            loop {
+                if let Some(request) = transport
+                    .written()
+                    .into_iter()
+                    .find(|m| m["request"]["subtype"] == "interrupt")
+                {
+                    transport.emit(json!({
+                        "type": "control_response",
+                        "response": {
+                            "subtype": "success",
+                            "request_id": request["request_id"],
+                            "response": {}
+                        }
+                    }));
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+    };
+    tokio::time::timeout(Duration::from_secs(2), client.interrupt())
+        .await
+        .expect("interrupt stalled behind the full queue")
+        .unwrap();
+    answer.await.unwrap();
+
+    // Every message is still delivered once the consumer catches up
+    let mut stream = client.receive_messages();
+    for i in 0..3 {
+        let message = tokio::time::timeout(Duration::from_secs(2), stream.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let Message::Assistant(assistant) = message else {
+            panic!("expected an assistant message");
+        };
+        assert!(format!("{:?}", assistant.message.content).contains(&format!("msg {}", i)));
+    }
+    drop(stream);
+
+    client.disconnect().await.unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_blocked_queue_holds_back_cli_output() {
+    // Records how many messages the CLI has managed to write so far
+    let (dir, cli_path) = common::FakeCli::new(
+        r#"      i=0
+      while [ $i -lt 2000 ]; do
+        printf '{"type":"assistant","message":{"content":[{"type":"text","text":"%s"}],"model":"test"}}\n' "$pad"
+        i=$((i + 1))
+        echo $i > "$0.progress"
+      done
+      printf '{"type":"result","subtype":"success","duration_ms":1,"duration_api_ms":1,"is_error":false,"num_turns":1,"session_id":"s"}\n'"#,
+    )
+    .setup(r#"pad=$(head -c 1000 /dev/zero | tr '\0' 'p')"#)
+    .write("backpressure");
+    let progress_path = dir.join("claude.progress");
+    let progress = || {
+        std::fs::read_to_string(&progress_path)
+            .ok()
+            .and_then(|s| s.trim().parse::<usize>().ok())
+            .unwrap_or(0)
+    };
+
+    let options = ClaudeAgentOptions::builder()
+        .cli_path(cli_path)
+        .skip_version_check(true)
+        .message_queue_capacity(4)
+        .overflow_policy(OverflowPolicy::Block)
+        .build();
+    let mut client = ClaudeClient::new(options);
+    client.connect().await.unwrap();
+    client.query("hello").await.unwrap();
+
+    // Nobody consumes, so the CLI stalls once the pipe and both buffers are full
+    let mut stalled_at = 0;
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let now = progress();
+        if now > 0 && now == stalled_at {
+            break;
+        }
+        stalled_at = now;
+    }
+    assert!(
+        stalled_at > 0 && stalled_at < 2000,
+        "CLI wrote {}",
+        stalled_at
+    );
+    let metrics = client.queue_metrics().unwrap();
+    // The queue, staging, and one message each held by the forwarder and the reader
+    assert!(metrics.depth <= 10, "{:?}", metrics);
+    assert!(metrics.blocked_sends > 0);
+
+    // Consuming lets the CLI finish
+    let count = tokio::time::timeout(Duration::from_secs(10), client.receive_response().count())
+        .await
+        .expect("CLI should finish once the consumer catches up");
+    assert_eq!(count, 2001);
+    assert_eq!(progress(), 2000);
+
+    client.disconnect().await.unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_oversized_messages_do_not_end_the_session() {
+    // Logs its stdin so the test can see how the SDK answered
+    let (dir, cli_path) = common::FakeCli::new(
+        r#"      printf '{"type":"user","message":{"role":"user","content":"%s"}}\n' "$big"
+      printf '{"type":"control_request","request_id":"big_1","request":{"subtype":"can_use_tool","tool_name":"Write","input":{"content":"%s"}}}\n' "$big"
+      printf '{"type":"assistant","message":{"content":[{"type":"text","text":"still here"}],"model":"test"}}\n'
+      printf '{"type":"result","subtype":"success","duration_ms":1,"duration_api_ms":1,"is_error":false,"num_turns":1,"session_id":"s"}\n'"#,
+    )
+    .setup(r#"big=$(head -c 5000 /dev/zero | tr '\0' 'y')"#)
+    .on_line(r#"  printf '%s\n' "$line" >> "$0.log""#)
+    .write("oversized");
+
+    let options = ClaudeAgentOptions::builder()
+        .cli_path(cli_path.clone())
+        .skip_version_check(true)
+        .max_buffer_size(1024)
+        .build();
+    let mut client = ClaudeClient::new(options);
+    client.connect().await.unwrap();
+    client.query("hello").await.unwrap();
+
+    let items: Vec<_> = {
+        let stream = client.receive_response();
+        tokio::time::timeout(Duration::from_secs(5), stream.collect())
+            .await
+            .expect("session should survive oversized messages")
+    };
+    assert_eq!(items.len(), 4, "{:?}", items);
+    assert!(items[..2].iter().all(|item| item.is_err()));
+    assert!(matches!(items[2], Ok(Message::Assistant(_))));
+    assert!(matches!(items[3], Ok(Message::Result(_))));
+
+    // The oversized control request was rejected instead of left unanswered
+    let log_path = dir.join("claude.log");
+    let rejection = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let log = std::fs::read_to_string(&log_path).unwrap_or_default();
+            if let Some(line) = log.lines().find(|line| line.contains("big_1")) {
+                return serde_json::from_str::<serde_json::Value>(line).unwrap();
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(rejection["response"]["subtype"], "error");
+    assert!(
+        rejection["response"]["error"]
+            .as_str()
+            .unwrap()
+            .contains("exceeds maximum of 1024 bytes")
+    );
+
+    client.disconnect().await.unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}